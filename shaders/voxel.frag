@@ -11,11 +11,12 @@ uniform sampler2D blockTexture;
 uniform vec3 viewPos;
 uniform vec3 lightDir;
 uniform bool isWater;
+// Sky haze, or the tint of the fluid the camera is in
+uniform vec3 fogColor;
+uniform float fogStart;
+uniform float fogEnd;
 
 const float ambientStrength = 0.4;
-const vec3 skyColor = vec3(0.53, 0.81, 0.92);
-const float fogStart = 80.0;
-const float fogEnd = 200.0;
 
 void main() {
     // Sample texture
@@ -47,7 +48,7 @@ void main() {
     // Atmospheric fog
     float distance = length(viewPos - FragPos);
     float fogFactor = clamp((fogEnd - distance) / (fogEnd - fogStart), 0.0, 1.0);
-    result = mix(skyColor, result, fogFactor);
+    result = mix(fogColor, result, fogFactor);
     
     // Alpha
     float alpha = texColor.a;
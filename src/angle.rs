@@ -0,0 +1,78 @@
+// Angles for world generation, as whole steps of a turn. std's sin, cos and
+// atan2 come from the platform's maths library and may round differently on
+// another platform, which would change generated worlds. The sines here come
+// from a table the compiler fills in with plain arithmetic, so every build
+// gets the same bits.
+
+// Steps in a full turn
+pub const TURN: i32 = 256;
+
+const QUARTER: usize = TURN as usize / 4;
+
+// Sine of 0..=QUARTER steps
+const SINES: [f64; QUARTER + 1] = quarter_sines();
+
+// Taylor series; for angles up to a quarter turn twelve terms are well below
+// f64 precision
+const fn quarter_sines() -> [f64; QUARTER + 1] {
+    let mut sines = [0.0; QUARTER + 1];
+    let mut i = 0;
+    while i <= QUARTER {
+        let x = i as f64 / TURN as f64 * std::f64::consts::TAU;
+        let mut term = x;
+        let mut sum = 0.0;
+        let mut n = 1;
+        while n < 24 {
+            sum += term;
+            term = -term * x * x / ((n + 1) * (n + 2)) as f64;
+            n += 2;
+        }
+        sines[i] = sum;
+        i += 1;
+    }
+    // Rounding leaves the last one a bit short
+    sines[QUARTER] = 1.0;
+    sines
+}
+
+pub fn sin(step: i32) -> f64 {
+    let step = step.rem_euclid(TURN) as usize;
+    match step / QUARTER {
+        0 => SINES[step],
+        1 => SINES[2 * QUARTER - step],
+        2 => -SINES[step - 2 * QUARTER],
+        _ => -SINES[4 * QUARTER - step],
+    }
+}
+
+pub fn cos(step: i32) -> f64 {
+    sin(step + TURN / 4)
+}
+
+// The nearest whole step
+pub fn from_degrees(degrees: f64) -> i32 {
+    (degrees / 360.0 * TURN as f64).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_matches_std_closely() {
+        for step in -TURN..2 * TURN {
+            let radians = step as f64 / TURN as f64 * std::f64::consts::TAU;
+            assert!((sin(step) - radians.sin()).abs() < 1e-12, "sin({})", step);
+            assert!((cos(step) - radians.cos()).abs() < 1e-12, "cos({})", step);
+        }
+    }
+
+    #[test]
+    fn quarter_turns_are_exact() {
+        assert_eq!(sin(0), 0.0);
+        assert_eq!(sin(TURN / 4), 1.0);
+        assert_eq!(cos(TURN / 2), -1.0);
+        assert_eq!(from_degrees(90.0), TURN / 4);
+        assert_eq!(from_degrees(-45.0), -TURN / 8);
+    }
+}
@@ -0,0 +1,316 @@
+// Headless world preview: generates a rectangle of chunks and writes a
+// top-down PNG map, plus an optional side-on slice at a fixed Z.
+//
+//   cargo run --release --bin worldmap -- --radius 24 --out map.png
+//   cargo run --release --bin worldmap -- --biomes --slice-z 40
+//   cargo run --release --bin worldmap -- --dimension sky
+
+use image::{Rgb, RgbImage};
+use std::env;
+use std::process;
+use voxel_engine::biome::Biome;
+use voxel_engine::block::BlockType;
+use voxel_engine::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, HeightmapKind};
+use voxel_engine::dimension::{ChunkGenerator, DimensionId};
+use voxel_engine::sky_islands::SkyIslandGenerator;
+use voxel_engine::terrain::TerrainGenerator;
+use voxel_engine::terrain_config::TerrainConfig;
+
+const USAGE: &str = "\
+Usage: worldmap [options]
+  --seed N            world seed (default 12345, same as the game)
+  --config PATH       terrain config file (default terrain.toml)
+  --dimension NAME    overworld or sky (default overworld)
+  --center X,Z        centre chunk of the map (default 0,0)
+  --radius N          chunks in each direction from the centre (default 16)
+  --out PATH          map image (default world_map.png)
+  --biomes            colour the map by biome instead of by block
+  --slice-z Z         also render a vertical cross-section at world Z
+  --slice-out PATH    cross-section image (default world_slice.png)";
+
+const SKY: Rgb<u8> = Rgb([135, 206, 235]);
+
+struct Options {
+    seed: u32,
+    config: String,
+    dimension: DimensionId,
+    center: (i32, i32),
+    radius: i32,
+    out: String,
+    biomes: bool,
+    slice_z: Option<i32>,
+    slice_out: String,
+}
+
+// What the map needs to know about one column
+#[derive(Clone, Copy)]
+struct ColumnSample {
+    height: i32,
+    block: BlockType,
+    water_depth: i32,
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+
+    let config = TerrainConfig::load_or_default(&options.config);
+    let terrain_gen = TerrainGenerator::new(options.seed, config.clone());
+    let sky_gen = SkyIslandGenerator::new(options.seed, config);
+    let generator: &dyn ChunkGenerator = match options.dimension {
+        DimensionId::Overworld => &terrain_gen,
+        DimensionId::Sky => &sky_gen,
+    };
+
+    let min_cx = options.center.0 - options.radius;
+    let min_cz = options.center.1 - options.radius;
+    let chunks = options.radius * 2 + 1;
+    let size = (chunks * CHUNK_SIZE as i32) as usize;
+
+    println!(
+        "Generating {}x{} chunks around chunk ({}, {})...",
+        chunks, chunks, options.center.0, options.center.1
+    );
+    let mut samples = vec![
+        ColumnSample {
+            height: 0,
+            block: BlockType::Air,
+            water_depth: 0,
+        };
+        size * size
+    ];
+    for cz in 0..chunks {
+        for cx in 0..chunks {
+            let chunk = generator.generate_chunk(min_cx + cx, min_cz + cz);
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let px = cx as usize * CHUNK_SIZE + x;
+                    let pz = cz as usize * CHUNK_SIZE + z;
+                    let top = chunk.height(HeightmapKind::Surface, x, z);
+                    samples[pz * size + px] = sample_column(top, |y| {
+                        chunk.get_block(x, y, z).map(|block| block.block_type)
+                    });
+                }
+            }
+        }
+        println!("  row {}/{}", cz + 1, chunks);
+    }
+
+    let min_x = min_cx * CHUNK_SIZE as i32;
+    let min_z = min_cz * CHUNK_SIZE as i32;
+    let mut map = RgbImage::new(size as u32, size as u32);
+    for pz in 0..size {
+        for px in 0..size {
+            let sample = samples[pz * size + px];
+            // Light from the north-west: compare against that neighbour
+            let neighbour = if px > 0 && pz > 0 {
+                samples[(pz - 1) * size + px - 1].height
+            } else {
+                sample.height
+            };
+
+            let base = if options.biomes {
+                biome_color(terrain_gen.biome_at(min_x + px as i32, min_z + pz as i32))
+            } else {
+                block_color(sample.block)
+            };
+            map.put_pixel(px as u32, pz as u32, shade(base, sample, neighbour));
+        }
+    }
+    save(&map, &options.out);
+
+    if let Some(slice_z) = options.slice_z {
+        let slice = render_slice(generator, min_cx, chunks, slice_z);
+        save(&slice, &options.slice_out);
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        seed: 12345,
+        config: "terrain.toml".to_string(),
+        dimension: DimensionId::Overworld,
+        center: (0, 0),
+        radius: 16,
+        out: "world_map.png".to_string(),
+        biomes: false,
+        slice_z: None,
+        slice_out: "world_slice.png".to_string(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--seed" => options.seed = parse_number(&value()?)?,
+            "--config" => options.config = value()?,
+            "--dimension" => {
+                let name = value()?;
+                options.dimension =
+                    DimensionId::from_name(&name).ok_or(format!("unknown dimension {}", name))?;
+            }
+            "--center" => {
+                let text = value()?;
+                let (x, z) = text
+                    .split_once(',')
+                    .ok_or(format!("expected X,Z, got {}", text))?;
+                options.center = (parse_number(x)?, parse_number(z)?);
+            }
+            "--radius" => options.radius = parse_number(&value()?)?,
+            "--out" => options.out = value()?,
+            "--biomes" => options.biomes = true,
+            "--slice-z" => options.slice_z = Some(parse_number(&value()?)?),
+            "--slice-out" => options.slice_out = value()?,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    if options.radius < 0 {
+        return Err("--radius must not be negative".to_string());
+    }
+    if options.biomes && options.dimension != DimensionId::Overworld {
+        return Err("--biomes only works for the overworld".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("invalid number: {}", text))
+}
+
+// Finds the highest non-air block, looking through water to the floor.
+// top is the column's surface height, where the search starts.
+fn sample_column(
+    top: Option<usize>,
+    block_at: impl Fn(usize) -> Option<BlockType>,
+) -> ColumnSample {
+    let mut water_depth = 0;
+    let start = top.map_or(0, |top| top + 1);
+    for y in (0..start).rev() {
+        match block_at(y) {
+            Some(BlockType::Air) | None => continue,
+            Some(BlockType::Water) => water_depth += 1,
+            Some(block) => {
+                return ColumnSample {
+                    height: y as i32,
+                    block,
+                    water_depth,
+                };
+            }
+        }
+    }
+    ColumnSample {
+        height: 0,
+        block: BlockType::Air,
+        water_depth,
+    }
+}
+
+// Relief shading by height and slope; water is tinted by its depth
+fn shade(base: Rgb<u8>, sample: ColumnSample, neighbour_height: i32) -> Rgb<u8> {
+    let altitude = 0.75 + sample.height as f32 / CHUNK_HEIGHT as f32 * 0.5;
+    let slope = 1.0 + (sample.height - neighbour_height).clamp(-4, 4) as f32 * 0.06;
+    let land = scale(base, altitude * slope);
+
+    if sample.water_depth == 0 {
+        return land;
+    }
+    let depth = (sample.water_depth as f32 / 12.0).min(1.0);
+    let water = Rgb([40, 90, 200]);
+    mix(land, water, 0.55 + depth * 0.4)
+}
+
+fn block_color(block: BlockType) -> Rgb<u8> {
+    match block {
+        BlockType::Grass => Rgb([86, 170, 48]),
+        BlockType::Dirt => Rgb([139, 90, 43]),
+        BlockType::Stone => Rgb([125, 125, 125]),
+        BlockType::Sand => Rgb([219, 207, 163]),
+        BlockType::Gravel => Rgb([136, 126, 126]),
+        BlockType::Water => Rgb([40, 90, 200]),
+        BlockType::OakLog | BlockType::BirchLog | BlockType::SpruceLog => Rgb([102, 81, 60]),
+        BlockType::Leaves => Rgb([48, 130, 40]),
+        BlockType::BirchLeaves => Rgb([110, 150, 60]),
+        BlockType::SpruceLeaves => Rgb([52, 92, 60]),
+        BlockType::Cactus => Rgb([58, 130, 44]),
+        BlockType::TallGrass => Rgb([92, 160, 48]),
+        BlockType::Dandelion => Rgb([240, 220, 40]),
+        BlockType::Poppy => Rgb([200, 30, 30]),
+        BlockType::OakSapling | BlockType::BirchSapling | BlockType::SpruceSapling => {
+            Rgb([60, 140, 40])
+        }
+        BlockType::Wheat => Rgb([200, 176, 70]),
+        BlockType::Planks => Rgb([176, 138, 86]),
+        BlockType::Door => Rgb([128, 92, 52]),
+        BlockType::Cobblestone => Rgb([96, 96, 96]),
+        BlockType::Snow | BlockType::SnowLayer => Rgb([240, 244, 250]),
+        BlockType::Ice => Rgb([160, 196, 240]),
+        // Only seen where a column is empty all the way down
+        BlockType::Air => Rgb([20, 24, 40]),
+    }
+}
+
+fn biome_color(biome: Biome) -> Rgb<u8> {
+    match biome {
+        Biome::Ocean => Rgb([30, 60, 160]),
+        Biome::Beach => Rgb([230, 215, 160]),
+        Biome::Plains => Rgb([140, 190, 80]),
+        Biome::Forest => Rgb([40, 120, 40]),
+        Biome::Desert => Rgb([230, 190, 100]),
+        Biome::Taiga => Rgb([60, 110, 90]),
+        Biome::Tundra => Rgb([200, 215, 220]),
+        Biome::Mountains => Rgb([140, 130, 120]),
+    }
+}
+
+// Side view of one Z row across the whole map width, sky at the top
+fn render_slice(generator: &dyn ChunkGenerator, min_cx: i32, chunks: i32, z: i32) -> RgbImage {
+    let width = (chunks * CHUNK_SIZE as i32) as u32;
+    let mut slice = RgbImage::from_pixel(width, CHUNK_HEIGHT as u32, SKY);
+    let cz = z.div_euclid(CHUNK_SIZE as i32);
+    let local_z = z.rem_euclid(CHUNK_SIZE as i32) as usize;
+
+    for cx in 0..chunks {
+        let chunk = generator.generate_chunk(min_cx + cx, cz);
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_HEIGHT {
+                let block = chunk.get_block(x, y, local_z).unwrap().block_type;
+                if block == BlockType::Air {
+                    continue;
+                }
+                let px = cx as u32 * CHUNK_SIZE as u32 + x as u32;
+                let py = (CHUNK_HEIGHT - 1 - y) as u32;
+                slice.put_pixel(px, py, block_color(block));
+            }
+        }
+    }
+    slice
+}
+
+fn scale(color: Rgb<u8>, factor: f32) -> Rgb<u8> {
+    Rgb(color.0.map(|c| (c as f32 * factor).clamp(0.0, 255.0) as u8))
+}
+
+fn mix(a: Rgb<u8>, b: Rgb<u8>, t: f32) -> Rgb<u8> {
+    Rgb(std::array::from_fn(|i| {
+        (a.0[i] as f32 * (1.0 - t) + b.0[i] as f32 * t) as u8
+    }))
+}
+
+fn save(image: &RgbImage, path: &str) {
+    match image.save(path) {
+        Ok(()) => println!("Wrote {}", path),
+        Err(err) => {
+            eprintln!("Failed to write {}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
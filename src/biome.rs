@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+// Broad climate zones, picked from temperature, humidity and terrain height
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Plains,
+    Forest,
+    Desert,
+    Taiga,
+    Tundra,
+    Mountains,
+}
+
+// Height of a mountain biome above sea level
+const MOUNTAIN_HEIGHT: usize = 35;
+
+impl Biome {
+    // temperature and humidity are noise values in roughly -1..1,
+    // surface is the y of the topmost terrain block
+    pub fn classify(temperature: f64, humidity: f64, surface: usize, sea_level: usize) -> Self {
+        if surface < sea_level {
+            return Biome::Ocean;
+        }
+        if surface <= sea_level + 2 {
+            return Biome::Beach;
+        }
+        if surface >= sea_level + MOUNTAIN_HEIGHT {
+            return Biome::Mountains;
+        }
+
+        if temperature < -0.3 {
+            if humidity > 0.0 {
+                Biome::Taiga
+            } else {
+                Biome::Tundra
+            }
+        } else if temperature > 0.3 && humidity < -0.1 {
+            Biome::Desert
+        } else if humidity > 0.2 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub fn is_land(&self) -> bool {
+        !matches!(self, Biome::Ocean)
+    }
+}
@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
+
 // Block types for the voxel engine
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BlockType {
     Air,
     Dirt,
@@ -8,35 +10,164 @@ pub enum BlockType {
     Water,
     OakLog,
     Leaves,
+    Sand,
+    Gravel,
+    Planks,
+    Cobblestone,
+    BirchLog,
+    BirchLeaves,
+    SpruceLog,
+    SpruceLeaves,
+    Cactus,
+    TallGrass,
+    Dandelion,
+    Poppy,
+    Snow,
+    // Thin sheet of snow lying on top of another block
+    SnowLayer,
+    Ice,
+    OakSapling,
+    BirchSapling,
+    SpruceSapling,
+    // Grows through CROP_STAGES stages, kept in the block's level
+    Wheat,
+    // A wooden door, open when its level is DOOR_OPEN
+    Door,
 }
 
+// Growth stages of a crop, from just planted to ripe
+pub const CROP_STAGES: u8 = 8;
+// Level bit of leaves placed by a player, which never decay
+pub const PERSISTENT_LEAVES: u8 = 0x80;
+// Level bits of leaves holding their distance to the nearest log. 0 until
+// it is worked out, as for generated leaves.
+pub const LEAF_DISTANCE: u8 = 0x7F;
+// Level of a door that stands open
+pub const DOOR_OPEN: u8 = 1;
+
 // Block with properties
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub block_type: BlockType,
+    // For fluids: 0 is a source, 1 to 7 is flowing water ever further from
+    // one. For crops the growth stage, for leaves their distance to a log
+    // and PERSISTENT_LEAVES when a player placed them and for doors
+    // DOOR_OPEN when open. 0 for other blocks.
+    #[serde(default)]
+    pub level: u8,
 }
 
 impl Block {
     pub fn new(block_type: BlockType) -> Self {
-        Self { block_type }
+        Self {
+            block_type,
+            level: 0,
+        }
+    }
+
+    // Water that ran level blocks from its source
+    pub fn flowing_water(level: u8) -> Self {
+        Self {
+            block_type: BlockType::Water,
+            level,
+        }
     }
 
     pub fn is_solid(&self) -> bool {
-        !matches!(self.block_type, BlockType::Air | BlockType::Water)
+        !matches!(
+            self.block_type,
+            BlockType::Air | BlockType::Water | BlockType::SnowLayer
+        ) && !self.is_cross()
+            && !self.is_open_door()
     }
 
     pub fn is_transparent(&self) -> bool {
         matches!(
             self.block_type,
-            BlockType::Air | BlockType::Water | BlockType::Leaves
+            BlockType::Air | BlockType::Water | BlockType::SnowLayer
+        ) || self.is_leaves()
+            || self.is_cross()
+            || self.block_type == BlockType::Door
+    }
+
+    pub fn is_leaves(&self) -> bool {
+        matches!(
+            self.block_type,
+            BlockType::Leaves | BlockType::BirchLeaves | BlockType::SpruceLeaves
+        )
+    }
+
+    pub fn is_persistent_leaves(&self) -> bool {
+        self.is_leaves() && self.level & PERSISTENT_LEAVES != 0
+    }
+
+    // Steps from these leaves to the nearest log, if worked out yet
+    pub fn leaf_distance(&self) -> Option<u8> {
+        let distance = self.level & LEAF_DISTANCE;
+        (distance > 0).then_some(distance)
+    }
+
+    // The same leaves at another distance from a log
+    pub fn with_leaf_distance(self, distance: u8) -> Self {
+        Self {
+            level: self.level & PERSISTENT_LEAVES | distance.min(LEAF_DISTANCE),
+            ..self
+        }
+    }
+
+    // The block as a player places it
+    pub fn placed(block_type: BlockType) -> Self {
+        let mut block = Self::new(block_type);
+        if block.is_leaves() {
+            block.level = PERSISTENT_LEAVES;
+        }
+        block
+    }
+
+    pub fn is_open_door(&self) -> bool {
+        self.block_type == BlockType::Door && self.level == DOOR_OPEN
+    }
+
+    // The same door swung the other way
+    pub fn toggle_door(self) -> Self {
+        let level = if self.is_open_door() { 0 } else { DOOR_OPEN };
+        Self { level, ..self }
+    }
+
+    pub fn is_log(&self) -> bool {
+        matches!(
+            self.block_type,
+            BlockType::OakLog | BlockType::BirchLog | BlockType::SpruceLog
+        )
+    }
+
+    // Falls when there is nothing solid under it
+    pub fn has_gravity(&self) -> bool {
+        matches!(self.block_type, BlockType::Sand | BlockType::Gravel)
+    }
+
+    // A fluid can flow into it, washing it away
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self.block_type, BlockType::Air | BlockType::SnowLayer) || self.is_cross()
+    }
+
+    // Plants drawn as two crossed quads instead of a cube
+    pub fn is_cross(&self) -> bool {
+        matches!(
+            self.block_type,
+            BlockType::TallGrass
+                | BlockType::Dandelion
+                | BlockType::Poppy
+                | BlockType::OakSapling
+                | BlockType::BirchSapling
+                | BlockType::SpruceSapling
+                | BlockType::Wheat
         )
     }
 }
 
 impl Default for Block {
     fn default() -> Self {
-        Self {
-            block_type: BlockType::Air,
-        }
+        Self::new(BlockType::Air)
     }
 }
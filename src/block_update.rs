@@ -0,0 +1,200 @@
+// How blocks react to the world around them. There are three ways a block
+// gets to act:
+// - a neighbour update, right after a block touching it changed
+// - a scheduled tick, asked for some number of game ticks ahead
+// - a random tick, given to a few random blocks of every chunk section each
+//   game tick, for slow changes like plants growing
+use crate::block::{Block, BlockType};
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::entity::{Entity, EntityKind};
+use crate::fluid::{self, WATER_FLOW_DELAY};
+use crate::growth;
+use crate::rng::{ChunkRng, SALT_GROWTH};
+use crate::world::{BlockPos, World};
+use glam::Vec3;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+
+// Game ticks before an unsupported sand or gravel block starts to fall
+pub const FALL_DELAY: u64 = 2;
+// Random ticks per chunk section per game tick, unless the level says
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+// Chunks are split into cubes this tall for random ticks
+pub const SECTION_HEIGHT: usize = 16;
+
+// Which of the ticks due in the same game tick run first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TickPriority {
+    High,
+    Normal,
+    Low,
+}
+
+// Scheduled ticks in the order they are due: by game tick, then priority,
+// then the order they were asked for. A position waits for at most one.
+#[derive(Default)]
+pub struct TickQueue {
+    queue: BinaryHeap<Reverse<(u64, TickPriority, u64, BlockPos)>>,
+    pending: HashSet<BlockPos>,
+    scheduled: u64,
+}
+
+impl TickQueue {
+    // False if the position already waits for a tick
+    pub fn schedule(&mut self, pos: BlockPos, due: u64, priority: TickPriority) -> bool {
+        if !self.pending.insert(pos) {
+            return false;
+        }
+        self.scheduled += 1;
+        self.queue
+            .push(Reverse((due, priority, self.scheduled, pos)));
+        true
+    }
+
+    pub fn is_scheduled(&self, pos: BlockPos) -> bool {
+        self.pending.contains(&pos)
+    }
+
+    // The next position due by the given game tick
+    pub fn pop_due(&mut self, now: u64) -> Option<BlockPos> {
+        let Reverse((due, _, _, pos)) = *self.queue.peek()?;
+        if due > now {
+            return None;
+        }
+        self.queue.pop();
+        self.pending.remove(&pos);
+        Some(pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.pending.clear();
+    }
+}
+
+// The block at pos, or one next to it, changed
+pub fn neighbour_changed(world: &mut World, pos: BlockPos) {
+    let (x, y, z) = pos;
+    let Some(block) = world.get_block(x, y, z) else {
+        return;
+    };
+    if block.block_type == BlockType::Water {
+        world.schedule_tick(pos, WATER_FLOW_DELAY, TickPriority::Normal);
+    }
+    if block.has_gravity() && !is_supported(world, pos) {
+        world.schedule_tick(pos, FALL_DELAY, TickPriority::Normal);
+    }
+    if block.is_leaves() {
+        growth::leaves_changed(world, pos, block);
+    }
+}
+
+// A tick this position asked for is due
+pub fn scheduled_tick(world: &mut World, pos: BlockPos) {
+    let (x, y, z) = pos;
+    let Some(block) = world.get_block(x, y, z) else {
+        return;
+    };
+    if block.block_type == BlockType::Water {
+        fluid::flow(world, pos);
+    } else if block.has_gravity() && !is_supported(world, pos) {
+        // Taking it out updates the block above, which falls next
+        world.set_block(x, y, z, Block::new(BlockType::Air));
+        let position = Vec3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5);
+        world.spawn_entity(Entity::new(
+            EntityKind::FallingBlock(block.block_type),
+            position,
+        ));
+    }
+}
+
+// The block at this position was picked for a random tick
+pub fn random_tick(world: &mut World, pos: BlockPos) {
+    let (x, y, z) = pos;
+    let Some(block) = world.get_block(x, y, z) else {
+        return;
+    };
+    let salt = SALT_GROWTH.wrapping_add(y as u32);
+    let mut rng = ChunkRng::at_tick(world.seed, world.game_tick(), x, z, salt);
+    match block.block_type {
+        BlockType::Grass => growth::grass(world, pos, &mut rng),
+        BlockType::OakSapling | BlockType::BirchSapling | BlockType::SpruceSapling => {
+            growth::sapling(world, pos, block, &mut rng);
+        }
+        BlockType::Wheat => growth::crop(world, pos, block, &mut rng),
+        _ if block.is_leaves() => growth::leaves(world, pos, block, &mut rng),
+        _ => {}
+    }
+}
+
+// Positions in a chunk, in chunk-local x and z, to give random ticks to
+// this game tick: count of them in every section
+pub fn random_tick_positions(rng: &mut ChunkRng, count: u32) -> Vec<(usize, usize, usize)> {
+    let mut positions = Vec::new();
+    for section in 0..CHUNK_HEIGHT / SECTION_HEIGHT {
+        for _ in 0..count {
+            let x = rng.below(CHUNK_SIZE as u32) as usize;
+            let y = section * SECTION_HEIGHT + rng.below(SECTION_HEIGHT as u32) as usize;
+            let z = rng.below(CHUNK_SIZE as u32) as usize;
+            positions.push((x, y, z));
+        }
+    }
+    positions
+}
+
+// Something solid, or the bottom of the world, holds the block up
+fn is_supported(world: &World, (x, y, z): BlockPos) -> bool {
+    world
+        .get_block(x, y - 1, z)
+        .is_none_or(|below| below.is_solid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_run_by_due_time_then_priority() {
+        let mut queue = TickQueue::default();
+        queue.schedule((0, 0, 0), 5, TickPriority::Low);
+        queue.schedule((1, 0, 0), 5, TickPriority::High);
+        queue.schedule((2, 0, 0), 3, TickPriority::Low);
+        queue.schedule((3, 0, 0), 5, TickPriority::Low);
+
+        assert_eq!(queue.pop_due(2), None);
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop_due(5)).collect();
+        assert_eq!(order, vec![(2, 0, 0), (1, 0, 0), (0, 0, 0), (3, 0, 0)]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn a_position_waits_for_one_tick_at_a_time() {
+        let mut queue = TickQueue::default();
+        assert!(queue.schedule((4, 5, 6), 10, TickPriority::Normal));
+        assert!(!queue.schedule((4, 5, 6), 2, TickPriority::High));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop_due(10), Some((4, 5, 6)));
+        assert!(!queue.is_scheduled((4, 5, 6)));
+        assert!(queue.schedule((4, 5, 6), 12, TickPriority::Normal));
+    }
+
+    #[test]
+    fn random_ticks_cover_every_section() {
+        let mut rng = ChunkRng::new(7, 0, 0, 0);
+        let positions = random_tick_positions(&mut rng, 3);
+        assert_eq!(positions.len(), 3 * CHUNK_HEIGHT / SECTION_HEIGHT);
+        for (i, &(x, y, z)) in positions.iter().enumerate() {
+            assert!(x < CHUNK_SIZE && z < CHUNK_SIZE);
+            assert_eq!(y / SECTION_HEIGHT, i / 3);
+        }
+        assert!(random_tick_positions(&mut rng, 0).is_empty());
+    }
+}
@@ -1,12 +1,46 @@
 use crate::block::{Block, BlockType};
+use crate::entity::Entity;
 
 pub const CHUNK_SIZE: usize = 16;
 pub const CHUNK_HEIGHT: usize = 256;
 
+// Which blocks a heightmap looks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightmapKind {
+    // Blocks you can stand on
+    Solid,
+    // Solid blocks and water: what stops movement, rain and sunlight
+    MotionBlocking,
+    // Anything but air, including plants and snow layers
+    Surface,
+}
+
+impl HeightmapKind {
+    const ALL: [HeightmapKind; 3] = [
+        HeightmapKind::Solid,
+        HeightmapKind::MotionBlocking,
+        HeightmapKind::Surface,
+    ];
+
+    pub fn matches(&self, block: &Block) -> bool {
+        match self {
+            HeightmapKind::Solid => block.is_solid(),
+            HeightmapKind::MotionBlocking => {
+                block.is_solid() || block.block_type == BlockType::Water
+            }
+            HeightmapKind::Surface => block.block_type != BlockType::Air,
+        }
+    }
+}
+
 pub struct Chunk {
     pub x: i32,
     pub z: i32,
-    pub blocks: Box<[[[Block; CHUNK_SIZE]; CHUNK_HEIGHT]; CHUNK_SIZE]>,
+    blocks: Box<[[[Block; CHUNK_SIZE]; CHUNK_HEIGHT]; CHUNK_SIZE]>,
+    // Per kind and column: 1 + y of the highest matching block, 0 if none
+    heightmaps: [[[u16; CHUNK_SIZE]; CHUNK_SIZE]; 3],
+    // Entities standing in this chunk; they load and unload with it
+    pub entities: Vec<Entity>,
 }
 
 impl Chunk {
@@ -15,15 +49,42 @@ impl Chunk {
             x,
             z,
             blocks: Box::new([[[Block::default(); CHUNK_SIZE]; CHUNK_HEIGHT]; CHUNK_SIZE]),
+            heightmaps: [[[0; CHUNK_SIZE]; CHUNK_SIZE]; 3],
+            entities: Vec::new(),
         }
     }
 
     pub fn set_block(&mut self, x: usize, y: usize, z: usize, block_type: BlockType) {
+        self.put_block(x, y, z, Block::new(block_type));
+    }
+
+    // Like set_block, keeping the fluid level of the block
+    pub fn put_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
         if x < CHUNK_SIZE && y < CHUNK_HEIGHT && z < CHUNK_SIZE {
-            self.blocks[x][y][z] = Block::new(block_type);
+            self.blocks[x][y][z] = block;
+            for (i, kind) in HeightmapKind::ALL.iter().enumerate() {
+                let top = self.heightmaps[i][x][z] as usize;
+                if kind.matches(&block) {
+                    if y + 1 > top {
+                        self.heightmaps[i][x][z] = (y + 1) as u16;
+                    }
+                } else if y + 1 == top {
+                    // The top block went away: look down for the next one
+                    let below = (0..y)
+                        .rev()
+                        .find(|&by| kind.matches(&self.blocks[x][by][z]));
+                    self.heightmaps[i][x][z] = below.map_or(0, |by| by as u16 + 1);
+                }
+            }
         }
     }
 
+    // y of the highest block of this kind in a column, if there is one
+    pub fn height(&self, kind: HeightmapKind, x: usize, z: usize) -> Option<usize> {
+        let top = self.heightmaps[kind as usize][x][z] as usize;
+        top.checked_sub(1)
+    }
+
     pub fn get_block(&self, x: usize, y: usize, z: usize) -> Option<&Block> {
         if x < CHUNK_SIZE && y < CHUNK_HEIGHT && z < CHUNK_SIZE {
             Some(&self.blocks[x][y][z])
@@ -32,3 +93,42 @@ impl Chunk {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heightmaps_follow_set_block() {
+        let mut chunk = Chunk::new(0, 0);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), None);
+
+        for y in 0..10 {
+            chunk.set_block(3, y, 4, BlockType::Stone);
+        }
+        chunk.set_block(3, 10, 4, BlockType::Water);
+        chunk.set_block(3, 11, 4, BlockType::TallGrass);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), Some(9));
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 3, 4), Some(10));
+        assert_eq!(chunk.height(HeightmapKind::Surface, 3, 4), Some(11));
+
+        // Digging out the top finds the next block down
+        chunk.set_block(3, 11, 4, BlockType::Air);
+        chunk.set_block(3, 10, 4, BlockType::Air);
+        chunk.set_block(3, 9, 4, BlockType::Air);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), Some(8));
+        assert_eq!(chunk.height(HeightmapKind::MotionBlocking, 3, 4), Some(8));
+        assert_eq!(chunk.height(HeightmapKind::Surface, 3, 4), Some(8));
+
+        // A floating block above a gap becomes the new top
+        chunk.set_block(3, 200, 4, BlockType::Planks);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), Some(200));
+        chunk.set_block(3, 200, 4, BlockType::Air);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), Some(8));
+
+        // Changes below the top leave it alone
+        chunk.set_block(3, 2, 4, BlockType::Air);
+        assert_eq!(chunk.height(HeightmapKind::Solid, 3, 4), Some(8));
+        assert_eq!(chunk.height(HeightmapKind::Solid, 4, 4), None);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::chunk::Chunk;
+use serde::{Deserialize, Serialize};
+
+// Separate spaces a world can hold, each with its own chunks and generator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DimensionId {
+    #[default]
+    Overworld,
+    Sky,
+}
+
+impl DimensionId {
+    pub const ALL: [DimensionId; 2] = [DimensionId::Overworld, DimensionId::Sky];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DimensionId::Overworld => "overworld",
+            DimensionId::Sky => "sky",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|id| id.name() == name)
+    }
+}
+
+// Anything that can fill a chunk from scratch. Generation must only depend
+// on the chunk position so chunks can be made in any order.
+pub trait ChunkGenerator {
+    fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk;
+}
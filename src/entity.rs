@@ -0,0 +1,317 @@
+use crate::block::BlockType;
+use crate::chunk::CHUNK_SIZE;
+use crate::mob::MobBrain;
+use crate::physics::{AABB, Hitbox, STEP_HEIGHT, sweep_aabb};
+use crate::spawn::fell_out_of_world;
+use crate::world::World;
+use glam::Vec3;
+
+pub type EntityId = u64;
+
+// Blocks per second squared, the same pull the player feels
+const GRAVITY: f32 = -20.0;
+// Dropped items disappear after five minutes of game time
+const ITEM_LIFETIME: f32 = 300.0;
+const PROJECTILE_LIFETIME: f32 = 30.0;
+// Share of horizontal velocity lost per second while sliding on the ground
+const GROUND_FRICTION: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    // A dropped block that can be picked up
+    Item(BlockType),
+    Mob,
+    Projectile,
+    // A block pulled down by gravity, placed again where it lands
+    FallingBlock(BlockType),
+}
+
+impl EntityKind {
+    pub fn hitbox(&self) -> Hitbox {
+        let cube = |size: f32| Hitbox {
+            width: size,
+            height: size,
+            eye_height: size / 2.0,
+        };
+        match self {
+            EntityKind::Item(_) => cube(0.25),
+            EntityKind::Mob => Hitbox {
+                width: 0.6,
+                height: 1.7,
+                eye_height: 1.5,
+            },
+            EntityKind::Projectile => cube(0.25),
+            // Slightly under a block so it fits down a one-block shaft
+            EntityKind::FallingBlock(_) => cube(0.98),
+        }
+    }
+
+    // Texture of the box the entity is drawn as
+    pub fn model_block(&self) -> BlockType {
+        match self {
+            EntityKind::Item(block_type) | EntityKind::FallingBlock(block_type) => *block_type,
+            EntityKind::Mob => BlockType::Planks,
+            EntityKind::Projectile => BlockType::Cobblestone,
+        }
+    }
+
+    // Share of gravity that applies
+    fn gravity_scale(&self) -> f32 {
+        match self {
+            EntityKind::Projectile => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    // Share of velocity lost per second to the air
+    fn drag(&self) -> f32 {
+        match self {
+            EntityKind::Item(_) => 1.0,
+            EntityKind::Mob => 0.0,
+            EntityKind::Projectile => 0.1,
+            EntityKind::FallingBlock(_) => 0.2,
+        }
+    }
+
+    fn step_height(&self) -> f32 {
+        match self {
+            EntityKind::Mob => STEP_HEIGHT,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    // Given by the world when the entity is spawned
+    pub id: EntityId,
+    pub kind: EntityKind,
+    // The bottom centre of the hitbox
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    // Seconds of game time since it was spawned
+    pub age: f32,
+    // Set to take the entity out of the world after this tick
+    pub removed: bool,
+    // Mobs that move on their own
+    pub brain: Option<MobBrain>,
+    previous_position: Vec3,
+}
+
+impl Entity {
+    pub fn new(kind: EntityKind, position: Vec3) -> Self {
+        Self {
+            id: 0,
+            kind,
+            position,
+            velocity: Vec3::ZERO,
+            on_ground: false,
+            age: 0.0,
+            removed: false,
+            brain: None,
+            previous_position: position,
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_brain(mut self, brain: MobBrain) -> Self {
+        self.brain = Some(brain);
+        self
+    }
+
+    pub fn aabb(&self) -> AABB {
+        self.kind.hitbox().at(self.position)
+    }
+
+    pub fn chunk_pos(&self) -> (i32, i32) {
+        chunk_of(self.position)
+    }
+
+    // Position between the previous tick and this one, for rendering
+    pub fn interpolated_position(&self, alpha: f32) -> Vec3 {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    // Lets the brain, if any, steer the entity for the coming tick
+    pub fn think(&mut self, world: &World, player: Option<Vec3>, dt: f32) {
+        let Some(brain) = &mut self.brain else {
+            return;
+        };
+        let steering = brain.think(world, self.id, self.position, self.on_ground, player, dt);
+        self.velocity.x = steering.x;
+        self.velocity.z = steering.z;
+        if steering.y > 0.0 {
+            self.velocity.y = steering.y;
+        }
+    }
+
+    // Gravity, drag and collision for one tick of dt seconds
+    pub fn tick(&mut self, world: &World, dt: f32) {
+        self.previous_position = self.position;
+        self.age += dt;
+
+        self.velocity.y += GRAVITY * self.kind.gravity_scale() * dt;
+        self.velocity /= 1.0 + self.kind.drag() * dt;
+        if self.on_ground {
+            let friction = 1.0 + GROUND_FRICTION * dt;
+            self.velocity.x /= friction;
+            self.velocity.z /= friction;
+        }
+
+        let motion = self.velocity * dt;
+        let sweep = sweep_aabb(world, &self.aabb(), motion, self.kind.step_height());
+        self.position += sweep.movement;
+        let contacts = sweep.contacts;
+        if contacts.grounded || contacts.ceiling {
+            self.velocity.y = 0.0;
+        }
+        if contacts.wall_x {
+            self.velocity.x = 0.0;
+        }
+        if contacts.wall_z {
+            self.velocity.z = 0.0;
+        }
+        self.on_ground = contacts.grounded;
+
+        match self.kind {
+            EntityKind::Item(_) if self.age > ITEM_LIFETIME => self.removed = true,
+            // The world puts the block back where it lands
+            EntityKind::FallingBlock(_) if contacts.grounded => self.removed = true,
+            EntityKind::Projectile => {
+                let hit = contacts.grounded || contacts.ceiling || contacts.wall();
+                if hit || self.age > PROJECTILE_LIFETIME {
+                    self.removed = true;
+                }
+            }
+            _ => {}
+        }
+        if fell_out_of_world(self.position.y) {
+            self.removed = true;
+        }
+    }
+}
+
+pub fn chunk_of(position: Vec3) -> (i32, i32) {
+    (
+        (position.x.floor() as i32).div_euclid(CHUNK_SIZE as i32),
+        (position.z.floor() as i32).div_euclid(CHUNK_SIZE as i32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::block_update::FALL_DELAY;
+    use crate::test_world::{block_at, empty_world, lay_floor, run};
+
+    // Two chunks side by side along x with a stone floor topped at y = 5
+    fn test_world() -> World {
+        let mut world = empty_world(0..=1, 0..=0);
+        lay_floor(&mut world, BlockType::Stone, 0..32, 0..16);
+        world
+    }
+
+    #[test]
+    fn items_fall_and_come_to_rest() {
+        let mut world = test_world();
+        let item = Entity::new(EntityKind::Item(BlockType::Dirt), Vec3::new(3.5, 20.0, 3.5))
+            .with_velocity(Vec3::new(2.0, 0.0, 0.0));
+        let id = world.spawn_entity(item).unwrap();
+        run(&mut world, 240);
+
+        let item = world.entity(id).unwrap();
+        assert!(item.on_ground);
+        assert!((item.position.y - 5.0).abs() < 1e-3);
+        assert!(item.velocity.length() < 0.01);
+        assert!(item.position.x > 3.5);
+    }
+
+    #[test]
+    fn entities_move_between_chunks() {
+        let mut world = test_world();
+        let mob = Entity::new(EntityKind::Mob, Vec3::new(15.5, 5.0, 8.5))
+            .with_velocity(Vec3::new(6.0, 0.0, 0.0));
+        let id = world.spawn_entity(mob).unwrap();
+        run(&mut world, 30);
+
+        let mob = world.entity(id).unwrap();
+        assert_eq!(mob.chunk_pos(), (1, 0));
+        assert!(world.chunks[&(0, 0)].entities.is_empty());
+        assert_eq!(world.chunks[&(1, 0)].entities.len(), 1);
+    }
+
+    #[test]
+    fn entities_stop_at_unloaded_chunks() {
+        let mut world = test_world();
+        let mob = Entity::new(EntityKind::Mob, Vec3::new(31.5, 5.0, 8.5))
+            .with_velocity(Vec3::new(6.0, 0.0, 0.0));
+        let id = world.spawn_entity(mob).unwrap();
+        run(&mut world, 30);
+        assert_eq!(world.entity(id).unwrap().chunk_pos(), (1, 0));
+
+        // Nor can they be spawned there
+        let outside = Entity::new(EntityKind::Mob, Vec3::new(40.0, 5.0, 8.0));
+        assert_eq!(world.spawn_entity(outside), None);
+    }
+
+    #[test]
+    fn projectiles_break_on_impact() {
+        let mut world = test_world();
+        world.set_block(10, 6, 8, Block::new(BlockType::Stone));
+        let arrow = Entity::new(EntityKind::Projectile, Vec3::new(2.5, 6.2, 8.5))
+            .with_velocity(Vec3::new(30.0, 2.0, 0.0));
+        let id = world.spawn_entity(arrow).unwrap();
+        run(&mut world, 60);
+        assert!(world.entity(id).is_none());
+    }
+
+    #[test]
+    fn entities_unload_with_their_chunk() {
+        let mut world = test_world();
+        let item = Entity::new(EntityKind::Item(BlockType::Sand), Vec3::new(20.0, 5.0, 4.0));
+        let id = world.spawn_entity(item).unwrap();
+        let chunk = world.chunks.remove(&(1, 0)).unwrap();
+        assert!(world.entity(id).is_none());
+        assert_eq!(chunk.entities[0].id, id);
+    }
+
+    #[test]
+    fn placed_sand_falls_and_lands() {
+        let mut world = test_world();
+        // A plant where it lands is crushed
+        world.set_block(3, 5, 3, Block::new(BlockType::TallGrass));
+        world.set_block(3, 12, 3, Block::new(BlockType::Sand));
+        run(&mut world, FALL_DELAY as usize + 1);
+        assert_eq!(block_at(&world, 3, 12, 3), BlockType::Air);
+        assert_eq!(world.entities().count(), 1);
+
+        run(&mut world, 120);
+        assert_eq!(block_at(&world, 3, 5, 3), BlockType::Sand);
+        assert_eq!(world.entities().count(), 0);
+    }
+
+    #[test]
+    fn removing_support_brings_down_the_column() {
+        let mut world = test_world();
+        world.set_block(3, 5, 3, Block::new(BlockType::Stone));
+        for y in 6..9 {
+            world.set_block(3, y, 3, Block::new(BlockType::Gravel));
+        }
+        run(&mut world, FALL_DELAY as usize + 1);
+        assert_eq!(world.entities().count(), 0);
+
+        world.set_block(3, 5, 3, Block::new(BlockType::Air));
+        run(&mut world, 180);
+        for y in 5..8 {
+            assert_eq!(block_at(&world, 3, y, 3), BlockType::Gravel);
+        }
+        assert_eq!(block_at(&world, 3, 8, 3), BlockType::Air);
+        assert_eq!(world.entities().count(), 0);
+    }
+}
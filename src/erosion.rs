@@ -0,0 +1,307 @@
+use crate::rng::ChunkRng;
+use crate::terrain_config::ErosionConfig;
+use glam::Vec2;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Regions kept in memory before the least recently used is dropped
+const MAX_CACHED_REGIONS: usize = 64;
+
+// Square grid of terrain heights, indexed [z * size + x]
+pub struct Heightmap {
+    pub size: usize,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(size: usize, heights: Vec<f32>) -> Self {
+        assert_eq!(heights.len(), size * size);
+        Self { size, heights }
+    }
+
+    fn get(&self, x: usize, z: usize) -> f32 {
+        self.heights[z * self.size + x]
+    }
+
+    // Bilinear height and slope at a point between grid nodes
+    fn sample(&self, pos: Vec2) -> (f32, Vec2) {
+        let x = pos.x as usize;
+        let z = pos.y as usize;
+        let u = pos.x - x as f32;
+        let v = pos.y - z as f32;
+
+        let h00 = self.get(x, z);
+        let h10 = self.get(x + 1, z);
+        let h01 = self.get(x, z + 1);
+        let h11 = self.get(x + 1, z + 1);
+
+        let gradient = Vec2::new(
+            (h10 - h00) * (1.0 - v) + (h11 - h01) * v,
+            (h01 - h00) * (1.0 - u) + (h11 - h10) * u,
+        );
+        let height =
+            h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+        (height, gradient)
+    }
+
+    // Spreads a height change over the four nodes around a point
+    fn add(&mut self, pos: Vec2, amount: f32) {
+        let x = pos.x as usize;
+        let z = pos.y as usize;
+        let u = pos.x - x as f32;
+        let v = pos.y - z as f32;
+        let size = self.size;
+
+        self.heights[z * size + x] += amount * (1.0 - u) * (1.0 - v);
+        self.heights[z * size + x + 1] += amount * u * (1.0 - v);
+        self.heights[(z + 1) * size + x] += amount * (1.0 - u) * v;
+        self.heights[(z + 1) * size + x + 1] += amount * u * v;
+    }
+}
+
+// Runs hydraulic then thermal erosion over the whole map
+pub fn erode(map: &mut Heightmap, config: &ErosionConfig, rng: &mut ChunkRng) {
+    let droplets = (map.size * map.size) as f64 * config.droplets_per_block;
+    for _ in 0..droplets as usize {
+        simulate_droplet(map, config, rng);
+    }
+    for _ in 0..config.thermal_iterations {
+        thermal_step(map, config);
+    }
+}
+
+// A drop of water rolls downhill, picking up sediment while it speeds up and
+// dropping it where it slows down or fills a pit. This cuts valleys and
+// fills basins.
+fn simulate_droplet(map: &mut Heightmap, config: &ErosionConfig, rng: &mut ChunkRng) {
+    let limit = (map.size - 1) as f32;
+    let mut pos = Vec2::new(rng.range_f32(0.0, limit), rng.range_f32(0.0, limit));
+    let mut direction = Vec2::ZERO;
+    let mut speed = 1.0_f32;
+    let mut water = 1.0_f32;
+    let mut sediment = 0.0_f32;
+
+    for _ in 0..config.droplet_lifetime {
+        let (height, gradient) = map.sample(pos);
+
+        direction = direction * config.inertia - gradient * (1.0 - config.inertia);
+        if direction.length_squared() < 1e-8 {
+            break;
+        }
+        direction = direction.normalize();
+
+        let next = pos + direction;
+        if next.x < 0.0 || next.y < 0.0 || next.x >= limit || next.y >= limit {
+            break;
+        }
+
+        let (next_height, _) = map.sample(next);
+        let delta = next_height - height;
+        let capacity =
+            (-delta * speed * water * config.sediment_capacity).max(config.min_sediment_capacity);
+
+        if delta > 0.0 || sediment > capacity {
+            // Uphill: fill the pit behind us. Otherwise drop the excess.
+            let amount = if delta > 0.0 {
+                delta.min(sediment)
+            } else {
+                (sediment - capacity) * config.deposit_speed
+            };
+            sediment -= amount;
+            map.add(pos, amount);
+        } else {
+            // Never dig deeper than the drop to the next point
+            let amount = ((capacity - sediment) * config.erode_speed).min(-delta);
+            sediment += amount;
+            map.add(pos, -amount);
+        }
+
+        speed = (speed * speed - delta * config.gravity).max(0.0).sqrt();
+        water *= 1.0 - config.evaporate_speed;
+        pos = next;
+    }
+}
+
+// Material slides off slopes steeper than the talus limit onto lower neighbours
+fn thermal_step(map: &mut Heightmap, config: &ErosionConfig) {
+    let size = map.size;
+    let mut change = vec![0.0_f32; size * size];
+
+    for z in 0..size {
+        for x in 0..size {
+            let height = map.get(x, z);
+            let neighbours = [
+                (x.wrapping_sub(1), z),
+                (x + 1, z),
+                (x, z.wrapping_sub(1)),
+                (x, z + 1),
+            ];
+            for (nx, nz) in neighbours {
+                if nx >= size || nz >= size {
+                    continue;
+                }
+                let difference = height - map.get(nx, nz);
+                if difference > config.talus {
+                    // A quarter per neighbour so a cell never gives away more
+                    // than the excess above its lowest neighbour
+                    let amount = (difference - config.talus) * config.thermal_rate * 0.25;
+                    change[z * size + x] -= amount;
+                    change[nz * size + nx] += amount;
+                }
+            }
+        }
+    }
+
+    for (height, delta) in map.heights.iter_mut().zip(change) {
+        *height += delta;
+    }
+}
+
+// How much erosion raised or lowered each point of one region
+pub struct ErodedRegion {
+    pub min_x: i32,
+    pub min_z: i32,
+    pub size: usize,
+    pub delta: Vec<f32>,
+}
+
+impl ErodedRegion {
+    pub fn delta_at(&self, x: i32, z: i32) -> f32 {
+        let lx = (x - self.min_x) as usize;
+        let lz = (z - self.min_z) as usize;
+        self.delta[lz * self.size + lx]
+    }
+}
+
+// Eroded regions shared by every chunk that overlaps them, so erosion is
+// only simulated once per region. When full, the region used longest ago
+// makes room.
+pub struct ErosionCache {
+    capacity: usize,
+    regions: Mutex<CachedRegions>,
+}
+
+#[derive(Default)]
+struct CachedRegions {
+    // Each region with the time it was last handed out
+    regions: HashMap<(i32, i32), (Arc<ErodedRegion>, u64)>,
+    clock: u64,
+}
+
+impl CachedRegions {
+    fn get(&mut self, key: (i32, i32)) -> Option<Arc<ErodedRegion>> {
+        self.clock += 1;
+        let (region, last_used) = self.regions.get_mut(&key)?;
+        *last_used = self.clock;
+        Some(region.clone())
+    }
+
+    fn insert(&mut self, key: (i32, i32), region: Arc<ErodedRegion>, capacity: usize) {
+        if !self.regions.contains_key(&key) && self.regions.len() >= capacity {
+            let oldest = self
+                .regions
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.regions.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.regions.insert(key, (region, self.clock));
+    }
+}
+
+impl Default for ErosionCache {
+    fn default() -> Self {
+        Self::new(MAX_CACHED_REGIONS)
+    }
+}
+
+impl ErosionCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            regions: Mutex::default(),
+        }
+    }
+
+    // The regions for all keys, in order, taking the lock once when they
+    // are all cached
+    pub fn get_all(
+        &self,
+        keys: &[(i32, i32)],
+        compute: impl Fn((i32, i32)) -> ErodedRegion,
+    ) -> Vec<Arc<ErodedRegion>> {
+        let cached: Vec<Option<Arc<ErodedRegion>>> = {
+            let mut regions = self.regions.lock().unwrap();
+            keys.iter().map(|&key| regions.get(key)).collect()
+        };
+        if cached.iter().all(Option::is_some) {
+            return cached.into_iter().flatten().collect();
+        }
+
+        // Simulate without holding the lock; regions are deterministic, so
+        // a duplicate computation would produce the same result
+        let computed: Vec<Arc<ErodedRegion>> = keys
+            .iter()
+            .zip(cached)
+            .map(|(&key, region)| region.unwrap_or_else(|| Arc::new(compute(key))))
+            .collect();
+        let mut regions = self.regions.lock().unwrap();
+        for (&key, region) in keys.iter().zip(&computed) {
+            regions.insert(key, region.clone(), self.capacity);
+        }
+        computed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn region(key: (i32, i32)) -> ErodedRegion {
+        ErodedRegion {
+            min_x: key.0,
+            min_z: key.1,
+            size: 1,
+            delta: vec![0.0],
+        }
+    }
+
+    #[test]
+    fn the_least_recently_used_region_is_evicted() {
+        let cache = ErosionCache::new(2);
+        let computed = RefCell::new(Vec::new());
+        let get = |key| {
+            cache.get_all(&[key], |key| {
+                computed.borrow_mut().push(key);
+                region(key)
+            })[0]
+                .clone()
+        };
+
+        get((0, 0));
+        get((1, 0));
+        // Using (0, 0) again makes (1, 0) the oldest
+        assert_eq!(get((0, 0)).min_x, 0);
+        get((2, 0));
+        get((0, 0));
+        get((1, 0));
+        assert_eq!(*computed.borrow(), [(0, 0), (1, 0), (2, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn regions_come_back_in_key_order() {
+        let cache = ErosionCache::new(8);
+        let keys = [(3, 1), (0, 0), (-2, 5)];
+        cache.get_all(&keys[1..], region);
+        let regions = cache.get_all(&keys, region);
+        let found: Vec<(i32, i32)> = regions
+            .iter()
+            .map(|region| (region.min_x, region.min_z))
+            .collect();
+        assert_eq!(found, keys);
+    }
+}
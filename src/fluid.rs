@@ -0,0 +1,219 @@
+use crate::block::{Block, BlockType};
+use crate::world::World;
+use glam::Vec3;
+
+// Game ticks between one step of spreading water and the next
+pub const WATER_FLOW_DELAY: u64 = 5;
+// Flowing water goes no further than seven blocks from its source
+pub const MAX_FLOW_LEVEL: u8 = 7;
+
+const HORIZONTAL: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// How a fluid acts on the things inside it and how it looks from inside
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FluidProperties {
+    // Share of gravity the fluid cancels when fully submerged; 1 floats
+    pub buoyancy: f32,
+    // Share of velocity lost per second
+    pub drag: f32,
+    // Movement speed relative to walking
+    pub speed_multiplier: f32,
+    // Upward acceleration while holding jump, in blocks per second squared
+    pub swim_acceleration: f32,
+    // Fog seen with the head under the surface
+    pub fog_color: Vec3,
+    pub fog_distance: f32,
+}
+
+pub const WATER: FluidProperties = FluidProperties {
+    buoyancy: 0.95,
+    drag: 4.0,
+    speed_multiplier: 0.4,
+    swim_acceleration: 13.0,
+    fog_color: Vec3::new(0.1, 0.25, 0.55),
+    fog_distance: 24.0,
+};
+
+impl FluidProperties {
+    // None for blocks that aren't fluids. Lava would get its own entry here:
+    // thicker, slower and with a short orange fog.
+    pub fn of(block_type: BlockType) -> Option<FluidProperties> {
+        match block_type {
+            BlockType::Water => Some(WATER),
+            _ => None,
+        }
+    }
+}
+
+fn water_level(world: &World, x: i32, y: i32, z: i32) -> Option<u8> {
+    world
+        .get_block(x, y, z)
+        .filter(|block| block.block_type == BlockType::Water)
+        .map(|block| block.level)
+}
+
+// The level flowing water here gets from what feeds it, or None if nothing
+// does any more. Water falling from above counts as one block from a source,
+// and water between two sources, over ground or more source water, becomes a
+// source itself.
+fn fed_level(world: &World, (x, y, z): (i32, i32, i32)) -> Option<u8> {
+    if water_level(world, x, y + 1, z).is_some() {
+        return Some(1);
+    }
+    let mut sources = 0;
+    let mut level = None;
+    for (dx, dz) in HORIZONTAL {
+        if let Some(neighbour) = water_level(world, x + dx, y, z + dz) {
+            if neighbour == 0 {
+                sources += 1;
+            }
+            if neighbour < MAX_FLOW_LEVEL {
+                level = Some(level.map_or(neighbour + 1, |level: u8| level.min(neighbour + 1)));
+            }
+        }
+    }
+    let below = world.get_block(x, y - 1, z);
+    let held = below.is_some_and(|block| {
+        block.is_solid() || (block.block_type == BlockType::Water && block.level == 0)
+    });
+    if sources >= 2 && held {
+        return Some(0);
+    }
+    level
+}
+
+// One step of the water at a position: flowing water takes the level its
+// neighbours feed it or dries up, then water runs down if it can and
+// otherwise spreads a level further out to the sides
+pub fn flow(world: &mut World, pos: (i32, i32, i32)) {
+    let (x, y, z) = pos;
+    let Some(mut level) = water_level(world, x, y, z) else {
+        return;
+    };
+    if level > 0 {
+        match fed_level(world, pos) {
+            None => {
+                world.set_block(x, y, z, Block::new(BlockType::Air));
+                return;
+            }
+            Some(fed) if fed != level => {
+                world.set_block(x, y, z, Block::flowing_water(fed));
+                level = fed;
+            }
+            _ => {}
+        }
+    }
+
+    let Some(below) = world.get_block(x, y - 1, z) else {
+        return;
+    };
+    if below.is_replaceable() {
+        world.set_block(x, y - 1, z, Block::flowing_water(1));
+        return;
+    }
+    // Water pouring into a stream joins it rather than spreading
+    if below.block_type == BlockType::Water && below.level > 0 {
+        return;
+    }
+    if level >= MAX_FLOW_LEVEL {
+        return;
+    }
+    for (dx, dz) in HORIZONTAL {
+        if world
+            .get_block(x + dx, y, z + dz)
+            .is_some_and(|block| block.is_replaceable())
+        {
+            world.set_block(x + dx, y, z + dz, Block::flowing_water(level + 1));
+        }
+    }
+}
+
+// Height of the water surface in a block, from 0 to 1
+pub fn surface_height(level: u8) -> f32 {
+    // A source sits a little below the top, and each level a little lower
+    0.8 * (MAX_FLOW_LEVEL + 1 - level) as f32 / (MAX_FLOW_LEVEL + 1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_world::{flat_world, run};
+
+    fn water_count(world: &World) -> usize {
+        let mut count = 0;
+        for x in -16..16 {
+            for y in 0..16 {
+                for z in -16..16 {
+                    count += water_level(world, x, y, z).is_some() as usize;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn sources_spread_seven_blocks() {
+        let mut world = flat_world();
+        world.set_block(0, 5, 0, Block::new(BlockType::Water));
+        run(&mut world, 200);
+
+        assert_eq!(water_level(&world, 0, 5, 0), Some(0));
+        assert_eq!(water_level(&world, 3, 5, 2), Some(5));
+        assert_eq!(water_level(&world, -7, 5, 0), Some(7));
+        assert_eq!(water_level(&world, 8, 5, 0), None);
+        // A diamond of every block within seven steps
+        assert_eq!(water_count(&world), 1 + 2 * 7 * 8);
+    }
+
+    #[test]
+    fn flowing_water_dries_up_without_its_source() {
+        let mut world = flat_world();
+        world.set_block(0, 5, 0, Block::new(BlockType::Water));
+        run(&mut world, 200);
+        world.set_block(0, 5, 0, Block::new(BlockType::Air));
+        run(&mut world, 400);
+        assert_eq!(water_count(&world), 0);
+    }
+
+    #[test]
+    fn water_pours_off_ledges() {
+        let mut world = flat_world();
+        for x in -2..=2 {
+            for z in -2..=2 {
+                world.set_block(x, 10, z, Block::new(BlockType::Stone));
+            }
+        }
+        world.set_block(0, 11, 0, Block::new(BlockType::Water));
+        run(&mut world, 400);
+
+        assert_eq!(water_level(&world, 3, 11, 0), Some(3));
+        assert_eq!(water_level(&world, 3, 7, 0), Some(1));
+        assert_eq!(water_level(&world, 3, 5, 0), Some(1));
+        assert_eq!(water_level(&world, 8, 5, 0), Some(6));
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_one() {
+        let mut world = flat_world();
+        world.set_block(0, 5, 0, Block::new(BlockType::Water));
+        world.set_block(2, 5, 0, Block::new(BlockType::Water));
+        run(&mut world, 200);
+        assert_eq!(water_level(&world, 1, 5, 0), Some(0));
+
+        // It stays when the sources that made it are taken away
+        world.set_block(0, 5, 0, Block::new(BlockType::Stone));
+        world.set_block(2, 5, 0, Block::new(BlockType::Stone));
+        run(&mut world, 200);
+        assert_eq!(water_level(&world, 1, 5, 0), Some(0));
+        assert_eq!(water_level(&world, 1, 5, 7), Some(7));
+    }
+
+    #[test]
+    fn plants_are_washed_away() {
+        let mut world = flat_world();
+        world.set_block(1, 5, 0, Block::new(BlockType::Poppy));
+        world.set_block(0, 5, 0, Block::new(BlockType::Water));
+        run(&mut world, 20);
+        assert_eq!(water_level(&world, 1, 5, 0), Some(1));
+    }
+}
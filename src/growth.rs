@@ -0,0 +1,389 @@
+// What plants do on random ticks: grass spreads and dies, leaves away from
+// any log decay, saplings grow into trees and crops ripen. How fast, and
+// whether at all, comes from the [growth] section of terrain.toml.
+use crate::block::{Block, BlockType, CROP_STAGES};
+use crate::entity::{Entity, EntityKind};
+use crate::rng::ChunkRng;
+use crate::vegetation::{TreeSpecies, grow_tree};
+use crate::world::{BlockPos, NEIGHBOURS, World};
+use glam::Vec3;
+use std::collections::{HashSet, VecDeque};
+
+// Blocks that let no light through
+fn is_opaque(block: Option<Block>) -> bool {
+    block.is_some_and(|block| !block.is_transparent())
+}
+
+fn is_soil(block: Option<Block>) -> bool {
+    block.is_some_and(|block| matches!(block.block_type, BlockType::Grass | BlockType::Dirt))
+}
+
+// Grass covered by an opaque block dies back to dirt. Otherwise it spreads to
+// dirt around it that is lit: open to the sky, until there is a light engine.
+pub fn grass(world: &mut World, pos: BlockPos, rng: &mut ChunkRng) {
+    let config = world.growth.grass.clone();
+    if !config.enabled {
+        return;
+    }
+    let (x, y, z) = pos;
+    if is_opaque(world.get_block(x, y + 1, z)) {
+        world.set_block(x, y, z, Block::new(BlockType::Dirt));
+        return;
+    }
+    for _ in 0..config.spread_attempts {
+        let (tx, ty, tz) = (
+            x + rng.range_i32(-1, 1),
+            y + rng.range_i32(-3, 1),
+            z + rng.range_i32(-1, 1),
+        );
+        let is_dirt = world
+            .get_block(tx, ty, tz)
+            .is_some_and(|block| block.block_type == BlockType::Dirt);
+        if is_dirt
+            && !is_opaque(world.get_block(tx, ty + 1, tz))
+            && world.is_exposed_to_sky(tx, ty + 1, tz)
+        {
+            world.set_block(tx, ty, tz, Block::new(BlockType::Grass));
+        }
+    }
+}
+
+// Steps from a leaf block to the nearest log, moving through leaves only, or
+// None if there is none within max_distance. A leaf touching a log is 1 away.
+pub fn log_distance(world: &World, start: BlockPos, max_distance: i32) -> Option<i32> {
+    let mut visited = HashSet::from([start]);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some(((x, y, z), distance)) = queue.pop_front() {
+        for (dx, dy, dz) in NEIGHBOURS {
+            let next = (x + dx, y + dy, z + dz);
+            if !visited.insert(next) {
+                continue;
+            }
+            let Some(block) = world.get_block(next.0, next.1, next.2) else {
+                continue;
+            };
+            if block.is_log() {
+                return Some(distance + 1);
+            }
+            if block.is_leaves() && distance + 1 < max_distance {
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    None
+}
+
+// The distance leaves store when no log is within decay_distance
+fn too_far(world: &World) -> i32 {
+    world.growth.leaves.decay_distance + 1
+}
+
+// How far leaves are from a log going by the blocks around them: one more
+// than the nearest neighbour, with a log 0 away. Neighbours that haven't
+// worked out their own distance yet are searched from.
+fn neighbour_distance(world: &World, (x, y, z): BlockPos) -> i32 {
+    let (max_distance, too_far) = (world.growth.leaves.decay_distance, too_far(world));
+    let mut nearest = too_far;
+    for (dx, dy, dz) in NEIGHBOURS {
+        let next = (x + dx, y + dy, z + dz);
+        let Some(block) = world.get_block(next.0, next.1, next.2) else {
+            continue;
+        };
+        let distance = if block.is_log() {
+            1
+        } else if let Some(distance) = block.leaf_distance() {
+            distance as i32 + 1
+        } else if block.is_leaves() {
+            log_distance(world, next, max_distance).map_or(too_far, |distance| distance + 1)
+        } else {
+            continue;
+        };
+        nearest = nearest.min(distance);
+    }
+    nearest
+}
+
+// Something at or next to leaves changed: they work out their distance to
+// a log again and keep it. Storing a new one notifies the leaves around
+// them, so a change runs through the whole tree.
+pub fn leaves_changed(world: &mut World, pos: BlockPos, block: Block) {
+    if !world.growth.leaves.enabled {
+        return;
+    }
+    let distance = neighbour_distance(world, pos);
+    if block.leaf_distance() != Some(distance as u8) {
+        let (x, y, z) = pos;
+        world.set_block(x, y, z, block.with_leaf_distance(distance as u8));
+    }
+}
+
+// Leaves cut off from their tree disappear, now and then dropping a sapling.
+// Leaves a player placed stay.
+pub fn leaves(world: &mut World, pos: BlockPos, block: Block, rng: &mut ChunkRng) {
+    let config = world.growth.leaves.clone();
+    if !config.enabled {
+        return;
+    }
+    let (x, y, z) = pos;
+    let distance = match block.leaf_distance() {
+        Some(distance) => distance as i32,
+        // Generated leaves search once and keep what they found
+        None => {
+            let distance =
+                log_distance(world, pos, config.decay_distance).unwrap_or(too_far(world));
+            world.set_block(x, y, z, block.with_leaf_distance(distance as u8));
+            distance
+        }
+    };
+    if block.is_persistent_leaves() || distance <= config.decay_distance {
+        return;
+    }
+    world.set_block(x, y, z, Block::new(BlockType::Air));
+    if let Some(sapling) = sapling_of(block.block_type)
+        && rng.chance(config.sapling_chance)
+    {
+        let center = Vec3::new(x as f32 + 0.5, y as f32 + 0.25, z as f32 + 0.5);
+        world.spawn_entity(Entity::new(EntityKind::Item(sapling), center));
+    }
+}
+
+fn sapling_of(leaves: BlockType) -> Option<BlockType> {
+    match leaves {
+        BlockType::Leaves => Some(BlockType::OakSapling),
+        BlockType::BirchLeaves => Some(BlockType::BirchSapling),
+        BlockType::SpruceLeaves => Some(BlockType::SpruceSapling),
+        _ => None,
+    }
+}
+
+fn species_of(sapling: BlockType) -> Option<TreeSpecies> {
+    match sapling {
+        BlockType::OakSapling => Some(TreeSpecies::Oak),
+        BlockType::BirchSapling => Some(TreeSpecies::Birch),
+        BlockType::SpruceSapling => Some(TreeSpecies::Spruce),
+        _ => None,
+    }
+}
+
+// A sapling on soil grows into a tree of its species, shaped by the same
+// generator as the trees of the terrain, if there is room for the trunk.
+// Returns whether it grew.
+pub fn sapling(world: &mut World, pos: BlockPos, block: Block, rng: &mut ChunkRng) -> bool {
+    let config = world.growth.saplings.clone();
+    let Some(species) = species_of(block.block_type) else {
+        return false;
+    };
+    let (x, y, z) = pos;
+    if !config.enabled
+        || !is_soil(world.get_block(x, y - 1, z))
+        || !rng.chance(config.growth_chance)
+    {
+        return false;
+    }
+
+    let tree = grow_tree(species, pos, &world.trees, rng);
+    let room = tree.iter().all(|&(bx, by, bz, block_type)| {
+        !Block::new(block_type).is_log()
+            || world
+                .get_block(bx, by, bz)
+                .is_some_and(|existing| existing.is_replaceable() || existing.is_leaves())
+    });
+    if !room {
+        return false;
+    }
+    // Logs come first and push through leaves; leaves only fill free space
+    for (bx, by, bz, block_type) in tree {
+        let new = Block::new(block_type);
+        let fits = world.get_block(bx, by, bz).is_some_and(|existing| {
+            existing.is_replaceable() || (existing.is_leaves() && new.is_log())
+        });
+        if fits {
+            world.set_block(bx, by, bz, new);
+        }
+    }
+    true
+}
+
+// A crop on soil grows one stage, up to ripe
+pub fn crop(world: &mut World, pos: BlockPos, block: Block, rng: &mut ChunkRng) {
+    let config = world.growth.crops.clone();
+    let (x, y, z) = pos;
+    if !config.enabled
+        || block.level + 1 >= CROP_STAGES
+        || !is_soil(world.get_block(x, y - 1, z))
+        || !rng.chance(config.growth_chance)
+    {
+        return;
+    }
+    let grown = Block {
+        level: block.level + 1,
+        ..block
+    };
+    world.set_block(x, y, z, grown);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_world::{block_at, empty_world, lay_floor, run};
+
+    // Dirt floor topped at y = 5 over one chunk, with growth that always
+    // happens when it may
+    fn test_world() -> World {
+        let mut world = empty_world(0..=0, 0..=0);
+        lay_floor(&mut world, BlockType::Dirt, 0..16, 0..16);
+        world.growth.leaves.sapling_chance = 0.0;
+        world.growth.saplings.growth_chance = 1.0;
+        world.growth.crops.growth_chance = 1.0;
+        world
+    }
+
+    fn rng() -> ChunkRng {
+        ChunkRng::new(1, 0, 0, 0)
+    }
+
+    #[test]
+    fn grass_spreads_to_open_dirt_only() {
+        let mut world = test_world();
+        world.set_block(8, 4, 8, Block::new(BlockType::Grass));
+        world.set_block(9, 5, 8, Block::new(BlockType::Stone));
+        let mut rng = rng();
+        for _ in 0..50 {
+            grass(&mut world, (8, 4, 8), &mut rng);
+        }
+        // Every open block of dirt around it, but not the one under the
+        // stone, nor any further away
+        let mut grown = Vec::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                if block_at(&world, x, 4, z) == BlockType::Grass {
+                    grown.push((x, z));
+                }
+            }
+        }
+        let expected = [
+            (7, 7),
+            (7, 8),
+            (7, 9),
+            (8, 7),
+            (8, 8),
+            (8, 9),
+            (9, 7),
+            (9, 9),
+        ];
+        assert_eq!(grown, expected);
+    }
+
+    #[test]
+    fn grass_dies_under_opaque_blocks() {
+        let mut world = test_world();
+        world.set_block(3, 4, 3, Block::new(BlockType::Grass));
+        world.set_block(3, 5, 3, Block::new(BlockType::Leaves));
+        grass(&mut world, (3, 4, 3), &mut rng());
+        assert_eq!(block_at(&world, 3, 4, 3), BlockType::Grass);
+
+        world.set_block(3, 5, 3, Block::new(BlockType::Planks));
+        grass(&mut world, (3, 4, 3), &mut rng());
+        assert_eq!(block_at(&world, 3, 4, 3), BlockType::Dirt);
+    }
+
+    #[test]
+    fn leaves_decay_away_from_logs() {
+        let mut world = test_world();
+        // A log with a row of leaves running off it
+        world.set_block(2, 5, 2, Block::new(BlockType::OakLog));
+        for x in 3..12 {
+            world.set_block(x, 5, 2, Block::new(BlockType::Leaves));
+        }
+        assert_eq!(log_distance(&world, (3, 5, 2), 6), Some(1));
+        assert_eq!(log_distance(&world, (8, 5, 2), 6), Some(6));
+        assert_eq!(log_distance(&world, (9, 5, 2), 6), None);
+
+        let leaf = world.get_block(9, 5, 2).unwrap();
+        leaves(&mut world, (9, 5, 2), leaf, &mut rng());
+        assert_eq!(block_at(&world, 9, 5, 2), BlockType::Air);
+        let leaf = world.get_block(8, 5, 2).unwrap();
+        leaves(&mut world, (8, 5, 2), leaf, &mut rng());
+        assert_eq!(block_at(&world, 8, 5, 2), BlockType::Leaves);
+
+        // Leaves a player placed never decay
+        world.set_block(11, 5, 2, Block::placed(BlockType::Leaves));
+        let leaf = world.get_block(11, 5, 2).unwrap();
+        leaves(&mut world, (11, 5, 2), leaf, &mut rng());
+        assert_eq!(block_at(&world, 11, 5, 2), BlockType::Leaves);
+        assert!(world.get_block(11, 5, 2).unwrap().is_persistent_leaves());
+    }
+
+    #[test]
+    fn leaves_keep_their_distance_to_a_log_up_to_date() {
+        let mut world = test_world();
+        world.random_tick_speed = 0;
+        let distances = |world: &World| -> Vec<u8> {
+            (3..12)
+                .map(|x| world.get_block(x, 5, 2).unwrap().leaf_distance().unwrap())
+                .collect()
+        };
+        // Placed leaves work out their distance, up to one past
+        // decay_distance
+        world.set_block(2, 5, 2, Block::new(BlockType::OakLog));
+        for x in 3..12 {
+            world.set_block(x, 5, 2, Block::new(BlockType::Leaves));
+        }
+        run(&mut world, 1);
+        assert_eq!(distances(&world), [1, 2, 3, 4, 5, 6, 7, 7, 7]);
+
+        // Cutting the log leaves every one of them too far
+        world.set_block(2, 5, 2, Block::new(BlockType::Air));
+        run(&mut world, 1);
+        assert_eq!(distances(&world), [7; 9]);
+
+        // A log at the other end brings the near ones back
+        world.set_block(12, 5, 2, Block::new(BlockType::OakLog));
+        run(&mut world, 1);
+        assert_eq!(distances(&world), [7, 7, 7, 6, 5, 4, 3, 2, 1]);
+
+        // Random ticks go by the distance the leaves keep
+        let leaf = world.get_block(5, 5, 2).unwrap();
+        leaves(&mut world, (5, 5, 2), leaf, &mut rng());
+        assert_eq!(block_at(&world, 5, 5, 2), BlockType::Air);
+        let leaf = world.get_block(6, 5, 2).unwrap();
+        leaves(&mut world, (6, 5, 2), leaf, &mut rng());
+        assert_eq!(block_at(&world, 6, 5, 2), BlockType::Leaves);
+    }
+
+    #[test]
+    fn saplings_grow_into_trees_when_there_is_room() {
+        let mut world = test_world();
+        world.set_block(8, 5, 8, Block::new(BlockType::OakSapling));
+        // A ceiling over the sapling leaves no room for the trunk
+        world.set_block(8, 7, 8, Block::new(BlockType::Stone));
+        let sapling_block = world.get_block(8, 5, 8).unwrap();
+        assert!(!sapling(&mut world, (8, 5, 8), sapling_block, &mut rng()));
+        assert_eq!(block_at(&world, 8, 5, 8), BlockType::OakSapling);
+
+        world.set_block(8, 7, 8, Block::new(BlockType::Air));
+        assert!(sapling(&mut world, (8, 5, 8), sapling_block, &mut rng()));
+        let trunk = world.trees.trunk_height as i32;
+        for y in 5..5 + trunk {
+            assert_eq!(block_at(&world, 8, y, 8), BlockType::OakLog);
+        }
+        assert_eq!(block_at(&world, 8, 5 + trunk, 8), BlockType::Leaves);
+    }
+
+    #[test]
+    fn crops_ripen_stage_by_stage() {
+        let mut world = test_world();
+        world.set_block(4, 5, 4, Block::new(BlockType::Wheat));
+        for _ in 0..20 {
+            let block = world.get_block(4, 5, 4).unwrap();
+            crop(&mut world, (4, 5, 4), block, &mut rng());
+        }
+        assert_eq!(world.get_block(4, 5, 4).unwrap().level, CROP_STAGES - 1);
+
+        // Nothing grows without soil
+        world.set_block(4, 6, 6, Block::new(BlockType::Wheat));
+        let block = world.get_block(4, 6, 6).unwrap();
+        crop(&mut world, (4, 6, 6), block, &mut rng());
+        assert_eq!(world.get_block(4, 6, 6).unwrap().level, 0);
+    }
+}
@@ -0,0 +1,31 @@
+pub mod angle;
+pub mod biome;
+pub mod block;
+pub mod block_update;
+pub mod camera;
+pub mod chunk;
+pub mod dimension;
+pub mod entity;
+pub mod erosion;
+pub mod fluid;
+pub mod growth;
+pub mod mesh;
+pub mod mob;
+pub mod pathfinding;
+pub mod physics;
+pub mod player;
+pub mod rng;
+pub mod save;
+pub mod shader;
+pub mod sky_islands;
+pub mod spawn;
+pub mod structure;
+pub mod surface_rules;
+pub mod terrain;
+pub mod terrain_config;
+#[cfg(test)]
+mod test_world;
+pub mod texture;
+pub mod timestep;
+pub mod vegetation;
+pub mod world;
@@ -1,29 +1,50 @@
-mod block;
-mod camera;
-mod chunk;
-mod mesh;
-mod physics;
-mod shader;
-mod terrain;
-mod texture;
-mod world;
-
-use block::{Block, BlockType};
-use camera::Camera;
-use chunk::CHUNK_SIZE;
 use glam::{Mat4, Vec3};
 use glfw::{Action, Context, Key, MouseButton};
-use mesh::generate_chunk_mesh;
-use physics::{check_collision, raycast};
-use shader::Shader;
 use std::collections::HashMap;
-use terrain::TerrainGenerator;
-use texture::TextureAtlas;
-use world::World;
+use voxel_engine::block::{Block, BlockType};
+use voxel_engine::camera::Camera;
+use voxel_engine::chunk::CHUNK_SIZE;
+use voxel_engine::dimension::DimensionId;
+use voxel_engine::entity::{Entity, EntityKind};
+use voxel_engine::mesh::{self, generate_chunk_mesh};
+use voxel_engine::mob::{Behaviour, MobBrain};
+use voxel_engine::physics::{AABB, raycast};
+use voxel_engine::player::{MovementMode, PlayerConfig, PlayerController, PlayerInput};
+use voxel_engine::save::LevelData;
+use voxel_engine::shader::Shader;
+use voxel_engine::sky_islands::SkyIslandGenerator;
+use voxel_engine::spawn::{self, SPAWN_SEARCH_RADIUS, SpawnPoint};
+use voxel_engine::structure::StructureKind;
+use voxel_engine::terrain::TerrainGenerator;
+use voxel_engine::terrain_config::TerrainConfig;
+use voxel_engine::texture::TextureAtlas;
+use voxel_engine::timestep::{FixedTimestep, TICK_RATE};
+use voxel_engine::world::World;
+use voxel_engine::world::edit::{self, Axis, Clipboard, Selection};
+use voxel_engine::world::history::EditHistory;
 
 const SCR_WIDTH: u32 = 1280;
 const SCR_HEIGHT: u32 = 720;
 const RENDER_DISTANCE: i32 = 5;
+const SKY_COLOR: Vec3 = Vec3::new(0.53, 0.81, 0.92);
+// Distance haze in the open air
+const FOG_START: f32 = 80.0;
+const FOG_END: f32 = 200.0;
+// How far away the edit keys pick blocks
+const EDIT_REACH: f32 = 64.0;
+// Radius of spheres the O key builds
+const SPHERE_RADIUS: f32 = 4.0;
+const TERRAIN_CONFIG_PATH: &str = "terrain.toml";
+// Seed of a new world; saved worlds keep their own
+const WORLD_SEED: u32 = 12345;
+const SAVE_PATH: &str = "save/level.toml";
+// Player edits and their undo history
+const HISTORY_PATH: &str = "save/history.bin";
+// Seconds between saves while playing, so a crash loses little
+const AUTOSAVE_INTERVAL: f32 = 60.0;
+const PLAYER_NAME: &str = "player";
+// Seconds before a dropped item can be picked up again
+const PICKUP_DELAY: f32 = 0.5;
 
 fn main() {
     // Initialize GLFW
@@ -68,60 +89,80 @@ fn main() {
     let shader = Shader::new("shaders/voxel.vert", "shaders/voxel.frag");
     let texture = TextureAtlas::new();
     let mut world = World::new();
-    let terrain_gen = TerrainGenerator::new(12345);
-
-    let mut meshes: HashMap<(i32, i32), mesh::Mesh> = HashMap::new();
-
-    // Pass 1: Generate all chunks
-    println!("Generating world (Pass 1/2: Chunks)...");
-    for cx in -RENDER_DISTANCE..=RENDER_DISTANCE {
-        for cz in -RENDER_DISTANCE..=RENDER_DISTANCE {
-            let chunk = terrain_gen.generate_chunk(cx, cz);
-            world.chunks.insert((cx, cz), chunk);
+    world.history = match EditHistory::load_or_default(HISTORY_PATH) {
+        Ok(history) => history,
+        Err(err) => {
+            println!("{} ({}), fix or move it to start", err, HISTORY_PATH);
+            std::process::exit(1);
         }
-        glfw.poll_events(); // Keep window responsive
-    }
-
-    // Pass 2: Generate all meshes (now that neighbors exist)
-    println!("Generating world (Pass 2/2: Meshes)...");
-    for cx in -RENDER_DISTANCE..=RENDER_DISTANCE {
-        for cz in -RENDER_DISTANCE..=RENDER_DISTANCE {
-            if let Some(chunk) = world.chunks.get(&(cx, cz)) {
-                let mesh = generate_chunk_mesh(&world, chunk);
-                meshes.insert((cx, cz), mesh);
-            }
+    };
+    // Better not to start than to replace a damaged save with a new world
+    let mut level = match LevelData::load_or_new(SAVE_PATH, WORLD_SEED) {
+        Ok(level) => level,
+        Err(err) => {
+            println!("{} ({}), fix or move it to start", err, SAVE_PATH);
+            std::process::exit(1);
+        }
+    };
+    world.seed = level.seed;
+    world.random_tick_speed = level.random_tick_speed;
+    world.set_game_tick(level.game_tick);
+    let terrain_config = TerrainConfig::load_or_default(TERRAIN_CONFIG_PATH);
+    world.growth = terrain_config.growth.clone();
+    world.trees = terrain_config.trees.clone();
+    let terrain_gen = TerrainGenerator::new(level.seed, terrain_config.clone());
+
+    for kind in StructureKind::ALL {
+        if let Some(start) = terrain_gen.find_nearest_structure(kind, (0, 0, 0)) {
+            println!("Nearest {:?} at {:?}", start.kind, start.origin);
         }
-        glfw.poll_events(); // Keep window responsive
-        println!("Generated mesh row: cx={}", cx);
     }
-    println!("World generation complete!");
 
-    // Find spawn height (highest block at 0,0) - Spawn from the SKY!
-    let mut spawn_y = 150.0;
-    for y in (0..256).rev() {
-        if let Some(block) = world.get_block(0, y, 0) {
-            if block.is_solid() {
-                spawn_y = y as f32 + 60.0; // Start 60 blocks above the ground
-                break;
-            }
-        }
+    world.add_dimension(DimensionId::Overworld, Box::new(terrain_gen));
+    world.add_dimension(
+        DimensionId::Sky,
+        Box::new(SkyIslandGenerator::new(level.seed, terrain_config)),
+    );
+
+    let mut meshes: HashMap<(i32, i32), mesh::Mesh> = HashMap::new();
+    // Rebuilt every frame from the moving entities
+    let mut entity_mesh = mesh::Mesh::new(&[]);
+    load_area(&mut world, &mut meshes, &mut glfw, (0, 0));
+
+    // A new world picks its spawn on the first start
+    if level.spawn.is_none() {
+        let position =
+            spawn::find_spawn(&world, (0, 0), SPAWN_SEARCH_RADIUS).unwrap_or_else(|| {
+                // Nowhere safe nearby: fall back to the top of the origin column
+                (0, world.height_at(0, 0).map_or(150, |y| y + 1), 0)
+            });
+        level.spawn = Some(SpawnPoint {
+            dimension: DimensionId::Overworld,
+            position,
+        });
+        save_world(&mut level, &world);
     }
-    println!("Dropping in from Y={}", spawn_y);
 
-    let mut camera = Camera::new(Vec3::new(0.0, spawn_y, 0.0));
-    let mut velocity = Vec3::ZERO;
-    let mut is_flying = false; // Start in walking mode
-    let gravity = -20.0; // Negative for downward
+    let mut player = PlayerController::new(Vec3::ZERO, PlayerConfig::default());
+    respawn(&mut world, &mut meshes, &mut glfw, &level, &mut player);
+    let mut camera = Camera::new(player.position);
 
     let mut is_paused = false;
     let mut last_frame = glfw.get_time() as f32;
+    let mut last_save = last_frame;
     let mut delta_time;
     let mut last_x = SCR_WIDTH as f32 / 2.0;
     let mut last_y = SCR_HEIGHT as f32 / 2.0;
     let mut first_mouse = true;
-    let mut last_space_time = 0.0;
-    let speed = 10.0;
+    let mut jump_pressed = false;
     let mut held_block = BlockType::Stone;
+    // Spawned mobs take turns to wander, follow and flee
+    let mut mob_spawns = 0;
+    // Level editing: two corners picked with the bracket keys and what was
+    // last copied or cut
+    let mut selection = Selection::default();
+    let mut clipboard: Option<Clipboard> = None;
+    let mut timestep = FixedTimestep::new(TICK_RATE);
 
     // Main loop
     while !window.should_close() {
@@ -142,13 +183,78 @@ fn main() {
                         first_mouse = true;
                     }
                 }
-                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => {
+                glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) if !is_paused => {
                     // Double-tap to toggle flying
-                    if current_frame - last_space_time < 0.3 {
-                        is_flying = !is_flying;
-                        println!("Flying mode: {}", if is_flying { "ON" } else { "OFF" });
+                    jump_pressed = true;
+                }
+                glfw::WindowEvent::Key(Key::G, _, Action::Press, _) if !is_paused => {
+                    // Spectator: fly through blocks
+                    let mode = if player.mode == MovementMode::Spectator {
+                        MovementMode::Walk
+                    } else {
+                        MovementMode::Spectator
+                    };
+                    player.set_mode(mode);
+                    println!("Movement mode: {:?}", mode);
+                }
+                glfw::WindowEvent::Key(Key::Tab, _, Action::Press, _) if !is_paused => {
+                    // Hop between the overworld and the sky islands
+                    let next = match world.dimension {
+                        DimensionId::Overworld => DimensionId::Sky,
+                        DimensionId::Sky => DimensionId::Overworld,
+                    };
+                    world.switch_dimension(next);
+                    let center = (
+                        (player.position.x.floor() as i32).div_euclid(CHUNK_SIZE as i32),
+                        (player.position.z.floor() as i32).div_euclid(CHUNK_SIZE as i32),
+                    );
+                    load_area(&mut world, &mut meshes, &mut glfw, center);
+                    println!("Entered the {} dimension", next.name());
+
+                    // Land on whatever is below, or hover if there is nothing
+                    let mut position = player.position;
+                    match world.height_at(position.x.floor() as i32, position.z.floor() as i32) {
+                        Some(y) => position.y = y as f32 + 1.5,
+                        None => player.set_mode(MovementMode::Fly),
+                    }
+                    player.teleport(position);
+                }
+                glfw::WindowEvent::Key(Key::B, _, Action::Press, _) if !is_paused => {
+                    // Checkpoint: respawn here instead of at the world spawn
+                    let position = (
+                        player.position.x.floor() as i32,
+                        player.position.y.floor() as i32,
+                        player.position.z.floor() as i32,
+                    );
+                    if spawn::is_safe_spawn(&world, position.0, position.1, position.2) {
+                        let point = SpawnPoint {
+                            dimension: world.dimension,
+                            position,
+                        };
+                        level.respawns.insert(PLAYER_NAME.to_string(), point);
+                        save_world(&mut level, &world);
+                        println!("Checkpoint set at {:?}", position);
+                    } else {
+                        println!("Can't set a checkpoint here: stand on solid ground");
                     }
-                    last_space_time = current_frame;
+                }
+                glfw::WindowEvent::Key(Key::M, _, Action::Press, _) if !is_paused => {
+                    // A mob a few blocks ahead
+                    let ahead = Vec3::new(camera.front.x, 0.0, camera.front.z).normalize_or_zero();
+                    let behaviour =
+                        [Behaviour::Wander, Behaviour::Follow, Behaviour::Flee][mob_spawns % 3];
+                    mob_spawns += 1;
+                    let mob = Entity::new(EntityKind::Mob, player.position + ahead * 3.0)
+                        .with_brain(MobBrain::new(behaviour));
+                    if world.spawn_entity(mob).is_some() {
+                        println!("Spawned a mob that will {:?}", behaviour);
+                    }
+                }
+                glfw::WindowEvent::Key(Key::F, _, Action::Press, _) if !is_paused => {
+                    // Throw a projectile where the camera points
+                    let projectile = Entity::new(EntityKind::Projectile, player.eye())
+                        .with_velocity(camera.front * 25.0);
+                    world.spawn_entity(projectile);
                 }
                 glfw::WindowEvent::Key(Key::Num1, _, Action::Press, _) => {
                     held_block = BlockType::Dirt
@@ -165,170 +271,238 @@ fn main() {
                 glfw::WindowEvent::Key(Key::Num5, _, Action::Press, _) => {
                     held_block = BlockType::Leaves
                 }
-                glfw::WindowEvent::CursorPos(xpos, ypos) => {
-                    if !is_paused {
-                        if first_mouse {
-                            last_x = xpos as f32;
-                            last_y = ypos as f32;
-                            first_mouse = false;
-                        }
-                        let xoffset = xpos as f32 - last_x;
-                        let yoffset = last_y - ypos as f32;
-                        last_x = xpos as f32;
-                        last_y = ypos as f32;
-                        camera.process_mouse(xoffset, yoffset);
+                glfw::WindowEvent::Key(Key::Num6, _, Action::Press, _) => {
+                    held_block = BlockType::OakSapling
+                }
+                glfw::WindowEvent::Key(Key::Num7, _, Action::Press, _) => {
+                    held_block = BlockType::Wheat
+                }
+                glfw::WindowEvent::Key(Key::Num8, _, Action::Press, _) => {
+                    held_block = BlockType::Door
+                }
+                glfw::WindowEvent::Key(Key::Z, _, Action::Press, _)
+                    if !is_paused && control_held(&window) =>
+                {
+                    match world.undo() {
+                        Some(set) => println!("Undid {} blocks", set),
+                        None => println!("Nothing to undo here"),
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Y, _, Action::Press, _)
+                    if !is_paused && control_held(&window) =>
+                {
+                    match world.redo() {
+                        Some(set) => println!("Redid {} blocks", set),
+                        None => println!("Nothing to redo here"),
                     }
                 }
-                glfw::WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
-                    if !is_paused {
-                        // Break block
-                        if let Some(hit) = raycast(camera.position, camera.front, 10.0, &world) {
-                            world.set_block(
-                                hit.block_pos.0,
-                                hit.block_pos.1,
-                                hit.block_pos.2,
-                                Block::new(BlockType::Air),
+                glfw::WindowEvent::Key(
+                    Key::LeftBracket | Key::RightBracket,
+                    _,
+                    Action::Press,
+                    _,
+                ) if !is_paused => {
+                    if let Some(hit) = raycast(player.eye(), camera.front, EDIT_REACH, &world) {
+                        if let glfw::WindowEvent::Key(Key::LeftBracket, ..) = event {
+                            selection.first = Some(hit.block_pos);
+                        } else {
+                            selection.second = Some(hit.block_pos);
+                        }
+                        if let Some(region) = selection.region() {
+                            println!(
+                                "Selected {:?} to {:?}, {} blocks",
+                                region.min,
+                                region.max,
+                                region.volume()
                             );
-                            // Regenerate chunk mesh
-                            let chunk_x = hit.block_pos.0.div_euclid(CHUNK_SIZE as i32);
-                            let chunk_z = hit.block_pos.2.div_euclid(CHUNK_SIZE as i32);
-                            if let Some(chunk) = world.chunks.get(&(chunk_x, chunk_z)) {
-                                let new_mesh = generate_chunk_mesh(&world, chunk);
-                                meshes.insert((chunk_x, chunk_z), new_mesh);
-                            }
                         }
                     }
                 }
-                glfw::WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _) => {
-                    if !is_paused {
-                        // Place block
-                        if let Some(hit) = raycast(camera.position, camera.front, 10.0, &world) {
-                            let place_pos = (
-                                hit.block_pos.0 + hit.face_normal.x as i32,
-                                hit.block_pos.1 + hit.face_normal.y as i32,
-                                hit.block_pos.2 + hit.face_normal.z as i32,
-                            );
-                            world.set_block(
-                                place_pos.0,
-                                place_pos.1,
-                                place_pos.2,
-                                Block::new(held_block),
-                            );
-                            // Regenerate chunk mesh
-                            let chunk_x = place_pos.0.div_euclid(CHUNK_SIZE as i32);
-                            let chunk_z = place_pos.2.div_euclid(CHUNK_SIZE as i32);
-                            if let Some(chunk) = world.chunks.get(&(chunk_x, chunk_z)) {
-                                let new_mesh = generate_chunk_mesh(&world, chunk);
-                                meshes.insert((chunk_x, chunk_z), new_mesh);
+                glfw::WindowEvent::Key(
+                    Key::L | Key::H | Key::K | Key::C | Key::X,
+                    _,
+                    Action::Press,
+                    _,
+                ) if !is_paused => {
+                    let Some(region) = selection.region() else {
+                        println!("Pick two corners with [ and ] first");
+                        continue;
+                    };
+                    let block = Block::placed(held_block);
+                    match event {
+                        glfw::WindowEvent::Key(Key::L, ..) => {
+                            let set = edit::fill(&mut world, region, block);
+                            println!("Filled {} blocks", set);
+                        }
+                        glfw::WindowEvent::Key(Key::H, ..) => {
+                            let set = edit::hollow_box(&mut world, region, block);
+                            println!("Built a box of {} blocks", set);
+                        }
+                        glfw::WindowEvent::Key(Key::K, ..) => {
+                            // The type of the block looked at becomes the held one
+                            let target = raycast(player.eye(), camera.front, EDIT_REACH, &world)
+                                .and_then(|hit| {
+                                    let (x, y, z) = hit.block_pos;
+                                    world.get_block(x, y, z)
+                                });
+                            if let Some(target) = target {
+                                let set =
+                                    edit::replace(&mut world, region, target.block_type, block);
+                                println!("Replaced {} blocks", set);
                             }
                         }
+                        glfw::WindowEvent::Key(Key::C, ..) => {
+                            clipboard = Some(edit::copy(&world, region));
+                            println!("Copied {} blocks", region.volume());
+                        }
+                        _ => {
+                            clipboard = Some(edit::cut(&mut world, region));
+                            println!("Cut {} blocks", region.volume());
+                        }
                     }
                 }
-                _ => {}
-            }
-        }
-
-        if !is_paused {
-            // Movement
-            let mut move_dir = Vec3::ZERO;
-            if window.get_key(Key::W) == Action::Press {
-                move_dir += camera.front;
-            }
-            if window.get_key(Key::S) == Action::Press {
-                move_dir -= camera.front;
-            }
-            if window.get_key(Key::A) == Action::Press {
-                move_dir -= camera.right;
-            }
-            if window.get_key(Key::D) == Action::Press {
-                move_dir += camera.right;
-            }
-
-            if is_flying {
-                // Flying mode
-                if window.get_key(Key::Space) == Action::Press {
-                    move_dir += camera.world_up;
-                }
-                if window.get_key(Key::LeftShift) == Action::Press {
-                    move_dir -= camera.world_up;
+                glfw::WindowEvent::Key(Key::V, _, Action::Press, _) if !is_paused => {
+                    // The clipboard's lowest corner goes in front of the face looked at
+                    if let Some(clipboard) = &clipboard
+                        && let Some(hit) = raycast(player.eye(), camera.front, EDIT_REACH, &world)
+                    {
+                        let set = edit::paste(&mut world, clipboard, hit.previous);
+                        println!("Pasted {} blocks", set);
+                    }
                 }
-
-                if move_dir.length_squared() > 0.0 {
-                    move_dir = move_dir.normalize();
+                glfw::WindowEvent::Key(Key::R, _, Action::Press, _) if !is_paused => {
+                    if let Some(copied) = &clipboard {
+                        clipboard = Some(copied.rotated(1));
+                        println!("Turned the clipboard a quarter turn");
+                    }
                 }
-
-                let displacement = move_dir * speed * delta_time;
-
-                // Apply collision
-                let mut next_pos = camera.position;
-                next_pos.x += displacement.x;
-                if !check_collision(next_pos, &world) {
-                    camera.position.x += displacement.x;
+                glfw::WindowEvent::Key(Key::N, _, Action::Press, _) if !is_paused => {
+                    if let Some(copied) = &clipboard {
+                        clipboard = Some(copied.mirrored(Axis::X));
+                        println!("Mirrored the clipboard");
+                    }
                 }
-
-                next_pos = camera.position;
-                next_pos.y += displacement.y;
-                if !check_collision(next_pos, &world) {
-                    camera.position.y += displacement.y;
+                glfw::WindowEvent::Key(Key::O, _, Action::Press, _) if !is_paused => {
+                    if let Some(hit) = raycast(player.eye(), camera.front, EDIT_REACH, &world) {
+                        let block = Block::placed(held_block);
+                        let set = edit::sphere(&mut world, hit.block_pos, SPHERE_RADIUS, block);
+                        println!("Built a sphere of {} blocks", set);
+                    }
                 }
-
-                next_pos = camera.position;
-                next_pos.z += displacement.z;
-                if !check_collision(next_pos, &world) {
-                    camera.position.z += displacement.z;
+                glfw::WindowEvent::CursorPos(xpos, ypos) if !is_paused => {
+                    if first_mouse {
+                        last_x = xpos as f32;
+                        last_y = ypos as f32;
+                        first_mouse = false;
+                    }
+                    let xoffset = xpos as f32 - last_x;
+                    let yoffset = last_y - ypos as f32;
+                    last_x = xpos as f32;
+                    last_y = ypos as f32;
+                    camera.process_mouse(xoffset, yoffset);
                 }
-
-                velocity = Vec3::ZERO;
-            } else {
-                // Walking mode
-                move_dir.y = 0.0;
-                if move_dir.length_squared() > 0.0 {
-                    move_dir = move_dir.normalize();
+                glfw::WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _)
+                    if !is_paused =>
+                {
+                    // Break block, dropping it as an item
+                    if let Some(hit) = raycast(player.eye(), camera.front, 10.0, &world) {
+                        let (x, y, z) = hit.block_pos;
+                        if let Some(block) = world.get_block(x, y, z) {
+                            let center = Vec3::new(x as f32 + 0.5, y as f32 + 0.25, z as f32 + 0.5);
+                            let item = Entity::new(EntityKind::Item(block.block_type), center)
+                                .with_velocity(Vec3::new(0.0, 4.0, 0.0));
+                            world.spawn_entity(item);
+                        }
+                        let (x, y, z) = hit.block_pos;
+                        world.transaction(|world| {
+                            world.set_block(x, y, z, Block::new(BlockType::Air))
+                        });
+                    }
                 }
-
-                let target_vel = move_dir * speed;
-                velocity.x = target_vel.x;
-                velocity.z = target_vel.z;
-
-                // Gravity
-                velocity.y += gravity * delta_time;
-
-                // Jumping
-                if window.get_key(Key::Space) == Action::Press {
-                    // Check if on ground
-                    let mut ground_check = camera.position;
-                    ground_check.y -= 0.1;
-                    if check_collision(ground_check, &world) {
-                        velocity.y = 8.0; // Jump force
+                glfw::WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _)
+                    if !is_paused =>
+                {
+                    let hit = raycast(player.eye(), camera.front, 10.0, &world);
+                    let door = hit.as_ref().and_then(|hit| {
+                        let (x, y, z) = hit.block_pos;
+                        world
+                            .get_block(x, y, z)
+                            .filter(|block| block.block_type == BlockType::Door)
+                            .map(|door| (hit.block_pos, door))
+                    });
+                    // Doors swing open or shut; anything else gets a block
+                    // placed in the empty cell in front of the hit face
+                    if let Some(((x, y, z), door)) = door {
+                        world.transaction(|world| world.set_block(x, y, z, door.toggle_door()));
+                    } else if let Some(hit) = hit
+                        && hit.previous != hit.block_pos
+                    {
+                        let (x, y, z) = hit.previous;
+                        world.transaction(|world| {
+                            world.set_block(x, y, z, Block::placed(held_block))
+                        });
                     }
                 }
+                _ => {}
+            }
+        }
 
-                let displacement = velocity * delta_time;
-
-                // Collision
-                let mut next_pos = camera.position;
-                next_pos.x += displacement.x;
-                if check_collision(next_pos, &world) {
-                    velocity.x = 0.0;
-                } else {
-                    camera.position.x += displacement.x;
+        // Simulation: the player moves in fixed ticks, however long the frame
+        let ticks = if is_paused {
+            0
+        } else {
+            timestep.advance(delta_time)
+        };
+        let input = PlayerInput {
+            look: camera.front,
+            forward: key_axis(&window, Key::W, Key::S),
+            strafe: key_axis(&window, Key::D, Key::A),
+            jump: window.get_key(Key::Space) == Action::Press,
+            jump_pressed,
+            sprint: window.get_key(Key::LeftControl) == Action::Press,
+            sneak: window.get_key(Key::LeftShift) == Action::Press,
+        };
+        for tick in 0..ticks {
+            // Presses count once, in the first tick after them
+            let input = PlayerInput {
+                jump_pressed: input.jump_pressed && tick == 0,
+                ..input
+            };
+            player.tick(&input, &world, timestep.tick_length());
+            jump_pressed = false;
+            world.tick(timestep.tick_length(), Some(player.position));
+
+            // Walking into a dropped item picks it up into the hand
+            let player_box = player.aabb();
+            let reach = AABB::new(player_box.min - 0.5, player_box.max + 0.5);
+            let picked = world.remove_entities(|entity| {
+                matches!(entity.kind, EntityKind::Item(_))
+                    && entity.age > PICKUP_DELAY
+                    && entity.aabb().intersects(&reach)
+            });
+            for item in picked {
+                if let EntityKind::Item(block_type) = item.kind {
+                    held_block = block_type;
+                    println!("Picked up {:?}", block_type);
                 }
+            }
 
-                next_pos = camera.position;
-                next_pos.z += displacement.z;
-                if check_collision(next_pos, &world) {
-                    velocity.z = 0.0;
-                } else {
-                    camera.position.z += displacement.z;
-                }
+            if spawn::fell_out_of_world(player.position.y) {
+                println!("Fell out of the world");
+                respawn(&mut world, &mut meshes, &mut glfw, &level, &mut player);
+            }
+        }
+        camera.position = player.interpolated_eye(timestep.alpha());
 
-                next_pos = camera.position;
-                next_pos.y += displacement.y;
-                if check_collision(next_pos, &world) {
-                    velocity.y = 0.0;
-                } else {
-                    camera.position.y += displacement.y;
-                }
+        if current_frame - last_save >= AUTOSAVE_INTERVAL {
+            save_world(&mut level, &world);
+            last_save = current_frame;
+        }
+
+        // Rebuild the meshes of chunks whose blocks changed this frame
+        for (chunk_x, chunk_z) in world.take_changed_chunks() {
+            if let Some(chunk) = world.chunks.get(&(chunk_x, chunk_z)) {
+                meshes.insert((chunk_x, chunk_z), generate_chunk_mesh(&world, chunk));
             }
         }
 
@@ -340,9 +514,15 @@ fn main() {
         );
         window.set_title(&title);
 
+        // With the head in a fluid the view takes its colour and the fog closes in
+        let (fog_color, fog_start, fog_end) = match player.head_fluid(&world) {
+            Some(fluid) => (fluid.fog_color, 0.0, fluid.fog_distance),
+            None => (SKY_COLOR, FOG_START, FOG_END),
+        };
+
         // Render
         unsafe {
-            gl::ClearColor(0.53, 0.81, 0.92, 1.0);
+            gl::ClearColor(fog_color.x, fog_color.y, fog_color.z, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
@@ -362,6 +542,9 @@ fn main() {
         shader.set_vec3("lightDir", &Vec3::new(0.5, -1.0, 0.5));
         shader.set_int("blockTexture", 0);
         shader.set_bool("isWater", false);
+        shader.set_vec3("fogColor", &fog_color);
+        shader.set_float("fogStart", fog_start);
+        shader.set_float("fogEnd", fog_end);
 
         // Render chunks
         for ((cx, cz), mesh) in &meshes {
@@ -375,6 +558,11 @@ fn main() {
             mesh.draw();
         }
 
+        // Entities, already in world space
+        mesh::update_entity_mesh(&mut entity_mesh, world.entities(), timestep.alpha());
+        shader.set_mat4("model", &Mat4::IDENTITY);
+        entity_mesh.draw();
+
         // Crosshair
         unsafe {
             gl::Disable(gl::DEPTH_TEST);
@@ -388,4 +576,98 @@ fn main() {
 
         window.swap_buffers();
     }
+
+    save_world(&mut level, &world);
+}
+
+// Puts the player at their bed or checkpoint, or else at the world spawn,
+// moving them aside if the spot has been built over
+fn respawn(
+    world: &mut World,
+    meshes: &mut HashMap<(i32, i32), mesh::Mesh>,
+    glfw: &mut glfw::Glfw,
+    level: &LevelData,
+    player: &mut PlayerController,
+) {
+    let Some(point) = level.respawn_point(PLAYER_NAME) else {
+        return;
+    };
+    let (x, _, z) = point.position;
+    let center = (
+        x.div_euclid(CHUNK_SIZE as i32),
+        z.div_euclid(CHUNK_SIZE as i32),
+    );
+    if world.dimension != point.dimension || !world.chunks.contains_key(&center) {
+        world.switch_dimension(point.dimension);
+        load_area(world, meshes, glfw, center);
+    }
+
+    let (x, y, z) = spawn::settle(world, point.position).unwrap_or(point.position);
+    player.teleport(Vec3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5));
+    println!(
+        "Spawned at ({}, {}, {}) in the {}",
+        x,
+        y,
+        z,
+        point.dimension.name()
+    );
+}
+
+// 1 if the first key is held, -1 for the second, 0 for both or neither
+fn key_axis(window: &glfw::PWindow, positive: Key, negative: Key) -> f32 {
+    let held = |key| window.get_key(key) == Action::Press;
+    held(positive) as i32 as f32 - held(negative) as i32 as f32
+}
+
+// Either control key, which undo and redo need held
+fn control_held(window: &glfw::PWindow) -> bool {
+    [Key::LeftControl, Key::RightControl]
+        .into_iter()
+        .any(|key| window.get_key(key) == Action::Press)
+}
+
+// Saves the level, with the game tick the world has reached, and the edit
+// history together, so the edits always belong to the level next to them
+fn save_world(level: &mut LevelData, world: &World) {
+    level.game_tick = world.game_tick();
+    if let Err(err) = level.save(SAVE_PATH) {
+        println!("{}", err);
+    }
+    if let Err(err) = world.history.save(HISTORY_PATH) {
+        println!("{}", err);
+    }
+}
+
+// Generates the chunks around a chunk in the current dimension and rebuilds
+// their meshes
+fn load_area(
+    world: &mut World,
+    meshes: &mut HashMap<(i32, i32), mesh::Mesh>,
+    glfw: &mut glfw::Glfw,
+    center: (i32, i32),
+) {
+    let (center_x, center_z) = center;
+    // Pass 1: Generate all chunks
+    println!("Generating world (Pass 1/2: Chunks)...");
+    for cx in center_x - RENDER_DISTANCE..=center_x + RENDER_DISTANCE {
+        for cz in center_z - RENDER_DISTANCE..=center_z + RENDER_DISTANCE {
+            world.load_chunk(cx, cz);
+        }
+        glfw.poll_events(); // Keep window responsive
+    }
+
+    // Pass 2: Generate all meshes (now that neighbors exist)
+    println!("Generating world (Pass 2/2: Meshes)...");
+    meshes.clear();
+    for cx in center_x - RENDER_DISTANCE..=center_x + RENDER_DISTANCE {
+        for cz in center_z - RENDER_DISTANCE..=center_z + RENDER_DISTANCE {
+            if let Some(chunk) = world.chunks.get(&(cx, cz)) {
+                let mesh = generate_chunk_mesh(world, chunk);
+                meshes.insert((cx, cz), mesh);
+            }
+        }
+        glfw.poll_events(); // Keep window responsive
+        println!("Generated mesh row: cx={}", cx);
+    }
+    println!("World generation complete!");
 }
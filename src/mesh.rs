@@ -1,10 +1,20 @@
-use crate::block::BlockType;
-use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk};
+use crate::block::{Block, BlockType, CROP_STAGES};
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk, HeightmapKind};
+use crate::entity::Entity;
+use crate::fluid::surface_height;
 use crate::texture::TextureAtlas;
 use crate::world::World;
 
+// Brightness of faces the sky can't reach
+const SHADED_SKY_LIGHT: f32 = 0.6;
+// How thick a door panel is
+const DOOR_THICKNESS: f32 = 0.1875;
+
 pub struct Mesh {
     vao: u32,
+    vbo: u32,
+    // Bytes the vertex buffer has room for
+    capacity: isize,
     pub vertex_count: i32,
 }
 
@@ -21,7 +31,7 @@ impl Mesh {
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (vertices.len() * std::mem::size_of::<f32>()) as isize,
+                std::mem::size_of_val(vertices) as isize,
                 vertices.as_ptr() as *const _,
                 gl::STATIC_DRAW,
             );
@@ -75,10 +85,34 @@ impl Mesh {
 
         Self {
             vao,
+            vbo,
+            capacity: std::mem::size_of_val(vertices) as isize,
             vertex_count: (vertices.len() / 9) as i32,
         }
     }
 
+    // Replaces the vertices in the same buffer, only growing it when they
+    // don't fit. For meshes rebuilt every frame.
+    pub fn update(&mut self, vertices: &[f32]) {
+        let size = std::mem::size_of_val(vertices) as isize;
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            if size > self.capacity {
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    size,
+                    vertices.as_ptr() as *const _,
+                    gl::DYNAMIC_DRAW,
+                );
+                self.capacity = size;
+            } else {
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, vertices.as_ptr() as *const _);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        self.vertex_count = (vertices.len() / 9) as i32;
+    }
+
     pub fn draw(&self) {
         unsafe {
             gl::BindVertexArray(self.vao);
@@ -88,6 +122,15 @@ impl Mesh {
     }
 }
 
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
 // Generate mesh for a chunk with ambient occlusion
 pub fn generate_chunk_mesh(world: &World, chunk: &Chunk) -> Mesh {
     let mut vertices = Vec::new();
@@ -100,6 +143,53 @@ pub fn generate_chunk_mesh(world: &World, chunk: &Chunk) -> Mesh {
                     continue;
                 }
 
+                // Plants are two crossed quads and never hide neighbours
+                if block.is_cross() {
+                    let light = sky_light(world, chunk, x as i32, y as i32, z as i32);
+                    // Crops grow taller with every stage
+                    let height = if block.block_type == BlockType::Wheat {
+                        (block.level + 1) as f32 / CROP_STAGES as f32
+                    } else {
+                        1.0
+                    };
+                    add_cross(
+                        &mut vertices,
+                        x as f32,
+                        y as f32,
+                        z as f32,
+                        &block.block_type,
+                        height,
+                        light,
+                    );
+                    continue;
+                }
+
+                // Doors are a thin panel and never hide neighbours either
+                if block.block_type == BlockType::Door {
+                    let light = sky_light(world, chunk, x as i32, y as i32, z as i32);
+                    add_door(
+                        &mut vertices,
+                        x as f32,
+                        y as f32,
+                        z as f32,
+                        block.is_open_door(),
+                        light,
+                    );
+                    continue;
+                }
+
+                // Water and snow layers are lower than a full block
+                let top = match block.block_type {
+                    BlockType::Water => Some(water_corners(
+                        world,
+                        chunk.x * CHUNK_SIZE as i32 + x as i32,
+                        y as i32,
+                        chunk.z * CHUNK_SIZE as i32 + z as i32,
+                    )),
+                    BlockType::SnowLayer => Some([0.125; 4]),
+                    _ => None,
+                };
+
                 // Check each face
                 for face in 0..6 {
                     if should_render_face(
@@ -111,6 +201,10 @@ pub fn generate_chunk_mesh(world: &World, chunk: &Chunk) -> Mesh {
                         face,
                         &block.block_type,
                     ) {
+                        // Lit by the sky if the space in front of the face is
+                        let (dx, dy, dz) = face_offset(face);
+                        let light =
+                            sky_light(world, chunk, x as i32 + dx, y as i32 + dy, z as i32 + dz);
                         add_face(
                             &mut vertices,
                             x as f32,
@@ -122,6 +216,8 @@ pub fn generate_chunk_mesh(world: &World, chunk: &Chunk) -> Mesh {
                             x as i32,
                             y as i32,
                             z as i32,
+                            top,
+                            light,
                         );
                     }
                 }
@@ -132,6 +228,47 @@ pub fn generate_chunk_mesh(world: &World, chunk: &Chunk) -> Mesh {
     Mesh::new(&vertices)
 }
 
+// Every entity as a textured box in world space, placed alpha of the way
+// between its last two ticks. Refills the given mesh so the same buffers
+// serve every frame.
+pub fn update_entity_mesh<'a>(
+    mesh: &mut Mesh,
+    entities: impl Iterator<Item = &'a Entity>,
+    alpha: f32,
+) {
+    let mut vertices = Vec::new();
+    for entity in entities {
+        let aabb = entity.kind.hitbox().at(entity.interpolated_position(alpha));
+        let size = aabb.max - aabb.min;
+        let block_type = entity.kind.model_block();
+        for face in 0..6 {
+            let (positions, normal) = get_face_data(face);
+            let (u_min, v_min, u_max, v_max) = TextureAtlas::get_uv(&block_type, face);
+            let uvs = [
+                (u_min, v_max),
+                (u_max, v_max),
+                (u_max, v_min),
+                (u_min, v_min),
+            ];
+            for i in [0, 1, 2, 0, 2, 3] {
+                let pos = positions[i];
+                vertices.extend_from_slice(&[
+                    aabb.min.x + pos.0 * size.x,
+                    aabb.min.y + pos.1 * size.y,
+                    aabb.min.z + pos.2 * size.z,
+                    uvs[i].0,
+                    uvs[i].1,
+                    normal.0,
+                    normal.1,
+                    normal.2,
+                    1.0,
+                ]);
+            }
+        }
+    }
+    mesh.update(&vertices);
+}
+
 fn should_render_face(
     world: &World,
     chunk: &Chunk,
@@ -141,15 +278,7 @@ fn should_render_face(
     face: usize,
     block_type: &BlockType,
 ) -> bool {
-    let (dx, dy, dz) = match face {
-        0 => (-1, 0, 0), // Left
-        1 => (1, 0, 0),  // Right
-        2 => (0, -1, 0), // Bottom
-        3 => (0, 1, 0),  // Top
-        4 => (0, 0, -1), // Back
-        5 => (0, 0, 1),  // Front
-        _ => (0, 0, 0),
-    };
+    let (dx, dy, dz) = face_offset(face);
 
     let nx = x + dx;
     let ny = y + dy;
@@ -183,12 +312,17 @@ fn should_render_face(
         if n_block.block_type == BlockType::Water {
             return false;
         }
-        return n_block.block_type == BlockType::Air || n_block.block_type == BlockType::Leaves;
+        return n_block.block_type == BlockType::Air || n_block.is_leaves() || n_block.is_cross();
+    }
+
+    // Neighbouring snow layers are flush, so the faces between them are hidden
+    if *block_type == BlockType::SnowLayer && n_block.block_type == BlockType::SnowLayer {
+        return false;
     }
 
     // Leaves should not cull against other leaves to look "fancy" and not hollow
-    if *block_type == BlockType::Leaves {
-        if n_block.block_type == BlockType::Leaves {
+    if Block::new(*block_type).is_leaves() {
+        if n_block.is_leaves() {
             return true; // Render internal leaf faces
         }
         return n_block.is_transparent();
@@ -197,6 +331,39 @@ fn should_render_face(
     n_block.is_transparent()
 }
 
+fn face_offset(face: usize) -> Offset {
+    match face {
+        0 => (-1, 0, 0), // Left
+        1 => (1, 0, 0),  // Right
+        2 => (0, -1, 0), // Bottom
+        3 => (0, 1, 0),  // Top
+        4 => (0, 0, -1), // Back
+        5 => (0, 0, 1),  // Front
+        _ => (0, 0, 0),
+    }
+}
+
+// Full light where nothing above blocks the sky, dimmer under overhangs,
+// leaves and water. Takes chunk-local coordinates.
+fn sky_light(world: &World, chunk: &Chunk, x: i32, y: i32, z: i32) -> f32 {
+    let top = if (0..CHUNK_SIZE as i32).contains(&x) && (0..CHUNK_SIZE as i32).contains(&z) {
+        chunk
+            .height(HeightmapKind::MotionBlocking, x as usize, z as usize)
+            .map(|top| top as i32)
+    } else {
+        world.heightmap_at(
+            HeightmapKind::MotionBlocking,
+            chunk.x * CHUNK_SIZE as i32 + x,
+            chunk.z * CHUNK_SIZE as i32 + z,
+        )
+    };
+    if top.is_none_or(|top| y > top) {
+        1.0
+    } else {
+        SHADED_SKY_LIGHT
+    }
+}
+
 // Calculate ambient occlusion for a vertex
 fn calculate_ao(chunk: &Chunk, x: i32, y: i32, z: i32, face: usize, corner: usize) -> f32 {
     // Get the 3 neighbors for this corner
@@ -233,10 +400,12 @@ fn is_solid_at(chunk: &Chunk, x: i32, y: i32, z: i32) -> bool {
     block.is_solid()
 }
 
-fn get_ao_neighbors(
-    face: usize,
-    corner: usize,
-) -> ((i32, i32, i32), (i32, i32, i32), (i32, i32, i32)) {
+// Block offset from the face's block
+type Offset = (i32, i32, i32);
+// Corner position or normal, relative to the block
+type Vertex = (f32, f32, f32);
+
+fn get_ao_neighbors(face: usize, corner: usize) -> (Offset, Offset, Offset) {
     // Returns (side1, side2, corner) offsets for AO calculation
     match (face, corner) {
         // Top face (y+)
@@ -249,6 +418,7 @@ fn get_ao_neighbors(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_face(
     vertices: &mut Vec<f32>,
     x: f32,
@@ -260,6 +430,8 @@ fn add_face(
     bx: i32,
     by: i32,
     bz: i32,
+    top: Option<[f32; 4]>,
+    light: f32,
 ) {
     let (positions, normal) = get_face_data(face);
     let (u_min, v_min, u_max, v_max) = TextureAtlas::get_uv(block_type, face);
@@ -285,18 +457,12 @@ fn add_face(
         let mut pos = positions[i];
         let uv = uvs[i];
 
-        // Half-height water
-        if *block_type == BlockType::Water {
-            // If it's the top face (face 3), or the top vertices of side faces
-            if face == 3 {
-                pos.1 = 0.8; // Water is 80% height
-            } else if face != 2 {
-                // Not the bottom face
-                // For side faces, lower the top vertices
-                if pos.1 > 0.0 {
-                    pos.1 = 0.8;
-                }
-            }
+        // Lower the top corners of blocks shorter than a full block: all of
+        // the top face and the upper edge of the sides
+        if let Some(top) = top
+            && pos.1 > 0.0
+        {
+            pos.1 = top[corner_index(pos.0, pos.2)];
         }
 
         // Position
@@ -313,12 +479,138 @@ fn add_face(
         vertices.push(normal.1);
         vertices.push(normal.2);
 
-        // AO
-        vertices.push(ao[i]);
+        // AO, darkened out of the sky light
+        vertices.push(ao[i] * light);
+    }
+}
+
+// Top corners in the order water_corners gives their heights
+fn corner_index(x: f32, z: f32) -> usize {
+    match (x > 0.0, z > 0.0) {
+        (false, false) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+        (false, true) => 3,
+    }
+}
+
+// Height of the water surface at the four top corners of a water block, in
+// the order (0, 0), (1, 0), (1, 1), (0, 1) of x and z. A corner is the
+// average of the water blocks around it, so neighbours meet edge to edge and
+// flowing water slopes down away from its source. Water above any of them
+// fills the corner to the top. Takes world coordinates.
+fn water_corners(world: &World, x: i32, y: i32, z: i32) -> [f32; 4] {
+    let water = |x: i32, y: i32, z: i32| {
+        world
+            .get_block(x, y, z)
+            .filter(|block| block.block_type == BlockType::Water)
+    };
+    [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(cx, cz)| {
+        let mut total = 0.0;
+        let mut count = 0;
+        for (dx, dz) in [(-1, -1), (0, -1), (-1, 0), (0, 0)] {
+            let (bx, bz) = (x + cx + dx, z + cz + dz);
+            if water(bx, y + 1, bz).is_some() {
+                return 1.0;
+            }
+            if let Some(block) = water(bx, y, bz) {
+                total += surface_height(block.level);
+                count += 1;
+            }
+        }
+        // The block itself is always one of the four
+        total / count as f32
+    })
+}
+
+// A door panel along the low z side of the block, swung round to the low x
+// side when open
+fn add_door(vertices: &mut Vec<f32>, x: f32, y: f32, z: f32, open: bool, light: f32) {
+    let size = if open {
+        (DOOR_THICKNESS, 1.0, 1.0)
+    } else {
+        (1.0, 1.0, DOOR_THICKNESS)
+    };
+    for face in 0..6 {
+        let (positions, normal) = get_face_data(face);
+        let (u_min, v_min, u_max, v_max) = TextureAtlas::get_uv(&BlockType::Door, face);
+        let uvs = [
+            (u_min, v_max),
+            (u_max, v_max),
+            (u_max, v_min),
+            (u_min, v_min),
+        ];
+        for i in [0, 1, 2, 0, 2, 3] {
+            let pos = positions[i];
+            vertices.extend_from_slice(&[
+                x + pos.0 * size.0,
+                y + pos.1 * size.1,
+                z + pos.2 * size.2,
+                uvs[i].0,
+                uvs[i].1,
+                normal.0,
+                normal.1,
+                normal.2,
+                light,
+            ]);
+        }
+    }
+}
+
+// Two diagonal quads through the block, used for plants, height blocks tall
+fn add_cross(
+    vertices: &mut Vec<f32>,
+    x: f32,
+    y: f32,
+    z: f32,
+    block_type: &BlockType,
+    height: f32,
+    light: f32,
+) {
+    let (u_min, v_min, u_max, v_max) = TextureAtlas::get_uv(block_type, 0);
+    let quads = [
+        [
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 1.0),
+            (1.0, 1.0, 1.0),
+            (0.0, 1.0, 0.0),
+        ],
+        [
+            (1.0, 0.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (0.0, 1.0, 1.0),
+            (1.0, 1.0, 0.0),
+        ],
+    ];
+    let uvs = [
+        (u_min, v_max),
+        (u_max, v_max),
+        (u_max, v_min),
+        (u_min, v_min),
+    ];
+
+    for positions in &quads {
+        for &i in &[0, 1, 2, 0, 2, 3] {
+            let pos = positions[i];
+
+            vertices.push(x + pos.0);
+            vertices.push(y + pos.1 * height);
+            vertices.push(z + pos.2);
+
+            vertices.push(uvs[i].0);
+            vertices.push(uvs[i].1);
+
+            // Lit like a top face so plants match the ground they stand on
+            vertices.push(0.0);
+            vertices.push(1.0);
+            vertices.push(0.0);
+
+            vertices.push(light);
+        }
     }
 }
 
-fn get_face_data(face: usize) -> ([(f32, f32, f32); 4], (f32, f32, f32)) {
+fn get_face_data(face: usize) -> ([Vertex; 4], Vertex) {
     match face {
         0 => (
             [
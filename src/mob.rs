@@ -0,0 +1,324 @@
+use crate::block::BlockType;
+use crate::pathfinding::{Node, PathRules, find_path, is_standable};
+use crate::rng::{ChunkRng, SALT_WANDER};
+use crate::world::World;
+use glam::Vec3;
+
+// Blocks per second
+const WALK_SPEED: f32 = 3.0;
+const SWIM_SPEED: f32 = 2.0;
+// Same as the player's, enough to clear one block
+const JUMP_VELOCITY: f32 = 8.0;
+// Close enough to a waypoint to head for the next one
+const WAYPOINT_REACH: f32 = 0.3;
+// Followers stop this close to the player and lose interest this far away
+const FOLLOW_DISTANCE: f32 = 2.5;
+const SIGHT_DISTANCE: f32 = 24.0;
+// Fleeing mobs run when the player gets this close, this far at a time
+const FLEE_DISTANCE: f32 = 8.0;
+const FLEE_STEP: f32 = 10.0;
+const WANDER_RADIUS: i32 = 8;
+// Seconds a wanderer stands around between walks
+const IDLE_MIN: f32 = 2.0;
+const IDLE_MAX: f32 = 6.0;
+// A path to a moving goal is searched again at most this often, in seconds
+const REPATH_INTERVAL: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behaviour {
+    // Walks to random nearby spots, resting in between
+    Wander,
+    // Walks up to the player when it can see them
+    Follow,
+    // Runs from the player when they come close
+    Flee,
+}
+
+// What a mob is doing and the path it is walking. The path is kept from
+// tick to tick and only searched again when the goal moves or a block
+// along it changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobBrain {
+    pub behaviour: Behaviour,
+    pub rules: PathRules,
+    // Waypoints still to walk, the next one last
+    path: Vec<Node>,
+    goal: Option<Node>,
+    idle: f32,
+    repath: f32,
+    // Walks picked so far, which seeds the next random choice
+    wanders: i32,
+}
+
+fn node_at(position: Vec3) -> Node {
+    let cell = position.floor().as_ivec3();
+    (cell.x, cell.y, cell.z)
+}
+
+fn centre_of((x, _, z): Node) -> Vec3 {
+    Vec3::new(x as f32 + 0.5, 0.0, z as f32 + 0.5)
+}
+
+impl MobBrain {
+    pub fn new(behaviour: Behaviour) -> Self {
+        Self {
+            behaviour,
+            rules: PathRules::default(),
+            path: Vec::new(),
+            goal: None,
+            idle: 0.0,
+            repath: 0.0,
+            wanders: 0,
+        }
+    }
+
+    pub fn goal(&self) -> Option<Node> {
+        self.goal
+    }
+
+    pub fn has_path(&self) -> bool {
+        !self.path.is_empty()
+    }
+
+    // Called for every block the world changes. Only a path that moves
+    // through, stands on or jumps or drops past that block is dropped.
+    pub fn block_changed(&mut self, pos: Node) {
+        self.region_changed(pos, pos);
+    }
+
+    // Same for every block in a box, from its lowest to its highest corner
+    pub fn region_changed(&mut self, min: Node, max: Node) {
+        let above = self.rules.height + self.rules.max_jump.max(self.rules.max_fall);
+        let affected = self.path.iter().any(|&(px, py, pz)| {
+            (min.0..=max.0).contains(&px)
+                && (min.2..=max.2).contains(&pz)
+                && min.1 < py + above
+                && max.1 >= py - 1
+        });
+        if affected {
+            self.path.clear();
+            self.goal = None;
+        }
+    }
+
+    // Picks a goal for this tick and returns the velocity that walks the
+    // mob towards it. A positive y asks for a jump or a swim upwards.
+    pub fn think(
+        &mut self,
+        world: &World,
+        id: u64,
+        position: Vec3,
+        on_ground: bool,
+        player: Option<Vec3>,
+        dt: f32,
+    ) -> Vec3 {
+        self.repath -= dt;
+        let feet = node_at(position);
+        let in_water = world
+            .get_block(feet.0, feet.1, feet.2)
+            .is_some_and(|block| block.block_type == BlockType::Water);
+        // Paths are only searched from solid footing, not mid-jump
+        let can_plan = on_ground || in_water;
+
+        match self.behaviour {
+            Behaviour::Wander => {
+                if self.path.is_empty() && can_plan {
+                    if self.idle > 0.0 {
+                        self.idle -= dt;
+                        return Vec3::ZERO;
+                    }
+                    self.wanders += 1;
+                    let mut rng = ChunkRng::new(id as u32, self.wanders, 0, SALT_WANDER);
+                    self.idle = rng.range_f32(IDLE_MIN, IDLE_MAX);
+                    let x = feet.0 + rng.range_i32(-WANDER_RADIUS, WANDER_RADIUS);
+                    let z = feet.2 + rng.range_i32(-WANDER_RADIUS, WANDER_RADIUS);
+                    if let Some(goal) = self.standable_near(world, x, feet.1, z) {
+                        self.plan(world, feet, goal);
+                    }
+                }
+            }
+            Behaviour::Follow => {
+                let Some(player) = player else {
+                    self.stop();
+                    return Vec3::ZERO;
+                };
+                let distance = position.distance(player);
+                if distance <= FOLLOW_DISTANCE || distance > SIGHT_DISTANCE {
+                    self.stop();
+                    return Vec3::ZERO;
+                }
+                let target = node_at(player);
+                let stale = self.path.is_empty() || self.goal != Some(target);
+                if stale && self.repath <= 0.0 && can_plan {
+                    self.plan(world, feet, target);
+                }
+            }
+            Behaviour::Flee => {
+                let Some(player) = player else {
+                    return self.steer(position, on_ground, in_water);
+                };
+                let away = (position - player).with_y(0.0);
+                let threatened = away.length() < FLEE_DISTANCE;
+                if threatened && self.repath <= 0.0 && can_plan {
+                    let direction = if away.length() > 1e-3 {
+                        away.normalize()
+                    } else {
+                        Vec3::X
+                    };
+                    let spot = position + direction * FLEE_STEP;
+                    let (x, z) = (spot.x.floor() as i32, spot.z.floor() as i32);
+                    if let Some(goal) = self.standable_near(world, x, feet.1, z) {
+                        self.plan(world, feet, goal);
+                    }
+                }
+            }
+        }
+        self.steer(position, on_ground, in_water)
+    }
+
+    fn stop(&mut self) {
+        self.path.clear();
+        self.goal = None;
+    }
+
+    fn plan(&mut self, world: &World, from: Node, goal: Node) {
+        self.path = find_path(world, from, goal, &self.rules).unwrap_or_default();
+        self.path.reverse();
+        self.goal = Some(goal);
+        self.repath = REPATH_INTERVAL;
+    }
+
+    // The closest node a mob could stand at in the column, a few blocks
+    // above or below the given height
+    fn standable_near(&self, world: &World, x: i32, y: i32, z: i32) -> Option<Node> {
+        (0..=self.rules.max_fall + 1)
+            .flat_map(|offset| [y + offset, y - offset])
+            .map(|y| (x, y, z))
+            .find(|&node| is_standable(world, node, &self.rules))
+    }
+
+    // Velocity towards the next waypoint, dropping the ones already reached
+    fn steer(&mut self, position: Vec3, on_ground: bool, in_water: bool) -> Vec3 {
+        while let Some(&next) = self.path.last() {
+            let offset = (centre_of(next) - position).with_y(0.0);
+            let level = (position.y - next.1 as f32).abs() < 0.6;
+            if offset.length() < WAYPOINT_REACH && level {
+                self.path.pop();
+                continue;
+            }
+
+            let speed = if in_water { SWIM_SPEED } else { WALK_SPEED };
+            let mut velocity = offset.normalize_or_zero() * speed;
+            if next.1 as f32 > position.y + 0.5 {
+                if in_water {
+                    velocity.y = SWIM_SPEED;
+                } else if on_ground {
+                    velocity.y = JUMP_VELOCITY;
+                }
+            } else if in_water && next.1 as f32 >= position.y - 0.5 {
+                // Keep afloat rather than sinking to the bottom
+                velocity.y = SWIM_SPEED * 0.5;
+            }
+            return velocity;
+        }
+        Vec3::ZERO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::entity::{Entity, EntityKind};
+    use crate::test_world::{flat_world, run_with_player};
+
+    fn spawn(world: &mut World, behaviour: Behaviour, position: Vec3) -> u64 {
+        let mob = Entity::new(EntityKind::Mob, position).with_brain(MobBrain::new(behaviour));
+        world.spawn_entity(mob).unwrap()
+    }
+
+    #[test]
+    fn followers_walk_around_walls_to_the_player() {
+        let mut world = flat_world();
+        for z in -4..=4 {
+            for y in 5..8 {
+                world.set_block(0, y, z, Block::new(BlockType::Stone));
+            }
+        }
+        let id = spawn(&mut world, Behaviour::Follow, Vec3::new(-5.5, 5.0, 0.5));
+        let player = Vec3::new(6.5, 5.0, 0.5);
+        run_with_player(&mut world, player, 600);
+
+        let mob = world.entity(id).unwrap();
+        assert!(mob.position.distance(player) <= FOLLOW_DISTANCE + 0.5);
+    }
+
+    #[test]
+    fn followers_climb_steps() {
+        let mut world = flat_world();
+        for x in 2..16 {
+            for z in -16..16 {
+                world.set_block(x, 5, z, Block::new(BlockType::Stone));
+            }
+        }
+        let id = spawn(&mut world, Behaviour::Follow, Vec3::new(-3.5, 5.0, 0.5));
+        let player = Vec3::new(8.5, 6.0, 0.5);
+        run_with_player(&mut world, player, 600);
+
+        let mob = world.entity(id).unwrap();
+        assert!((mob.position.y - 6.0).abs() < 1e-3);
+        assert!(mob.position.x > 4.0);
+    }
+
+    #[test]
+    fn fleeing_mobs_keep_their_distance() {
+        let mut world = flat_world();
+        let id = spawn(&mut world, Behaviour::Flee, Vec3::new(-2.5, 5.0, -2.5));
+        let player = Vec3::new(0.5, 5.0, 0.5);
+        let before = world.entity(id).unwrap().position.distance(player);
+        run_with_player(&mut world, player, 300);
+
+        let after = world.entity(id).unwrap().position.distance(player);
+        assert!(after > before + 3.0);
+    }
+
+    #[test]
+    fn wanderers_walk_then_rest() {
+        let mut world = flat_world();
+        let start = Vec3::new(0.5, 5.0, 0.5);
+        let id = spawn(&mut world, Behaviour::Wander, start);
+        run_with_player(&mut world, Vec3::splat(100.0), 240);
+        let moved = world.entity(id).unwrap().position;
+        assert!(moved.distance(start) > 1.0);
+        assert!(moved.distance(start) <= WANDER_RADIUS as f32 * 2.0);
+    }
+
+    #[test]
+    fn paths_are_dropped_when_a_block_on_them_changes() {
+        let world = flat_world();
+        let mut brain = MobBrain::new(Behaviour::Follow);
+        brain.plan(&world, (0, 5, 0), (8, 5, 0));
+        assert!(brain.has_path());
+
+        // A block well off to the side leaves the path alone
+        brain.block_changed((4, 5, 6));
+        assert!(brain.has_path());
+
+        brain.block_changed((4, 5, 0));
+        assert!(!brain.has_path());
+        assert_eq!(brain.goal(), None);
+    }
+
+    #[test]
+    fn world_edits_reach_the_mobs() {
+        let mut world = flat_world();
+        let id = spawn(&mut world, Behaviour::Follow, Vec3::new(-5.5, 5.0, 0.5));
+        run_with_player(&mut world, Vec3::new(8.5, 5.0, 0.5), 5);
+        let brain = world.entity(id).unwrap().brain.clone().unwrap();
+        assert!(brain.has_path());
+
+        world.set_block(0, 5, 0, Block::new(BlockType::Stone));
+        let brain = world.entity(id).unwrap().brain.as_ref().unwrap();
+        assert!(!brain.has_path());
+    }
+}
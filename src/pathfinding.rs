@@ -0,0 +1,379 @@
+use crate::block::{Block, BlockType};
+use crate::world::World;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+// The block a walker's feet are in
+pub type Node = (i32, i32, i32);
+
+// Costs are in tenths of a block walked, so the search can use integers
+const STEP_COST: u32 = 10;
+const CLIMB_COST: u32 = 5;
+const DROP_COST: u32 = 3;
+const WATER_COST: u32 = 20;
+
+// Furthest a path goes from its start along x or z. A block change can only
+// concern walkers this close to it, and those walking back from this far.
+pub const MAX_PATH_REACH: i32 = 32;
+
+// What a walker can manage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathRules {
+    // Blocks of headroom it needs
+    pub height: i32,
+    // Highest ledge it can jump onto and deepest drop it will take
+    pub max_jump: i32,
+    pub max_fall: i32,
+    pub can_swim: bool,
+    // Nodes searched before giving up
+    pub max_nodes: usize,
+}
+
+impl Default for PathRules {
+    fn default() -> Self {
+        Self {
+            height: 2,
+            max_jump: 1,
+            max_fall: 3,
+            can_swim: true,
+            max_nodes: 4000,
+        }
+    }
+}
+
+// Blocks a walker can be inside. Unloaded chunks count as walls, so paths
+// stay where the world is known. Open doors can be walked through and shut
+// ones are walls; toggling one is a block change, so paths get searched again.
+fn passable(block: Option<Block>, rules: &PathRules) -> bool {
+    match block {
+        Some(block) if block.block_type == BlockType::Water => rules.can_swim,
+        Some(block) => !block.is_solid(),
+        None => false,
+    }
+}
+
+fn is_water(world: &World, (x, y, z): Node) -> bool {
+    world
+        .get_block(x, y, z)
+        .is_some_and(|block| block.block_type == BlockType::Water)
+}
+
+fn body_fits(world: &World, (x, y, z): Node, rules: &PathRules) -> bool {
+    (y..y + rules.height).all(|by| passable(world.get_block(x, by, z), rules))
+}
+
+// A walker can stay at this node: room for the body and either ground
+// under the feet or water to swim in
+pub fn is_standable(world: &World, node: Node, rules: &PathRules) -> bool {
+    let (x, y, z) = node;
+    if !body_fits(world, node, rules) {
+        return false;
+    }
+    let ground = world
+        .get_block(x, y - 1, z)
+        .is_some_and(|block| block.is_solid());
+    ground || (rules.can_swim && is_water(world, node))
+}
+
+// Nodes reachable in one move, with what the move costs
+fn neighbours(world: &World, node: Node, rules: &PathRules) -> Vec<(Node, u32)> {
+    let (x, y, z) = node;
+    let swimming = is_water(world, node);
+    let step_cost = if swimming { WATER_COST } else { STEP_COST };
+    let mut found = Vec::new();
+
+    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let side = (x + dx, y, z + dz);
+        if is_standable(world, side, rules) {
+            found.push((side, step_cost));
+        } else if body_fits(world, side, rules) {
+            // Open but with nothing to stand on: drop to the first floor
+            for depth in 1..=rules.max_fall {
+                let below = (side.0, y - depth, side.2);
+                if !passable(world.get_block(below.0, below.1, below.2), rules) {
+                    break;
+                }
+                if is_standable(world, below, rules) {
+                    found.push((below, step_cost + DROP_COST * depth as u32));
+                    break;
+                }
+            }
+        } else {
+            // Blocked: jump up, if there is headroom above the current node
+            for rise in 1..=rules.max_jump {
+                let head = world.get_block(x, y + rules.height + rise - 1, z);
+                if !passable(head, rules) {
+                    break;
+                }
+                let above = (side.0, y + rise, side.2);
+                if is_standable(world, above, rules) {
+                    found.push((above, step_cost + CLIMB_COST * rise as u32));
+                    break;
+                }
+            }
+        }
+    }
+
+    // Swimming straight up or down
+    if swimming {
+        for dy in [1, -1] {
+            let next = (x, y + dy, z);
+            if is_standable(world, next, rules) {
+                found.push((next, WATER_COST));
+            }
+        }
+    }
+    found
+}
+
+// Horizontal blocks to the goal: never more than the real cost, since every
+// move covers at most one of them
+fn estimate(a: Node, b: Node) -> u32 {
+    ((a.0 - b.0).unsigned_abs() + (a.2 - b.2).unsigned_abs()) * STEP_COST
+}
+
+fn within_reach(start: Node, node: Node) -> bool {
+    (node.0 - start.0).abs() <= MAX_PATH_REACH && (node.2 - start.2).abs() <= MAX_PATH_REACH
+}
+
+// A* over the block grid. The path leaves out the start and ends at the
+// goal; None if the goal can't be reached within the rules' node budget
+// and MAX_PATH_REACH.
+pub fn find_path(world: &World, start: Node, goal: Node, rules: &PathRules) -> Option<Vec<Node>> {
+    if !within_reach(start, goal) || !is_standable(world, goal, rules) {
+        return None;
+    }
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Node, Node> = HashMap::new();
+    let mut cost: HashMap<Node, u32> = HashMap::new();
+    cost.insert(start, 0);
+    // Ties go to the node found last, which keeps the search heading on
+    let mut order = 0u32;
+    open.push(Reverse((estimate(start, goal), Reverse(order), start)));
+
+    let mut searched = 0;
+    while let Some(Reverse((_, _, node))) = open.pop() {
+        if node == goal {
+            let mut path = vec![node];
+            let mut current = node;
+            while let Some(&previous) = came_from.get(&current) {
+                if previous == start {
+                    break;
+                }
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        searched += 1;
+        if searched > rules.max_nodes {
+            return None;
+        }
+
+        let here = cost[&node];
+        for (next, step) in neighbours(world, node, rules) {
+            if !within_reach(start, next) {
+                continue;
+            }
+            let total = here + step;
+            if cost.get(&next).is_some_and(|&known| known <= total) {
+                continue;
+            }
+            cost.insert(next, total);
+            came_from.insert(next, node);
+            order += 1;
+            open.push(Reverse((
+                total + estimate(next, goal),
+                Reverse(order),
+                next,
+            )));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_world::{empty_world, flat_world, lay_floor};
+
+    fn set(world: &mut World, (x, y, z): Node, block_type: BlockType) {
+        world.set_block(x, y, z, Block::new(block_type));
+    }
+
+    // Every move in the path is one the walker could make
+    fn assert_walkable(world: &World, start: Node, path: &[Node], rules: &PathRules) {
+        let mut previous = start;
+        for &node in path {
+            assert!(
+                is_standable(world, node, rules),
+                "can't stand at {:?}",
+                node
+            );
+            let moves = neighbours(world, previous, rules);
+            assert!(
+                moves.iter().any(|&(next, _)| next == node),
+                "no move from {:?} to {:?}",
+                previous,
+                node
+            );
+            previous = node;
+        }
+    }
+
+    #[test]
+    fn straight_line_on_flat_ground() {
+        let world = flat_world();
+        let rules = PathRules::default();
+        let path = find_path(&world, (0, 5, 0), (6, 5, 0), &rules).unwrap();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path.last(), Some(&(6, 5, 0)));
+    }
+
+    #[test]
+    fn walks_through_open_doors_only() {
+        let mut world = flat_world();
+        // A wall across the way with a door in it
+        for z in -6..=6 {
+            for y in 5..7 {
+                set(&mut world, (3, y, z), BlockType::Stone);
+            }
+        }
+        set(&mut world, (3, 5, 0), BlockType::Door);
+        set(&mut world, (3, 6, 0), BlockType::Air);
+        let rules = PathRules::default();
+        let shut = find_path(&world, (0, 5, 0), (6, 5, 0), &rules).unwrap();
+        assert!(!shut.contains(&(3, 5, 0)));
+
+        let door = world.get_block(3, 5, 0).unwrap().toggle_door();
+        world.set_block(3, 5, 0, door);
+        let open = find_path(&world, (0, 5, 0), (6, 5, 0), &rules).unwrap();
+        assert_eq!(open.len(), 6);
+        assert!(open.contains(&(3, 5, 0)));
+        assert_walkable(&world, (0, 5, 0), &open, &rules);
+    }
+
+    #[test]
+    fn goes_around_walls() {
+        let mut world = flat_world();
+        for z in -3..=3 {
+            for y in 5..8 {
+                set(&mut world, (3, y, z), BlockType::Stone);
+            }
+        }
+        let rules = PathRules::default();
+        let path = find_path(&world, (0, 5, 0), (6, 5, 0), &rules).unwrap();
+        assert_walkable(&world, (0, 5, 0), &path, &rules);
+        assert!(path.iter().any(|&(_, _, z)| z.abs() >= 4));
+    }
+
+    #[test]
+    fn jumps_one_block_but_not_two() {
+        let mut world = flat_world();
+        // A step of one block, then a wall of two, across the whole floor
+        for z in -16..16 {
+            set(&mut world, (2, 5, z), BlockType::Stone);
+            for x in 3..16 {
+                set(&mut world, (x, 5, z), BlockType::Stone);
+            }
+            set(&mut world, (8, 6, z), BlockType::Stone);
+            set(&mut world, (8, 7, z), BlockType::Stone);
+        }
+        let rules = PathRules::default();
+        let path = find_path(&world, (0, 5, 0), (5, 6, 0), &rules).unwrap();
+        assert_walkable(&world, (0, 5, 0), &path, &rules);
+        assert_eq!(find_path(&world, (0, 5, 0), (10, 6, 0), &rules), None);
+
+        let climber = PathRules {
+            max_jump: 2,
+            ..rules
+        };
+        assert!(find_path(&world, (0, 5, 0), (10, 6, 0), &climber).is_some());
+    }
+
+    #[test]
+    fn limits_drops() {
+        let mut world = flat_world();
+        // A platform four blocks up
+        for x in -2..=2 {
+            for z in -2..=2 {
+                set(&mut world, (x, 8, z), BlockType::Stone);
+            }
+        }
+        let rules = PathRules::default();
+        assert_eq!(find_path(&world, (0, 9, 0), (6, 5, 0), &rules), None);
+        let brave = PathRules {
+            max_fall: 4,
+            ..rules
+        };
+        let path = find_path(&world, (0, 9, 0), (6, 5, 0), &brave).unwrap();
+        assert_walkable(&world, (0, 9, 0), &path, &brave);
+    }
+
+    #[test]
+    fn swims_only_when_allowed() {
+        let mut world = flat_world();
+        // A moat of water across the floor, two deep
+        for z in -16..16 {
+            for x in 3..6 {
+                set(&mut world, (x, 4, z), BlockType::Water);
+                set(&mut world, (x, 3, z), BlockType::Water);
+                set(&mut world, (x, 5, z), BlockType::Water);
+            }
+        }
+        let rules = PathRules::default();
+        let path = find_path(&world, (0, 5, 0), (8, 5, 0), &rules).unwrap();
+        assert_walkable(&world, (0, 5, 0), &path, &rules);
+        assert!(path.iter().any(|&node| is_water(&world, node)));
+
+        let dry = PathRules {
+            can_swim: false,
+            ..rules
+        };
+        assert_eq!(find_path(&world, (0, 5, 0), (8, 5, 0), &dry), None);
+    }
+
+    #[test]
+    fn gives_up_when_enclosed() {
+        let mut world = flat_world();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                if (x, z) != (0, 0) {
+                    for y in 5..8 {
+                        set(&mut world, (x, y, z), BlockType::Stone);
+                    }
+                }
+            }
+        }
+        let rules = PathRules::default();
+        assert_eq!(find_path(&world, (0, 5, 0), (6, 5, 0), &rules), None);
+        // Unloaded chunks are never entered either
+        assert_eq!(find_path(&world, (6, 5, 0), (20, 5, 0), &rules), None);
+    }
+
+    #[test]
+    fn paths_stay_within_reach_of_their_start() {
+        let mut world = empty_world(-3..=2, -2..=0);
+        lay_floor(&mut world, BlockType::Stone, -48..48, -32..16);
+        let rules = PathRules::default();
+        let start = (-20, 5, 0);
+        let far = (-20 + MAX_PATH_REACH, 5, 0);
+        let path = find_path(&world, start, far, &rules).unwrap();
+        assert_walkable(&world, start, &path, &rules);
+        assert_eq!(find_path(&world, start, (far.0 + 1, 5, 0), &rules), None);
+
+        // Nor does the way around a wall, through a gap at z = 15
+        for z in -32..16 {
+            for y in 5..8 {
+                if z != 15 {
+                    set(&mut world, (0, y, z), BlockType::Stone);
+                }
+            }
+        }
+        let start = (-10, 5, -15);
+        assert!(find_path(&world, start, (10, 5, -15), &rules).is_some());
+        let start = (-10, 5, -20);
+        assert_eq!(find_path(&world, start, (10, 5, -20), &rules), None);
+    }
+}
@@ -1,10 +1,23 @@
 use crate::world::World;
 use glam::Vec3;
 
-// Player bounding box
-const PLAYER_WIDTH: f32 = 0.6;
-const PLAYER_HEIGHT: f32 = 1.8;
+// Player bounding box, standing up and crouched
+pub const PLAYER_HITBOX: Hitbox = Hitbox {
+    width: 0.6,
+    height: 1.8,
+    eye_height: 1.62,
+};
+pub const SNEAKING_HITBOX: Hitbox = Hitbox {
+    width: 0.6,
+    height: 1.5,
+    eye_height: 1.27,
+};
+// Ledges up to this high are climbed without jumping
+pub const STEP_HEIGHT: f32 = 0.6;
+// Gap kept between boxes that touch, so rounding never pushes one into the other
+const CONTACT_EPSILON: f32 = 1e-4;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AABB {
     pub min: Vec3,
     pub max: Vec3,
@@ -23,110 +36,489 @@ impl AABB {
             && self.min.z <= other.max.z
             && self.max.z >= other.min.z
     }
+
+    pub fn translated(&self, offset: Vec3) -> AABB {
+        AABB::new(self.min + offset, self.max + offset)
+    }
+
+    // Overlap with some thickness on one axis; touching faces don't count
+    fn overlaps_on(&self, other: &AABB, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis]
+    }
+}
+
+// Size of an upright collision box, and where the eye sits in it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hitbox {
+    pub width: f32,
+    pub height: f32,
+    pub eye_height: f32,
 }
 
-// PROPERLY WORKING collision detection
-pub fn check_collision(pos: Vec3, world: &World) -> bool {
-    // Camera position is at EYE HEIGHT (1.6 blocks above feet)
-    const EYE_HEIGHT: f32 = 1.62;
-
-    let player_box = AABB::new(
-        Vec3::new(
-            pos.x - PLAYER_WIDTH / 2.0,
-            pos.y - EYE_HEIGHT, // Feet = eye - eye_height
-            pos.z - PLAYER_WIDTH / 2.0,
-        ),
-        Vec3::new(
-            pos.x + PLAYER_WIDTH / 2.0,
-            pos.y + (PLAYER_HEIGHT - EYE_HEIGHT), // Head = eye + remaining height
-            pos.z + PLAYER_WIDTH / 2.0,
-        ),
-    );
-
-    // Check all blocks in player's bounding box
-    let min_x = player_box.min.x.floor() as i32;
-    let max_x = player_box.max.x.ceil() as i32;
-    let min_y = player_box.min.y.floor() as i32;
-    let max_y = player_box.max.y.ceil() as i32;
-    let min_z = player_box.min.z.floor() as i32;
-    let max_z = player_box.max.z.ceil() as i32;
-
-    let mut checked = 0;
-    let mut found_blocks = 0;
-    for x in min_x..=max_x {
-        for y in min_y..=max_y {
-            for z in min_z..=max_z {
-                checked += 1;
-                if let Some(block) = world.get_block(x, y, z) {
-                    found_blocks += 1;
-                    if block.is_solid() {
-                        let block_min = Vec3::new(x as f32, y as f32, z as f32);
-                        let block_max = block_min + Vec3::ONE;
-                        let block_box = AABB::new(block_min, block_max);
-
-                        if player_box.intersects(&block_box) {
-                            return true;
-                        }
-                    }
+impl Hitbox {
+    // The box with its bottom centred on the feet
+    pub fn at(&self, feet: Vec3) -> AABB {
+        let half = self.width / 2.0;
+        AABB::new(
+            Vec3::new(feet.x - half, feet.y, feet.z - half),
+            Vec3::new(feet.x + half, feet.y + self.height, feet.z + half),
+        )
+    }
+}
+
+// Whether any solid block overlaps the box; touching doesn't count
+pub fn collides(world: &World, aabb: &AABB) -> bool {
+    let min = aabb.min.floor().as_ivec3();
+    let max = aabb.max.ceil().as_ivec3();
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                if !world
+                    .get_block(x, y, z)
+                    .is_some_and(|block| block.is_solid())
+                {
+                    continue;
+                }
+                let block_min = Vec3::new(x as f32, y as f32, z as f32);
+                let block = AABB::new(block_min, block_min + Vec3::ONE);
+                if (0..3).all(|axis| aabb.overlaps_on(&block, axis)) {
+                    return true;
                 }
             }
         }
     }
+    false
+}
+
+// Which sides of a moving box were stopped by blocks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Contacts {
+    pub grounded: bool,
+    pub ceiling: bool,
+    pub wall_x: bool,
+    pub wall_z: bool,
+}
 
-    if checked > 0 && found_blocks == 0 {
-        println!(
-            "WARNING: Checked {} positions but found 0 blocks! Pos: ({:.1}, {:.1}, {:.1})",
-            checked, pos.x, pos.y, pos.z
-        );
+impl Contacts {
+    pub fn wall(&self) -> bool {
+        self.wall_x || self.wall_z
     }
+}
 
-    false
+pub struct Sweep {
+    // How far the box actually moved
+    pub movement: Vec3,
+    pub contacts: Contacts,
+}
+
+// Moves a box through the world one axis at a time, y first, stopping it
+// flush against the first solid block in the way. The whole path is checked,
+// so a fast box can't pass through thin walls. A box that hits a wall while
+// on the ground climbs ledges up to step_height.
+pub fn sweep_aabb(world: &World, aabb: &AABB, motion: Vec3, step_height: f32) -> Sweep {
+    let direct = sweep_axes(world, aabb, motion);
+    let blocked =
+        (direct.contacts.wall_x && motion.x != 0.0) || (direct.contacts.wall_z && motion.z != 0.0);
+    if step_height <= 0.0 || !direct.contacts.grounded || !blocked {
+        return direct;
+    }
+
+    // Try again from step_height higher, then put the box back down
+    let up = sweep_axes(world, aabb, Vec3::new(0.0, step_height, 0.0));
+    let raised = aabb.translated(up.movement);
+    let across = sweep_axes(world, &raised, Vec3::new(motion.x, 0.0, motion.z));
+    let moved = raised.translated(across.movement);
+    let down = sweep_axes(world, &moved, Vec3::new(0.0, motion.y - up.movement.y, 0.0));
+
+    let stepped = up.movement + across.movement + down.movement;
+    let horizontal = |v: Vec3| v.x * v.x + v.z * v.z;
+    if !down.contacts.grounded || horizontal(stepped) <= horizontal(direct.movement) {
+        return direct;
+    }
+    Sweep {
+        movement: stepped,
+        contacts: Contacts {
+            grounded: true,
+            ceiling: direct.contacts.ceiling,
+            wall_x: across.contacts.wall_x,
+            wall_z: across.contacts.wall_z,
+        },
+    }
+}
+
+fn sweep_axes(world: &World, aabb: &AABB, motion: Vec3) -> Sweep {
+    let mut current = *aabb;
+    let mut movement = Vec3::ZERO;
+    let mut contacts = Contacts::default();
+
+    for axis in [1, 0, 2] {
+        let wanted = motion[axis];
+        if wanted == 0.0 {
+            continue;
+        }
+        let allowed = clip_axis(world, &current, axis, wanted);
+        if allowed != wanted {
+            match axis {
+                0 => contacts.wall_x = true,
+                1 if wanted < 0.0 => contacts.grounded = true,
+                1 => contacts.ceiling = true,
+                _ => contacts.wall_z = true,
+            }
+        }
+        movement[axis] = allowed;
+        let mut offset = Vec3::ZERO;
+        offset[axis] = allowed;
+        current = current.translated(offset);
+    }
+    Sweep { movement, contacts }
+}
+
+// How far the box can go along one axis before it touches a solid block.
+// Blocks it already overlaps on that axis are ignored, so a box stuck in a
+// block can still get out.
+fn clip_axis(world: &World, aabb: &AABB, axis: usize, distance: f32) -> f32 {
+    let mut swept = *aabb;
+    if distance > 0.0 {
+        swept.max[axis] += distance;
+    } else {
+        swept.min[axis] += distance;
+    }
+
+    let mut allowed = distance;
+    let min = swept.min.floor().as_ivec3();
+    let max = swept.max.ceil().as_ivec3();
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                if !world
+                    .get_block(x, y, z)
+                    .is_some_and(|block| block.is_solid())
+                {
+                    continue;
+                }
+                let block_min = Vec3::new(x as f32, y as f32, z as f32);
+                let block = AABB::new(block_min, block_min + Vec3::ONE);
+                let across = (0..3)
+                    .filter(|&other| other != axis)
+                    .all(|other| aabb.overlaps_on(&block, other));
+                if !across {
+                    continue;
+                }
+                if distance > 0.0 && block.min[axis] >= aabb.max[axis] - CONTACT_EPSILON {
+                    let gap = block.min[axis] - aabb.max[axis] - CONTACT_EPSILON;
+                    allowed = allowed.min(gap.max(0.0));
+                } else if distance < 0.0 && block.max[axis] <= aabb.min[axis] + CONTACT_EPSILON {
+                    let gap = block.max[axis] - aabb.min[axis] + CONTACT_EPSILON;
+                    allowed = allowed.max(gap.min(0.0));
+                }
+            }
+        }
+    }
+    allowed
 }
 
 // Raycasting for block selection
 pub struct RaycastHit {
     pub block_pos: (i32, i32, i32),
+    // Points out of the face the ray entered through. Zero if the ray
+    // started inside the block.
     pub face_normal: Vec3,
+    // Where the ray enters the block, and how far that is from the origin
+    pub point: Vec3,
+    pub distance: f32,
+    // The empty cell the ray passed through just before the block, where a
+    // placed block goes
+    pub previous: (i32, i32, i32),
 }
 
+// Walks the grid cell by cell along the ray (Amanatides & Woo), so every
+// block the ray touches is visited exactly once and none can be skipped
 pub fn raycast(
     origin: Vec3,
     direction: Vec3,
     max_distance: f32,
     world: &World,
 ) -> Option<RaycastHit> {
-    let step = 0.1;
-    let mut current = origin;
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    let mut cell = origin.floor().to_array().map(|v| v as i32);
+    let dir = direction.to_array();
+    let start = origin.to_array();
+    let mut step = [0; 3];
+    // Ray distance to the next cell boundary on each axis, and between two
+    // boundaries of the same axis
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        if dir[axis] > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (cell[axis] as f32 + 1.0 - start[axis]) / dir[axis];
+            t_delta[axis] = 1.0 / dir[axis];
+        } else if dir[axis] < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (start[axis] - cell[axis] as f32) / -dir[axis];
+            t_delta[axis] = -1.0 / dir[axis];
+        }
+    }
 
-    for _ in 0..(max_distance / step) as i32 {
-        current += direction * step;
+    let mut previous = cell;
+    let mut face_normal = Vec3::ZERO;
+    let mut distance = 0.0;
+    loop {
+        if let Some(block) = world.get_block(cell[0], cell[1], cell[2])
+            // Plants and open doors can be targeted even though they don't
+            // collide
+            && (block.is_solid() || block.is_cross() || block.is_open_door())
+        {
+            return Some(RaycastHit {
+                block_pos: (cell[0], cell[1], cell[2]),
+                face_normal,
+                point: origin + direction * distance,
+                distance,
+                previous: (previous[0], previous[1], previous[2]),
+            });
+        }
 
-        let x = current.x.floor() as i32;
-        let y = current.y.floor() as i32;
-        let z = current.z.floor() as i32;
+        // Cross whichever boundary is nearest; ties go to x, then y
+        let axis = if t_max[0] <= t_max[1] && t_max[0] <= t_max[2] {
+            0
+        } else if t_max[1] <= t_max[2] {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_distance {
+            return None;
+        }
+        previous = cell;
+        cell[axis] += step[axis];
+        distance = t_max[axis];
+        t_max[axis] += t_delta[axis];
+        face_normal = Vec3::ZERO;
+        face_normal[axis] = -step[axis] as f32;
+    }
+}
 
-        if let Some(block) = world.get_block(x, y, z) {
-            if block.is_solid() {
-                // Determine which face was hit
-                let block_center = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
-                let hit_offset = current - block_center;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockType};
+    use crate::test_world::empty_world;
 
-                let face_normal = if hit_offset.x.abs() > hit_offset.y.abs()
-                    && hit_offset.x.abs() > hit_offset.z.abs()
-                {
-                    Vec3::new(hit_offset.x.signum(), 0.0, 0.0)
-                } else if hit_offset.y.abs() > hit_offset.z.abs() {
-                    Vec3::new(0.0, hit_offset.y.signum(), 0.0)
-                } else {
-                    Vec3::new(0.0, 0.0, hit_offset.z.signum())
-                };
-
-                return Some(RaycastHit {
-                    block_pos: (x, y, z),
-                    face_normal,
-                });
+    // Empty chunks around the origin with stone at the given blocks
+    fn world_with(blocks: &[(i32, i32, i32)]) -> World {
+        let mut world = empty_world(-2..=2, -2..=2);
+        for &(x, y, z) in blocks {
+            world.set_block(x, y, z, Block::new(BlockType::Stone));
+        }
+        world
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    // A player-sized box with its feet at the given point
+    fn feet_box(x: f32, y: f32, z: f32) -> AABB {
+        PLAYER_HITBOX.at(Vec3::new(x, y, z))
+    }
+
+    // Stone floor with its top at y = 5 around the origin
+    fn floor_world() -> World {
+        let mut blocks = Vec::new();
+        for x in -8..8 {
+            for z in -8..8 {
+                blocks.push((x, 4, z));
             }
         }
+        world_with(&blocks)
+    }
+
+    #[test]
+    fn fast_fall_lands_on_the_floor() {
+        let world = floor_world();
+        let start = feet_box(0.5, 60.0, 0.5);
+        let sweep = sweep_aabb(&world, &start, Vec3::new(0.0, -200.0, 0.0), STEP_HEIGHT);
+        assert!(sweep.contacts.grounded);
+        let feet = start.min.y + sweep.movement.y;
+        assert!((feet - 5.0).abs() < 1e-3, "feet at {}", feet);
+    }
+
+    #[test]
+    fn walls_stop_one_axis_only() {
+        let mut world = floor_world();
+        world.set_block(2, 5, 0, Block::new(BlockType::Stone));
+        world.set_block(2, 6, 0, Block::new(BlockType::Stone));
+        let start = feet_box(0.5, 5.0, 0.5);
+        let sweep = sweep_aabb(&world, &start, Vec3::new(3.0, 0.0, 0.5), STEP_HEIGHT);
+        assert!(sweep.contacts.wall_x);
+        assert!(!sweep.contacts.wall_z);
+        assert!((start.max.x + sweep.movement.x - 2.0).abs() < 1e-3);
+        assert_eq!(sweep.movement.z, 0.5);
+    }
+
+    #[test]
+    fn ceiling_contact() {
+        let world = world_with(&[(0, 10, 0)]);
+        let start = feet_box(0.5, 7.0, 0.5);
+        let sweep = sweep_aabb(&world, &start, Vec3::new(0.0, 2.0, 0.0), STEP_HEIGHT);
+        assert!(sweep.contacts.ceiling);
+        assert!(!sweep.contacts.grounded);
+        assert!((start.max.y + sweep.movement.y - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn resting_box_slides_along_the_floor() {
+        let world = floor_world();
+        let start = feet_box(0.5, 5.0, 0.5);
+        let sweep = sweep_aabb(&world, &start, Vec3::new(1.0, -0.1, 0.0), STEP_HEIGHT);
+        assert!(sweep.contacts.grounded);
+        assert!(!sweep.contacts.wall());
+        assert_eq!(sweep.movement.x, 1.0);
+    }
+
+    #[test]
+    fn steps_up_low_ledges_only() {
+        let mut world = floor_world();
+        world.set_block(2, 5, 0, Block::new(BlockType::Stone));
+        let start = feet_box(0.5, 5.0, 0.5);
+        let motion = Vec3::new(2.0, -0.1, 0.0);
+
+        // A full block is too high for the default step
+        let blocked = sweep_aabb(&world, &start, motion, STEP_HEIGHT);
+        assert!(blocked.contacts.wall_x);
+        assert_eq!(blocked.movement.y, 0.0);
+
+        let climbed = sweep_aabb(&world, &start, motion, 1.0);
+        assert!(climbed.contacts.grounded);
+        assert!(!climbed.contacts.wall_x);
+        assert_eq!(climbed.movement.x, 2.0);
+        assert!((start.min.y + climbed.movement.y - 6.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn no_step_up_in_the_air() {
+        let world = world_with(&[(2, 5, 0)]);
+        let start = feet_box(0.5, 5.0, 0.5);
+        let sweep = sweep_aabb(&world, &start, Vec3::new(2.0, -0.1, 0.0), 1.0);
+        assert!(sweep.contacts.wall_x);
+        assert!(!sweep.contacts.grounded);
+    }
+
+    #[test]
+    fn hitboxes_of_different_sizes() {
+        // An overhang one block above the floor
+        let mut world = floor_world();
+        world.set_block(0, 6, 0, Block::new(BlockType::Stone));
+        let small = Hitbox {
+            width: 0.6,
+            height: 0.9,
+            eye_height: 0.5,
+        };
+        let tall = Hitbox {
+            width: 1.4,
+            height: 3.0,
+            eye_height: 2.6,
+        };
+        let feet = Vec3::new(0.5, 5.0, 0.5);
+        assert!(collides(&world, &PLAYER_HITBOX.at(feet)));
+        assert!(!collides(&world, &small.at(feet)));
+
+        // The small box passes under the overhang, the tall one stops with
+        // its side flush against it
+        let motion = Vec3::NEG_X * 4.0;
+        let start = Vec3::new(3.5, 5.0, 0.5);
+        let under = sweep_aabb(&world, &small.at(start), motion, 0.0);
+        assert!(!under.contacts.wall_x);
+        let blocked = sweep_aabb(&world, &tall.at(start), motion, 0.0);
+        assert!(blocked.contacts.wall_x);
+        assert!((start.x + blocked.movement.x - 0.7 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn hits_the_ground_from_above() {
+        let world = world_with(&[(2, 10, 3)]);
+        let hit = raycast(Vec3::new(2.5, 14.5, 3.5), Vec3::NEG_Y, 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (2, 10, 3));
+        assert_eq!(hit.previous, (2, 11, 3));
+        assert_eq!(hit.face_normal, Vec3::Y);
+        assert!((hit.distance - 3.5).abs() < 1e-5);
+        assert!(close(hit.point, Vec3::new(2.5, 11.0, 3.5)));
+    }
+
+    #[test]
+    fn diagonal_ray_catches_a_corner() {
+        // The ray only clips the corner of (1, 0, 0) before moving on to
+        // (1, 0, 1); a fixed step would jump straight over it
+        let world = world_with(&[(1, 0, 0)]);
+        let direction = Vec3::new(1.0, 0.0, 0.98);
+        let hit = raycast(Vec3::new(0.5, 0.5, 0.5), direction, 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (1, 0, 0));
+        assert_eq!(hit.previous, (0, 0, 0));
+        assert_eq!(hit.face_normal, Vec3::NEG_X);
+        assert!((hit.point.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn diagonal_ray_reports_the_face_it_entered() {
+        // Coming in steeply from above, the top face is hit even though the
+        // ray also moves sideways
+        let world = world_with(&[(3, 5, 3)]);
+        let origin = Vec3::new(2.9, 8.0, 3.5);
+        let hit = raycast(origin, Vec3::new(0.3, -1.0, 0.0), 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (3, 5, 3));
+        assert_eq!(hit.face_normal, Vec3::Y);
+        assert_eq!(hit.previous, (3, 6, 3));
+        assert!((hit.point.y - 6.0).abs() < 1e-5);
+        assert!((3.0..4.0).contains(&hit.point.x));
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        let world = world_with(&[(-3, 5, -1)]);
+        let hit = raycast(Vec3::new(-0.5, 5.5, -0.5), Vec3::NEG_X, 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (-3, 5, -1));
+        assert_eq!(hit.previous, (-2, 5, -1));
+        assert_eq!(hit.face_normal, Vec3::X);
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+        assert!(close(hit.point, Vec3::new(-2.0, 5.5, -0.5)));
+    }
+
+    #[test]
+    fn crosses_chunk_boundaries() {
+        let world = world_with(&[(17, 5, 0), (-1, 5, 20)]);
+        let hit = raycast(Vec3::new(15.5, 5.5, 0.5), Vec3::X, 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (17, 5, 0));
+        assert_eq!(hit.previous, (16, 5, 0));
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+
+        // Into the chunk at negative x, along a diagonal
+        let origin = Vec3::new(0.5, 5.5, 18.5);
+        let hit = raycast(origin, Vec3::new(-1.0, 0.0, 1.5), 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (-1, 5, 20));
+        assert_eq!(hit.face_normal, Vec3::NEG_Z);
+        assert_eq!(hit.previous, (-1, 5, 19));
+    }
+
+    #[test]
+    fn stops_at_max_distance() {
+        let world = world_with(&[(12, 0, 0)]);
+        let origin = Vec3::new(0.5, 0.5, 0.5);
+        assert!(raycast(origin, Vec3::X, 10.0, &world).is_none());
+        assert!(raycast(origin, Vec3::X, 12.0, &world).is_some());
+        assert!(raycast(origin, Vec3::ZERO, 10.0, &world).is_none());
+    }
+
+    #[test]
+    fn starting_inside_a_block() {
+        let world = world_with(&[(0, 0, 0)]);
+        let hit = raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::X, 10.0, &world).unwrap();
+        assert_eq!(hit.block_pos, (0, 0, 0));
+        assert_eq!(hit.previous, (0, 0, 0));
+        assert_eq!(hit.face_normal, Vec3::ZERO);
+        assert_eq!(hit.distance, 0.0);
     }
-    None
 }
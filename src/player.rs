@@ -0,0 +1,655 @@
+use crate::fluid::FluidProperties;
+use crate::physics::{
+    AABB, Hitbox, PLAYER_HITBOX, SNEAKING_HITBOX, STEP_HEIGHT, collides, sweep_aabb,
+};
+use crate::world::World;
+use glam::Vec3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementMode {
+    Walk,
+    Fly,
+    // Entered and left on its own when walking into or out of water
+    Swim,
+    // Flies through blocks
+    Spectator,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerConfig {
+    // Blocks per second
+    pub walk_speed: f32,
+    pub fly_speed: f32,
+    pub spectator_speed: f32,
+    pub sprint_multiplier: f32,
+    pub sneak_multiplier: f32,
+    // Blocks per second squared, negative for downward
+    pub gravity: f32,
+    pub jump_velocity: f32,
+    pub step_height: f32,
+    pub hitbox: Hitbox,
+    pub sneaking_hitbox: Hitbox,
+    // Two jump presses closer than this toggle flying
+    pub double_tap_time: f32,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            walk_speed: 10.0,
+            fly_speed: 10.0,
+            spectator_speed: 15.0,
+            sprint_multiplier: 1.5,
+            sneak_multiplier: 0.3,
+            gravity: -20.0,
+            jump_velocity: 8.0,
+            step_height: STEP_HEIGHT,
+            hitbox: PLAYER_HITBOX,
+            sneaking_hitbox: SNEAKING_HITBOX,
+            double_tap_time: 0.3,
+        }
+    }
+}
+
+// What the player wants to do this tick, whatever the input device
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerInput {
+    // Where the player looks; walking ignores its vertical part
+    pub look: Vec3,
+    // -1 to 1: backwards to forwards, left to right
+    pub forward: f32,
+    pub strafe: f32,
+    // Held: jump, swim up or fly up
+    pub jump: bool,
+    // The jump key went down since the last tick, for double taps
+    pub jump_pressed: bool,
+    pub sprint: bool,
+    // Held: sneak, or dive or fly down
+    pub sneak: bool,
+}
+
+pub struct PlayerController {
+    // The feet: the bottom centre of the hitbox
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mode: MovementMode,
+    pub on_ground: bool,
+    pub sprinting: bool,
+    pub sneaking: bool,
+    // The fluid around the body and how much of the body is in it, 0 to 1
+    pub fluid: Option<FluidProperties>,
+    pub submerged: f32,
+    pub config: PlayerConfig,
+    previous_eye: Vec3,
+    against_wall: bool,
+    // Seconds since the jump key last went down
+    since_jump_press: f32,
+}
+
+impl PlayerController {
+    pub fn new(position: Vec3, config: PlayerConfig) -> Self {
+        Self {
+            position,
+            velocity: Vec3::ZERO,
+            mode: MovementMode::Walk,
+            on_ground: false,
+            sprinting: false,
+            sneaking: false,
+            fluid: None,
+            submerged: 0.0,
+            previous_eye: position + Vec3::Y * config.hitbox.eye_height,
+            config,
+            against_wall: false,
+            since_jump_press: f32::INFINITY,
+        }
+    }
+
+    // Crouching shrinks the box and lowers the eye
+    pub fn hitbox(&self) -> Hitbox {
+        if self.sneaking {
+            self.config.sneaking_hitbox
+        } else {
+            self.config.hitbox
+        }
+    }
+
+    pub fn aabb(&self) -> AABB {
+        self.hitbox().at(self.position)
+    }
+
+    // Where the camera goes
+    pub fn eye(&self) -> Vec3 {
+        self.position + Vec3::Y * self.hitbox().eye_height
+    }
+
+    // Moves without passing through the space between, e.g. when respawning
+    pub fn teleport(&mut self, position: Vec3) {
+        self.position = position;
+        self.previous_eye = self.eye();
+        self.velocity = Vec3::ZERO;
+        self.on_ground = false;
+    }
+
+    pub fn set_mode(&mut self, mode: MovementMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.velocity = Vec3::ZERO;
+        }
+    }
+
+    // Eye between the previous tick and this one, alpha from 0 to 1
+    pub fn interpolated_eye(&self, alpha: f32) -> Vec3 {
+        self.previous_eye.lerp(self.eye(), alpha)
+    }
+
+    // The fluid the eye is in, for tinting the view
+    pub fn head_fluid(&self, world: &World) -> Option<FluidProperties> {
+        let eye = self.eye().floor().as_ivec3();
+        let block = world.get_block(eye.x, eye.y, eye.z)?;
+        FluidProperties::of(block.block_type)
+    }
+
+    // Advances the player by one simulation tick of dt seconds
+    pub fn tick(&mut self, input: &PlayerInput, world: &World, dt: f32) {
+        self.previous_eye = self.eye();
+        self.handle_double_tap(input, dt);
+        self.update_swimming(world);
+        self.update_sneaking(input, world);
+        self.sprinting = input.sprint && input.forward > 0.0 && !self.sneaking;
+
+        match self.mode {
+            MovementMode::Walk => self.walk(input, world, dt),
+            MovementMode::Fly => self.fly(input, world, dt),
+            MovementMode::Swim => match self.fluid {
+                Some(fluid) => self.swim(fluid, input, world, dt),
+                None => self.walk(input, world, dt),
+            },
+            MovementMode::Spectator => {
+                let speed = self.speed(self.config.spectator_speed);
+                self.position += free_direction(input) * speed * dt;
+                self.velocity = Vec3::ZERO;
+                self.on_ground = false;
+            }
+        }
+    }
+
+    fn handle_double_tap(&mut self, input: &PlayerInput, dt: f32) {
+        self.since_jump_press += dt;
+        if !input.jump_pressed {
+            return;
+        }
+        if self.since_jump_press < self.config.double_tap_time {
+            match self.mode {
+                MovementMode::Walk => self.set_mode(MovementMode::Fly),
+                MovementMode::Fly => self.set_mode(MovementMode::Walk),
+                _ => {}
+            }
+            // A third press starts a new double tap
+            self.since_jump_press = f32::INFINITY;
+        } else {
+            self.since_jump_press = 0.0;
+        }
+    }
+
+    fn update_swimming(&mut self, world: &World) {
+        self.measure_fluid(world);
+        let in_fluid = self.fluid.is_some();
+        match self.mode {
+            MovementMode::Walk if in_fluid => self.mode = MovementMode::Swim,
+            MovementMode::Swim if !in_fluid => self.mode = MovementMode::Walk,
+            _ => {}
+        }
+    }
+
+    // How deep the player stands in fluid, measured in the column under the
+    // middle of the box
+    fn measure_fluid(&mut self, world: &World) {
+        let aabb = self.aabb();
+        let (x, z) = (
+            self.position.x.floor() as i32,
+            self.position.z.floor() as i32,
+        );
+        let mut fluid = None;
+        let mut depth = 0.0;
+        for y in aabb.min.y.floor() as i32..aabb.max.y.ceil() as i32 {
+            let Some(properties) = world
+                .get_block(x, y, z)
+                .and_then(|block| FluidProperties::of(block.block_type))
+            else {
+                continue;
+            };
+            fluid.get_or_insert(properties);
+            depth += (aabb.max.y.min(y as f32 + 1.0) - aabb.min.y.max(y as f32)).max(0.0);
+        }
+        self.fluid = fluid;
+        self.submerged = (depth / (aabb.max.y - aabb.min.y)).clamp(0.0, 1.0);
+    }
+
+    // Sneaking only happens on foot, and getting up needs room for the
+    // standing box, so a player crouched under a low ceiling stays down
+    fn update_sneaking(&mut self, input: &PlayerInput, world: &World) {
+        let walking = self.mode == MovementMode::Walk;
+        let wants_up = !input.sneak || !walking;
+        if wants_up && self.sneaking {
+            let standing = self.config.hitbox.at(self.position);
+            self.sneaking = walking && collides(world, &standing);
+        } else if input.sneak && walking {
+            self.sneaking = true;
+        }
+    }
+
+    fn walk(&mut self, input: &PlayerInput, world: &World, dt: f32) {
+        let horizontal = ground_direction(input) * self.speed(self.config.walk_speed);
+        self.velocity.x = horizontal.x;
+        self.velocity.z = horizontal.z;
+        self.velocity.y += self.config.gravity * dt;
+        if input.jump && self.on_ground {
+            self.velocity.y = self.config.jump_velocity;
+        }
+        let mut motion = self.velocity * dt;
+        if self.sneaking && self.on_ground && !input.jump {
+            motion = self.guard_edges(world, motion);
+        }
+        self.apply_motion(world, motion, self.config.step_height);
+    }
+
+    // Cuts horizontal motion short where it would leave the box with nothing
+    // within a step below it, so sneaking never walks off a ledge
+    fn guard_edges(&self, world: &World, motion: Vec3) -> Vec3 {
+        const INCREMENT: f32 = 0.05;
+        let aabb = self.aabb();
+        let drop = -self.config.step_height;
+        let supported =
+            |dx: f32, dz: f32| collides(world, &aabb.translated(Vec3::new(dx, drop, dz)));
+        let shrink = |v: f32| {
+            if v.abs() <= INCREMENT {
+                0.0
+            } else {
+                v - INCREMENT * v.signum()
+            }
+        };
+
+        let (mut dx, mut dz) = (motion.x, motion.z);
+        while dx != 0.0 && !supported(dx, 0.0) {
+            dx = shrink(dx);
+        }
+        while dz != 0.0 && !supported(0.0, dz) {
+            dz = shrink(dz);
+        }
+        while dx != 0.0 && dz != 0.0 && !supported(dx, dz) {
+            dx = shrink(dx);
+            dz = shrink(dz);
+        }
+        Vec3::new(dx, motion.y, dz)
+    }
+
+    fn fly(&mut self, input: &PlayerInput, world: &World, dt: f32) {
+        let speed = self.speed(self.config.fly_speed);
+        self.velocity = Vec3::ZERO;
+        self.apply_motion(world, free_direction(input) * speed * dt, 0.0);
+        self.on_ground = false;
+    }
+
+    fn swim(&mut self, fluid: FluidProperties, input: &PlayerInput, world: &World, dt: f32) {
+        let speed = self.config.walk_speed * fluid.speed_multiplier;
+        let horizontal = ground_direction(input) * self.speed(speed);
+        self.velocity.x = horizontal.x;
+        self.velocity.z = horizontal.z;
+
+        // Buoyancy cancels gravity the deeper the player is, so swimmers sink
+        // slowly and float up to the surface when swimming
+        let buoyancy = fluid.buoyancy * self.submerged;
+        self.velocity.y += self.config.gravity * (1.0 - buoyancy) * dt;
+        let wading = self.submerged < 0.5;
+        if input.jump && wading && (self.on_ground || self.against_wall) {
+            // Jump out onto the shore
+            self.velocity.y = self.config.jump_velocity;
+        } else if input.jump {
+            self.velocity.y += fluid.swim_acceleration * dt;
+        } else if input.sneak {
+            self.velocity.y -= fluid.swim_acceleration * dt;
+        }
+        self.velocity.y /= 1.0 + fluid.drag * dt;
+
+        self.apply_motion(world, self.velocity * dt, self.config.step_height);
+    }
+
+    fn apply_motion(&mut self, world: &World, motion: Vec3, step_height: f32) {
+        let sweep = sweep_aabb(world, &self.aabb(), motion, step_height);
+        self.position += sweep.movement;
+        let contacts = sweep.contacts;
+        if contacts.grounded || contacts.ceiling {
+            self.velocity.y = 0.0;
+        }
+        if contacts.wall_x {
+            self.velocity.x = 0.0;
+        }
+        if contacts.wall_z {
+            self.velocity.z = 0.0;
+        }
+        self.on_ground = contacts.grounded;
+        self.against_wall = contacts.wall();
+    }
+
+    fn speed(&self, base: f32) -> f32 {
+        if self.sprinting {
+            base * self.config.sprint_multiplier
+        } else if self.sneaking {
+            base * self.config.sneak_multiplier
+        } else {
+            base
+        }
+    }
+}
+
+// Horizontal unit direction of the movement input, or zero
+fn ground_direction(input: &PlayerInput) -> Vec3 {
+    let front = Vec3::new(input.look.x, 0.0, input.look.z).normalize_or_zero();
+    let right = front.cross(Vec3::Y);
+    (front * input.forward + right * input.strafe).normalize_or_zero()
+}
+
+// Direction for flying: straight along the look direction, up with jump and
+// down with sneak
+fn free_direction(input: &PlayerInput) -> Vec3 {
+    let front = input.look.normalize_or_zero();
+    let right = front.cross(Vec3::Y).normalize_or_zero();
+    let mut direction = front * input.forward + right * input.strafe;
+    if input.jump {
+        direction += Vec3::Y;
+    }
+    if input.sneak {
+        direction -= Vec3::Y;
+    }
+    direction.normalize_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockType};
+    use crate::fluid::WATER;
+    use crate::test_world::{DT, empty_world, lay_floor};
+
+    // Stone floor with its top at y = 5, and water in a pool at x >= 8
+    fn test_world() -> World {
+        let mut world = empty_world(-1..=1, -1..=1);
+        lay_floor(&mut world, BlockType::Stone, -16..16, -16..16);
+        for x in 8..16 {
+            for z in -16..16 {
+                for y in 5..9 {
+                    world.set_block(x, y, z, Block::new(BlockType::Water));
+                }
+            }
+        }
+        world
+    }
+
+    fn standing_at(x: f32, z: f32) -> PlayerController {
+        PlayerController::new(Vec3::new(x, 5.0, z), PlayerConfig::default())
+    }
+
+    fn hold_input(player: &mut PlayerController, world: &World, input: PlayerInput, ticks: usize) {
+        for _ in 0..ticks {
+            player.tick(&input, world, DT);
+        }
+    }
+
+    fn forward() -> PlayerInput {
+        PlayerInput {
+            look: Vec3::NEG_Z,
+            forward: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn walks_at_walk_speed() {
+        let world = test_world();
+        let mut player = standing_at(0.5, 0.5);
+        hold_input(&mut player, &world, forward(), 60);
+        assert!(player.on_ground);
+        assert!((player.position.z - (0.5 - 10.0)).abs() < 0.05);
+        assert!((player.position.y - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn sprint_and_sneak_change_speed() {
+        let world = test_world();
+        let distance = |input: PlayerInput| {
+            let mut player = standing_at(0.5, 0.5);
+            hold_input(&mut player, &world, input, 30);
+            0.5 - player.position.z
+        };
+        let walk = distance(forward());
+        let sprint = distance(PlayerInput {
+            sprint: true,
+            ..forward()
+        });
+        let sneak = distance(PlayerInput {
+            sneak: true,
+            ..forward()
+        });
+        assert!((sprint / walk - 1.5).abs() < 0.01);
+        assert!((sneak / walk - 0.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn jumps_and_lands() {
+        let world = test_world();
+        let mut player = standing_at(0.5, 0.5);
+        hold_input(&mut player, &world, PlayerInput::default(), 2);
+        let jump = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        player.tick(&jump, &world, DT);
+        assert!(!player.on_ground);
+
+        let mut peak = player.position.y;
+        for _ in 0..120 {
+            player.tick(&PlayerInput::default(), &world, DT);
+            peak = peak.max(player.position.y);
+        }
+        // v^2 / 2g = 64 / 40
+        let height = peak - 5.0;
+        assert!((height - 1.6).abs() < 0.15, "jumped {}", height);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn double_tap_toggles_flying() {
+        let world = test_world();
+        let mut player = standing_at(0.5, 0.5);
+        let tap = PlayerInput {
+            jump_pressed: true,
+            jump: true,
+            ..Default::default()
+        };
+        player.tick(&tap, &world, DT);
+        hold_input(&mut player, &world, PlayerInput::default(), 5);
+        player.tick(&tap, &world, DT);
+        assert_eq!(player.mode, MovementMode::Fly);
+
+        // Flying doesn't fall
+        let height = player.position.y;
+        hold_input(&mut player, &world, PlayerInput::default(), 60);
+        assert_eq!(player.position.y, height);
+
+        // Presses too far apart do nothing
+        player.tick(&tap, &world, DT);
+        hold_input(&mut player, &world, PlayerInput::default(), 30);
+        player.tick(&tap, &world, DT);
+        assert_eq!(player.mode, MovementMode::Fly);
+    }
+
+    #[test]
+    fn spectator_passes_through_walls() {
+        let mut world = test_world();
+        for y in 5..8 {
+            world.set_block(0, y, -2, Block::new(BlockType::Stone));
+        }
+        let mut walker = standing_at(0.5, 0.5);
+        hold_input(&mut walker, &world, forward(), 30);
+        assert!(walker.position.z > -1.0);
+
+        let mut ghost = standing_at(0.5, 0.5);
+        ghost.set_mode(MovementMode::Spectator);
+        hold_input(&mut ghost, &world, forward(), 30);
+        assert!(ghost.position.z < -5.0);
+    }
+
+    // A player in the middle of the pool, feet at the given height
+    fn swimmer(feet: f32) -> PlayerController {
+        let mut player = standing_at(10.5, 0.5);
+        player.teleport(Vec3::new(10.5, feet, 0.5));
+        player
+    }
+
+    #[test]
+    fn sinks_slowly_in_water() {
+        let world = test_world();
+        let mut player = swimmer(6.0);
+        hold_input(&mut player, &world, PlayerInput::default(), 1);
+        assert_eq!(player.mode, MovementMode::Swim);
+        assert_eq!(player.fluid, Some(WATER));
+        assert_eq!(player.submerged, 1.0);
+        assert!(player.head_fluid(&world).is_some());
+
+        hold_input(&mut player, &world, PlayerInput::default(), 60);
+        assert!(player.velocity.y < 0.0);
+        assert!(player.velocity.y > -1.0, "sinking at {}", player.velocity.y);
+    }
+
+    #[test]
+    fn water_breaks_a_fall() {
+        let world = test_world();
+        let mut player = swimmer(8.5);
+        player.velocity.y = -20.0;
+        hold_input(&mut player, &world, PlayerInput::default(), 20);
+        assert!(
+            player.velocity.y > -5.0,
+            "still falling at {}",
+            player.velocity.y
+        );
+    }
+
+    #[test]
+    fn swimming_up_floats_at_the_surface() {
+        let world = test_world();
+        let mut player = swimmer(5.0);
+        let up = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        hold_input(&mut player, &world, up, 300);
+        // Head above the water at y = 9, body still in it
+        assert!(player.head_fluid(&world).is_none());
+        assert!(player.eye().y > 9.0);
+        assert!(player.position.y < 9.0);
+        assert_eq!(player.mode, MovementMode::Swim);
+    }
+
+    #[test]
+    fn swims_slower_than_walking() {
+        let world = test_world();
+        let mut player = swimmer(5.0);
+        let west = PlayerInput {
+            look: Vec3::NEG_X,
+            forward: 1.0,
+            ..Default::default()
+        };
+        hold_input(&mut player, &world, west, 6);
+        let before = player.position.x;
+        hold_input(&mut player, &world, west, 1);
+        let speed = (player.position.x - before).abs() / DT;
+        assert!((speed - 10.0 * WATER.speed_multiplier).abs() < 0.1);
+
+        // Walking out of the pool goes back to walking
+        hold_input(&mut player, &world, west, 120);
+        assert_eq!(player.mode, MovementMode::Walk);
+        assert!(player.fluid.is_none());
+    }
+
+    #[test]
+    fn sneaking_crouches_and_slows_down() {
+        let world = test_world();
+        let mut player = standing_at(0.5, 0.5);
+        let standing_eye = player.eye().y;
+        let sneak = PlayerInput {
+            sneak: true,
+            ..forward()
+        };
+        hold_input(&mut player, &world, sneak, 2);
+        assert!(player.sneaking);
+        assert_eq!(player.aabb().max.y - player.aabb().min.y, 1.5);
+        assert!(player.eye().y < standing_eye);
+
+        hold_input(&mut player, &world, forward(), 1);
+        assert!(!player.sneaking);
+        assert_eq!(player.eye().y, standing_eye);
+    }
+
+    #[test]
+    fn sneaking_stops_at_ledges() {
+        // The floor ends at z = -16
+        let world = test_world();
+        let mut careful = standing_at(0.5, -14.0);
+        let sneak = PlayerInput {
+            sneak: true,
+            ..forward()
+        };
+        hold_input(&mut careful, &world, sneak, 180);
+        assert!(careful.on_ground);
+        assert!((careful.position.y - 5.0).abs() < 1e-3);
+        // Still standing on the last block, hanging over the edge
+        assert!(careful.aabb().max.z > -16.0);
+        assert!(careful.position.z < -15.9);
+
+        let mut careless = standing_at(0.5, -14.0);
+        hold_input(&mut careless, &world, forward(), 60);
+        assert!(careless.position.y < 5.0);
+    }
+
+    #[test]
+    fn stays_crouched_under_low_ceilings() {
+        let mut world = test_world();
+        for z in -6..-2 {
+            world.set_block(0, 6, z, Block::new(BlockType::Stone));
+        }
+        let config = PlayerConfig {
+            sneaking_hitbox: Hitbox {
+                width: 0.6,
+                height: 0.9,
+                eye_height: 0.7,
+            },
+            ..PlayerConfig::default()
+        };
+        let mut player = PlayerController::new(Vec3::new(0.5, 5.0, 0.5), config);
+        let sneak = PlayerInput {
+            sneak: true,
+            ..forward()
+        };
+        // Crawl under the ceiling and let go of sneak there
+        while player.position.z > -4.5 {
+            player.tick(&sneak, &world, DT);
+        }
+        player.tick(&PlayerInput::default(), &world, DT);
+        assert!(player.sneaking);
+        assert!(!collides(&world, &player.aabb()));
+    }
+
+    #[test]
+    fn interpolates_between_ticks() {
+        let world = test_world();
+        let mut player = standing_at(0.5, 0.5);
+        hold_input(&mut player, &world, forward(), 2);
+        let before = player.previous_eye;
+        let middle = player.interpolated_eye(0.5);
+        assert!((middle - (before + player.eye()) / 2.0).length() < 1e-5);
+
+        player.teleport(Vec3::new(3.0, 20.0, 3.0));
+        assert_eq!(player.interpolated_eye(0.5), player.eye());
+        assert_eq!(player.eye().y, 20.0 + PLAYER_HITBOX.eye_height);
+    }
+}
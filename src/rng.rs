@@ -0,0 +1,155 @@
+// Random numbers for world generation. A ChunkRng is seeded from the world
+// seed, a grid position and a salt, and produces the same sequence on every
+// platform and build: it only uses integer arithmetic of fixed width, unlike
+// rand's StdRng whose algorithm may change between releases.
+
+// Salts keep the streams of different features apart at the same position
+pub const SALT_VEGETATION: u32 = 1;
+pub const SALT_LAKE: u32 = 2;
+pub const SALT_EROSION: u32 = 3;
+pub const SALT_SKY_DECORATION: u32 = 4;
+pub const SALT_WANDER: u32 = 5;
+pub const SALT_RANDOM_TICK: u32 = 6;
+// Structure decay salts each block by adding its y to this
+pub const SALT_STRUCTURE_DECAY: u32 = 0x1000;
+// Growth on random ticks salts each block by adding its y to this
+pub const SALT_GROWTH: u32 = 0x2000;
+
+// SplitMix64 seeded by a hash of its inputs
+#[derive(Clone, Debug)]
+pub struct ChunkRng {
+    state: u64,
+}
+
+impl ChunkRng {
+    // The position can be any grid: a chunk, a column or a larger region
+    pub fn new(world_seed: u32, chunk_x: i32, chunk_z: i32, salt: u32) -> Self {
+        let mut state = world_seed as u64 ^ 0x9E37_79B9_7F4A_7C15;
+        for v in [chunk_x as u32 as u64, chunk_z as u32 as u64, salt as u64] {
+            state ^= v;
+            state = state.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            state ^= state >> 31;
+        }
+        Self { state }
+    }
+
+    // Also seeded by the game tick, for what happens at random while the
+    // world runs: the same level and tick give the same numbers
+    pub fn at_tick(world_seed: u32, game_tick: u64, x: i32, z: i32, salt: u32) -> Self {
+        let mut rng = Self::new(world_seed, x, z, salt);
+        rng.state ^= game_tick;
+        rng.state = rng.state.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        rng.state ^= rng.state >> 31;
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    // Uniform in 0..bound, or 0 if bound is 0
+    pub fn below(&mut self, bound: u32) -> u32 {
+        ((self.next_u32() as u64 * bound as u64) >> 32) as u32
+    }
+
+    // Uniform in min..=max
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        assert!(min <= max, "empty range {}..={}", min, max);
+        let span = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + ((self.next_u32() as u64 * span) >> 32) as i64) as i32
+    }
+
+    // Uniform in min..max
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        let value = min + (max - min) * self.next_f32();
+        // Rounding can land exactly on max, which callers rely on excluding
+        if value < max { value } else { min }
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_inputs_give_same_sequence() {
+        let mut a = ChunkRng::new(12345, -3, 7, SALT_VEGETATION);
+        let mut b = ChunkRng::new(12345, -3, 7, SALT_VEGETATION);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn inputs_change_the_sequence() {
+        let first = |seed, x, z, salt| ChunkRng::new(seed, x, z, salt).next_u64();
+        let base = first(1, 0, 0, 0);
+        assert_ne!(base, first(2, 0, 0, 0));
+        assert_ne!(base, first(1, 1, 0, 0));
+        assert_ne!(base, first(1, 0, 1, 0));
+        assert_ne!(base, first(1, 0, 0, 1));
+        // Swapping the axes must not give the same stream
+        assert_ne!(first(1, 4, 9, 0), first(1, 9, 4, 0));
+    }
+
+    // Pins the algorithm: if this fails, every generated world has changed
+    #[test]
+    fn sequence_is_stable() {
+        let mut rng = ChunkRng::new(12345, 0, 0, 0);
+        let values: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(values, GOLDEN_SEQUENCE);
+    }
+
+    const GOLDEN_SEQUENCE: [u64; 3] = [
+        10883565488783333684,
+        15141641284169782742,
+        17337084514107800188,
+    ];
+
+    #[test]
+    fn ranges_stay_in_bounds() {
+        let mut rng = ChunkRng::new(7, 1, 2, 3);
+        for _ in 0..10_000 {
+            let i = rng.range_i32(-2, 3);
+            assert!((-2..=3).contains(&i));
+            assert!(rng.below(5) < 5);
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+            let g = rng.range_f32(30.0, 45.0);
+            assert!((30.0..45.0).contains(&g));
+        }
+        assert_eq!(rng.below(0), 0);
+        assert_eq!(rng.range_i32(4, 4), 4);
+    }
+
+    #[test]
+    fn range_covers_every_value() {
+        let mut rng = ChunkRng::new(99, 0, 0, 0);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            seen[(rng.range_i32(-2, 3) + 2) as usize] = true;
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::block_update::DEFAULT_RANDOM_TICK_SPEED;
+use crate::spawn::SpawnPoint;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// What a world keeps between sessions. Chunks aren't stored: they are
+// generated again from the seed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LevelData {
+    pub seed: u32,
+    // Chosen the first time the world is played
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<SpawnPoint>,
+    // Bed or checkpoint of each player, by name
+    #[serde(default)]
+    pub respawns: BTreeMap<String, SpawnPoint>,
+    // Random ticks per chunk section per game tick: how fast plants grow
+    // and other slow changes happen. 0 stops them.
+    #[serde(default = "default_random_tick_speed")]
+    pub random_tick_speed: u32,
+    // Game ticks run so far, so random ticks carry on rather than repeat
+    // the ones of the last session
+    #[serde(default)]
+    pub game_tick: u64,
+}
+
+fn default_random_tick_speed() -> u32 {
+    DEFAULT_RANDOM_TICK_SPEED
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    // A binary save that doesn't hold what it should
+    Damaged(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "failed to access save: {}", err),
+            SaveError::Parse(err) => write!(f, "failed to parse save: {}", err),
+            SaveError::Serialize(err) => write!(f, "failed to write save: {}", err),
+            SaveError::Damaged(problem) => write!(f, "save is damaged: {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl LevelData {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            spawn: None,
+            respawns: BTreeMap::new(),
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            game_tick: 0,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let text = fs::read_to_string(path).map_err(SaveError::Io)?;
+        toml::from_str(&text).map_err(SaveError::Parse)
+    }
+
+    // Starts a new world with the given seed when there is no save yet. A
+    // save that can't be read is an error rather than a new world, which
+    // would overwrite it at the first save.
+    pub fn load_or_new(path: impl AsRef<Path>, seed: u32) -> Result<Self, SaveError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::new(seed));
+        }
+        Self::load(path)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(SaveError::Io)?;
+        }
+        let text = toml::to_string(self).map_err(SaveError::Serialize)?;
+        fs::write(path, text).map_err(SaveError::Io)
+    }
+
+    // A player's bed or checkpoint, else the world spawn
+    pub fn respawn_point(&self, player: &str) -> Option<SpawnPoint> {
+        self.respawns.get(player).copied().or(self.spawn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damaged_saves_are_errors_and_left_alone() {
+        let path = std::env::temp_dir().join(format!("level-{}.toml", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert_eq!(LevelData::load_or_new(&path, 7).unwrap(), LevelData::new(7));
+
+        fs::write(&path, "seed = \"not a number\"").unwrap();
+        let result = LevelData::load_or_new(&path, 7);
+        let text = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(matches!(result, Err(SaveError::Parse(_))));
+        assert_eq!(text, "seed = \"not a number\"");
+    }
+}
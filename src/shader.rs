@@ -10,11 +10,9 @@ pub struct Shader {
 impl Shader {
     pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
         let vertex_code = fs::read_to_string(vertex_path)
-            .expect(&format!("Failed to read vertex shader: {}", vertex_path));
-        let fragment_code = fs::read_to_string(fragment_path).expect(&format!(
-            "Failed to read fragment shader: {}",
-            fragment_path
-        ));
+            .unwrap_or_else(|_| panic!("Failed to read vertex shader: {}", vertex_path));
+        let fragment_code = fs::read_to_string(fragment_path)
+            .unwrap_or_else(|_| panic!("Failed to read fragment shader: {}", fragment_path));
 
         unsafe {
             let vertex = Self::compile_shader(&vertex_code, gl::VERTEX_SHADER);
@@ -111,6 +109,14 @@ impl Shader {
         }
     }
 
+    pub fn set_float(&self, name: &str, value: f32) {
+        unsafe {
+            let c_name = CString::new(name).unwrap();
+            let location = gl::GetUniformLocation(self.id, c_name.as_ptr());
+            gl::Uniform1f(location, value);
+        }
+    }
+
     pub fn set_bool(&self, name: &str, value: bool) {
         unsafe {
             let c_name = CString::new(name).unwrap();
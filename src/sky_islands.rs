@@ -0,0 +1,249 @@
+use crate::block::BlockType;
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk};
+use crate::dimension::ChunkGenerator;
+use crate::rng::{ChunkRng, SALT_SKY_DECORATION};
+use crate::terrain_config::{HeightBand, TerrainConfig};
+use crate::vegetation::{MAX_TREE_REACH, TreeSpecies, grow_tree, pick_weighted, place_tree_block};
+use noise::{NoiseFn, Perlin};
+
+// Where the widest part of an island sits in its band, measured from the bottom
+const BAND_PEAK: f64 = 0.7;
+// Open air a tree needs above the ground before it may grow
+const TREE_CLEARANCE: usize = 12;
+
+const SKY_TREES: &[(TreeSpecies, u32)] = &[
+    (TreeSpecies::Oak, 4),
+    (TreeSpecies::Birch, 3),
+    (TreeSpecies::Bush, 2),
+    (TreeSpecies::GreatOak, 1),
+];
+const SKY_COVER: &[(BlockType, u32)] = &[
+    (BlockType::TallGrass, 6),
+    (BlockType::Dandelion, 2),
+    (BlockType::Poppy, 2),
+];
+
+// Generator for the sky dimension: floating islands over an empty void
+pub struct SkyIslandGenerator {
+    seed: u32,
+    noise: Perlin,
+    detail_noise: Perlin,
+    config: TerrainConfig,
+}
+
+impl SkyIslandGenerator {
+    pub fn new(seed: u32, config: TerrainConfig) -> Self {
+        Self {
+            seed,
+            noise: Perlin::new(seed.wrapping_add(10)),
+            detail_noise: Perlin::new(seed.wrapping_add(11)),
+            config,
+        }
+    }
+
+    pub fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let mut chunk = Chunk::new(chunk_x, chunk_z);
+        let dirt_depth = self.config.layers.dirt_depth;
+        let mut columns = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let world_x = chunk_x * CHUNK_SIZE as i32 + x as i32;
+                let world_z = chunk_z * CHUNK_SIZE as i32 + z as i32;
+                let solid = self.solid_column(world_x, world_z);
+
+                // Grass on every top face, dirt under it, stone further down
+                let mut depth = 0;
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    if !solid[y] {
+                        depth = 0;
+                        continue;
+                    }
+                    let block_type = if depth == 0 {
+                        BlockType::Grass
+                    } else if depth <= dirt_depth {
+                        BlockType::Dirt
+                    } else {
+                        BlockType::Stone
+                    };
+                    chunk.set_block(x, y, z, block_type);
+                    depth += 1;
+                }
+                columns.push(solid);
+            }
+        }
+
+        self.decorate(&mut chunk, &columns);
+        chunk
+    }
+
+    // Which blocks of a column are island, bottom to top
+    fn solid_column(&self, x: i32, z: i32) -> Vec<bool> {
+        let sky = &self.config.sky_islands;
+        let mut solid = vec![false; CHUNK_HEIGHT];
+
+        for band in &sky.bands {
+            for (y, cell) in solid.iter_mut().enumerate().take(band.max).skip(band.min) {
+                let fade = band_fade(band, y);
+                if fade <= 0.0 {
+                    continue;
+                }
+                let point = [
+                    x as f64 * sky.noise_scale,
+                    y as f64 * sky.noise_scale / sky.vertical_scale,
+                    z as f64 * sky.noise_scale,
+                ];
+                let detail = point.map(|v| v * 3.0);
+                let density = (self.noise.get(point) + self.detail_noise.get(detail) * 0.35) / 1.35;
+                if density * fade > sky.threshold {
+                    *cell = true;
+                }
+            }
+        }
+        solid
+    }
+
+    // Trees and flowers on the island tops. Trees rooted next to the chunk
+    // are grown too and clipped, so crowns continue across chunk borders.
+    fn decorate(&self, chunk: &mut Chunk, columns: &[Vec<bool>]) {
+        let sky = &self.config.sky_islands;
+        let min_x = chunk.x * CHUNK_SIZE as i32;
+        let min_z = chunk.z * CHUNK_SIZE as i32;
+        let reach = MAX_TREE_REACH;
+
+        for x in min_x - reach..min_x + CHUNK_SIZE as i32 + reach {
+            for z in min_z - reach..min_z + CHUNK_SIZE as i32 + reach {
+                let in_chunk = x >= min_x
+                    && x < min_x + CHUNK_SIZE as i32
+                    && z >= min_z
+                    && z < min_z + CHUNK_SIZE as i32;
+                let mut rng = ChunkRng::new(self.seed, x, z, SALT_SKY_DECORATION);
+                let grows_tree = rng.chance(sky.tree_chance);
+                if !in_chunk && !grows_tree {
+                    continue;
+                }
+
+                let outside;
+                let solid = if in_chunk {
+                    &columns[(x - min_x) as usize * CHUNK_SIZE + (z - min_z) as usize]
+                } else {
+                    outside = self.solid_column(x, z);
+                    &outside
+                };
+
+                // The tree takes the highest top with room above it
+                if grows_tree
+                    && let Some(ground) = tops(solid).find(|&y| has_clearance(solid, y))
+                    && let Some(species) = pick_weighted(SKY_TREES, &mut rng)
+                {
+                    let base = (x, ground as i32 + 1, z);
+                    for (bx, by, bz, block_type) in
+                        grow_tree(species, base, &self.config.trees, &mut rng)
+                    {
+                        place_tree_block(chunk, bx, by, bz, block_type);
+                    }
+                    continue;
+                }
+
+                if !in_chunk {
+                    continue;
+                }
+                let (lx, lz) = ((x - min_x) as usize, (z - min_z) as usize);
+                for ground in tops(solid) {
+                    let free = chunk
+                        .get_block(lx, ground + 1, lz)
+                        .is_some_and(|block| block.block_type == BlockType::Air);
+                    if rng.chance(sky.cover_chance)
+                        && free
+                        && let Some(plant) = pick_weighted(SKY_COVER, &mut rng)
+                    {
+                        chunk.set_block(lx, ground + 1, lz, plant);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerator for SkyIslandGenerator {
+    fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        SkyIslandGenerator::generate_chunk(self, chunk_x, chunk_z)
+    }
+}
+
+// 1 at the widest point of the band, falling to 0 at its bottom and top
+fn band_fade(band: &HeightBand, y: usize) -> f64 {
+    if y <= band.min || y >= band.max {
+        return 0.0;
+    }
+    let peak = band.min as f64 + (band.max - band.min) as f64 * BAND_PEAK;
+    let y = y as f64;
+    let d = if y < peak {
+        (peak - y) / (peak - band.min as f64)
+    } else {
+        (y - peak) / (band.max as f64 - peak)
+    };
+    1.0 - d * d
+}
+
+// Heights of the top blocks in a column, highest first
+fn tops(solid: &[bool]) -> impl Iterator<Item = usize> + '_ {
+    (0..CHUNK_HEIGHT - 1)
+        .rev()
+        .filter(|&y| solid[y] && !solid[y + 1])
+}
+
+fn has_clearance(solid: &[bool], ground: usize) -> bool {
+    ground + TREE_CLEARANCE < CHUNK_HEIGHT
+        && solid[ground + 1..=ground + TREE_CLEARANCE]
+            .iter()
+            .all(|s| !s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn islands_stay_inside_their_bands() {
+        let config = TerrainConfig::default();
+        let bands = config.sky_islands.bands.clone();
+        let generator = SkyIslandGenerator::new(12345, config);
+        let in_band = |y: usize| bands.iter().any(|band| y > band.min && y < band.max);
+
+        let mut island_blocks = 0;
+        for chunk_x in -2..2 {
+            for chunk_z in -2..2 {
+                let chunk = generator.generate_chunk(chunk_x, chunk_z);
+                for x in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        for y in 0..CHUNK_HEIGHT {
+                            let block_type = chunk.get_block(x, y, z).unwrap().block_type;
+                            if matches!(
+                                block_type,
+                                BlockType::Grass | BlockType::Dirt | BlockType::Stone
+                            ) {
+                                assert!(in_band(y), "island block at y {}", y);
+                                island_blocks += 1;
+                            } else if block_type != BlockType::Air {
+                                // Trees and plants only grow on top of islands
+                                assert!(y > bands[0].min, "{:?} at y {}", block_type, y);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(island_blocks > 0);
+    }
+
+    #[test]
+    fn bands_fade_out_at_their_edges() {
+        let band = HeightBand { min: 100, max: 150 };
+        assert_eq!(band_fade(&band, 100), 0.0);
+        assert_eq!(band_fade(&band, 150), 0.0);
+        assert_eq!(band_fade(&band, 40), 0.0);
+        assert_eq!(band_fade(&band, 135), 1.0);
+        assert!(band_fade(&band, 120) > band_fade(&band, 110));
+    }
+}
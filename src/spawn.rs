@@ -0,0 +1,128 @@
+use crate::block::BlockType;
+use crate::chunk::{CHUNK_HEIGHT, HeightmapKind};
+use crate::dimension::DimensionId;
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+
+// How far from its centre the spawn search looks, in blocks
+pub const SPAWN_SEARCH_RADIUS: i32 = 64;
+// Feet below this y have fallen out of the world
+pub const VOID_Y: f32 = -32.0;
+
+// The block the player's feet are placed in, and the dimension it is in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpawnPoint {
+    #[serde(default)]
+    pub dimension: DimensionId,
+    pub position: (i32, i32, i32),
+}
+
+// Dry, solid ground under the feet and room for the whole body above it.
+// Leaves and cacti count as unsafe ground.
+pub fn is_safe_spawn(world: &World, x: i32, y: i32, z: i32) -> bool {
+    let Some(ground) = world.get_block(x, y - 1, z) else {
+        return false;
+    };
+    if !ground.is_solid() || ground.is_leaves() || ground.block_type == BlockType::Cactus {
+        return false;
+    }
+    (y..y + 2).all(|by| {
+        world
+            .get_block(x, by, z)
+            .is_some_and(|block| !block.is_solid() && block.block_type != BlockType::Water)
+    })
+}
+
+// Nearest safe surface spot to a column, searching outwards ring by ring so
+// the result only depends on the terrain. Only loaded chunks are searched.
+pub fn find_spawn(world: &World, center: (i32, i32), radius: i32) -> Option<(i32, i32, i32)> {
+    (0..=radius).find_map(|ring| {
+        ring_columns(center, ring).find_map(|(x, z)| {
+            // The top of anything that blocks movement, water included, so
+            // columns under water are never picked
+            let top = world.heightmap_at(HeightmapKind::MotionBlocking, x, z)?;
+            let feet = top + 1;
+            if feet + 1 < CHUNK_HEIGHT as i32 && is_safe_spawn(world, x, feet, z) {
+                Some((x, feet, z))
+            } else {
+                None
+            }
+        })
+    })
+}
+
+// A stored spawn may have been built over or dug out since it was saved:
+// keep it if it is still safe, otherwise look for the nearest spot around it
+pub fn settle(world: &World, position: (i32, i32, i32)) -> Option<(i32, i32, i32)> {
+    let (x, y, z) = position;
+    if is_safe_spawn(world, x, y, z) {
+        return Some(position);
+    }
+    find_spawn(world, (x, z), SPAWN_SEARCH_RADIUS)
+}
+
+pub fn fell_out_of_world(feet_y: f32) -> bool {
+    feet_y < VOID_Y
+}
+
+// The columns at exactly `ring` blocks from the centre along either axis
+fn ring_columns(center: (i32, i32), ring: i32) -> impl Iterator<Item = (i32, i32)> {
+    let (cx, cz) = center;
+    (-ring..=ring).flat_map(move |dx| {
+        (-ring..=ring)
+            .filter(move |&dz| dx.abs() == ring || dz.abs() == ring)
+            .map(move |dz| (cx + dx, cz + dz))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::test_world::{empty_world, fill_box};
+
+    // A chunk of grass with its top at y = 10
+    fn grass_world() -> World {
+        let mut world = empty_world(0..=0, 0..=0);
+        fill_box(&mut world, BlockType::Grass, 0..16, 0..11, 0..16);
+        world
+    }
+
+    #[test]
+    fn finds_ground_at_the_centre() {
+        let world = grass_world();
+        assert_eq!(find_spawn(&world, (5, 5), 8), Some((5, 11, 5)));
+    }
+
+    #[test]
+    fn avoids_water_leaves_and_low_ceilings() {
+        let mut world = grass_world();
+        world.set_block(5, 11, 5, Block::new(BlockType::Water));
+        world.set_block(6, 11, 5, Block::new(BlockType::Leaves));
+        // Only one block of room between the ground and the overhang
+        world.set_block(4, 12, 5, Block::new(BlockType::Stone));
+        world.set_block(4, 11, 5, Block::new(BlockType::Air));
+
+        assert!(!is_safe_spawn(&world, 5, 12, 5));
+        assert!(!is_safe_spawn(&world, 6, 12, 5));
+        assert!(!is_safe_spawn(&world, 4, 11, 5));
+        let (x, y, z) = find_spawn(&world, (5, 5), 8).unwrap();
+        assert!(is_safe_spawn(&world, x, y, z));
+        assert_eq!((x - 5).abs().max((z - 5).abs()), 1);
+    }
+
+    #[test]
+    fn settle_keeps_safe_points_and_moves_buried_ones() {
+        let mut world = grass_world();
+        assert_eq!(settle(&world, (3, 11, 3)), Some((3, 11, 3)));
+        world.set_block(3, 11, 3, Block::new(BlockType::Stone));
+        let moved = settle(&world, (3, 11, 3)).unwrap();
+        assert_ne!(moved, (3, 11, 3));
+        assert!(is_safe_spawn(&world, moved.0, moved.1, moved.2));
+    }
+
+    #[test]
+    fn nothing_is_found_without_loaded_chunks() {
+        assert_eq!(find_spawn(&World::new(), (0, 0), 4), None);
+    }
+}
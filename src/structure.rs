@@ -0,0 +1,276 @@
+use crate::biome::Biome;
+use crate::block::BlockType;
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StructureKind {
+    Village,
+    Dungeon,
+    Ruins,
+}
+
+impl StructureKind {
+    pub const ALL: [StructureKind; 3] = [
+        StructureKind::Village,
+        StructureKind::Dungeon,
+        StructureKind::Ruins,
+    ];
+
+    // Biomes a structure may start in
+    pub fn allows_biome(&self, biome: Biome) -> bool {
+        match self {
+            StructureKind::Village => matches!(
+                biome,
+                Biome::Plains | Biome::Desert | Biome::Taiga | Biome::Tundra
+            ),
+            StructureKind::Dungeon => true,
+            StructureKind::Ruins => biome.is_land() && biome != Biome::Beach,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemplateId {
+    Well,
+    House,
+    Dungeon,
+    Ruin,
+}
+
+// A saved block volume. Cells set to None keep whatever terrain is there.
+pub struct StructureTemplate {
+    pub size: (usize, usize, usize),
+    pub foundation: Option<BlockType>,
+    blocks: Vec<Option<BlockType>>,
+}
+
+// On-disk layout of a template, see structures/*.toml
+#[derive(Deserialize)]
+struct TemplateFile {
+    size: [usize; 3],
+    foundation: Option<BlockType>,
+    palette: HashMap<char, BlockType>,
+    layers: Vec<Vec<String>>,
+}
+
+impl StructureTemplate {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let file: TemplateFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let [sx, sy, sz] = file.size;
+
+        if file.layers.len() != sy {
+            return Err(format!(
+                "expected {} layers, found {}",
+                sy,
+                file.layers.len()
+            ));
+        }
+
+        let mut blocks = vec![None; sx * sy * sz];
+        for (y, layer) in file.layers.iter().enumerate() {
+            if layer.len() != sz {
+                return Err(format!(
+                    "layer {} has {} rows, expected {}",
+                    y,
+                    layer.len(),
+                    sz
+                ));
+            }
+            for (z, row) in layer.iter().enumerate() {
+                if row.chars().count() != sx {
+                    return Err(format!("layer {} row {} is not {} blocks wide", y, z, sx));
+                }
+                for (x, c) in row.chars().enumerate() {
+                    if c == ' ' {
+                        continue;
+                    }
+                    let Some(block_type) = file.palette.get(&c) else {
+                        return Err(format!("unknown palette character '{}'", c));
+                    };
+                    blocks[(y * sz + z) * sx + x] = Some(*block_type);
+                }
+            }
+        }
+
+        Ok(Self {
+            size: (sx, sy, sz),
+            foundation: file.foundation,
+            blocks,
+        })
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<BlockType> {
+        let (sx, _, sz) = self.size;
+        self.blocks[(y * sz + z) * sx + x]
+    }
+}
+
+// Built-in templates, compiled into the binary so they are always available
+pub struct StructureTemplates {
+    well: StructureTemplate,
+    house: StructureTemplate,
+    dungeon: StructureTemplate,
+    ruin: StructureTemplate,
+}
+
+impl StructureTemplates {
+    pub fn load() -> Self {
+        let parse = |name: &str, text: &str| {
+            StructureTemplate::from_toml(text)
+                .unwrap_or_else(|e| panic!("Built-in structure template {} is broken: {}", name, e))
+        };
+        Self {
+            well: parse("well", include_str!("../structures/well.toml")),
+            house: parse("house", include_str!("../structures/house.toml")),
+            dungeon: parse("dungeon", include_str!("../structures/dungeon.toml")),
+            ruin: parse("ruin", include_str!("../structures/ruin.toml")),
+        }
+    }
+
+    pub fn get(&self, id: TemplateId) -> &StructureTemplate {
+        match id {
+            TemplateId::Well => &self.well,
+            TemplateId::House => &self.house,
+            TemplateId::Dungeon => &self.dungeon,
+            TemplateId::Ruin => &self.ruin,
+        }
+    }
+}
+
+// One template placed in the world, with its minimum corner at (x, y, z)
+#[derive(Clone, Debug)]
+pub struct Piece {
+    pub template: TemplateId,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    // Chance (0..1) of each block being left out, for weathered structures
+    pub decay: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct StructureStart {
+    pub kind: StructureKind,
+    pub origin: (i32, i32, i32),
+    pub pieces: Vec<Piece>,
+}
+
+// Writes the part of a piece that falls inside this chunk. `keep` decides
+// per block whether a decaying piece keeps it.
+pub fn place_piece(
+    chunk: &mut Chunk,
+    template: &StructureTemplate,
+    piece: &Piece,
+    keep: impl Fn(i32, i32, i32) -> bool,
+) {
+    let (sx, sy, sz) = template.size;
+    let chunk_min_x = chunk.x * CHUNK_SIZE as i32;
+    let chunk_min_z = chunk.z * CHUNK_SIZE as i32;
+
+    for tx in 0..sx {
+        for tz in 0..sz {
+            let lx = piece.x + tx as i32 - chunk_min_x;
+            let lz = piece.z + tz as i32 - chunk_min_z;
+            if lx < 0 || lx >= CHUNK_SIZE as i32 || lz < 0 || lz >= CHUNK_SIZE as i32 {
+                continue;
+            }
+            let (lx, lz) = (lx as usize, lz as usize);
+
+            for ty in 0..sy {
+                let y = piece.y + ty as i32;
+                if y < 0 || y >= CHUNK_HEIGHT as i32 {
+                    continue;
+                }
+                let Some(block_type) = template.get(tx, ty, tz) else {
+                    continue;
+                };
+                if piece.decay > 0.0 && !keep(lx as i32 + chunk_min_x, y, lz as i32 + chunk_min_z) {
+                    continue;
+                }
+                chunk.set_block(lx, y as usize, lz, block_type);
+            }
+
+            // Fill the gap between the floor and lower terrain so the piece
+            // doesn't float
+            if let Some(foundation) = template.foundation {
+                if template.get(tx, 0, tz).is_none() {
+                    continue;
+                }
+                let mut y = piece.y - 1;
+                while y >= 0 {
+                    let below = chunk.get_block(lx, y as usize, lz).unwrap();
+                    if below.is_solid() {
+                        break;
+                    }
+                    chunk.set_block(lx, y as usize, lz, foundation);
+                    y -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = r#"
+        size = [2, 2, 1]
+        foundation = "Cobblestone"
+        palette = { c = "Cobblestone", p = "Planks" }
+        layers = [["cp"], [" c"]]
+    "#;
+
+    #[test]
+    fn templates_parse_layer_by_layer() {
+        let template = StructureTemplate::from_toml(POST).unwrap();
+        assert_eq!(template.size, (2, 2, 1));
+        assert_eq!(template.foundation, Some(BlockType::Cobblestone));
+        assert_eq!(template.get(0, 0, 0), Some(BlockType::Cobblestone));
+        assert_eq!(template.get(1, 0, 0), Some(BlockType::Planks));
+        assert_eq!(template.get(0, 1, 0), None);
+        assert_eq!(template.get(1, 1, 0), Some(BlockType::Cobblestone));
+    }
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        let layers = r#"[["cp"], [" c"]]"#;
+        let cases = [
+            (layers, r#"[["cp"]]"#, "expected 2 layers, found 1"),
+            (layers, r#"[["cp"], []]"#, "layer 1 has 0 rows, expected 1"),
+            (
+                layers,
+                r#"[["cp"], ["c"]]"#,
+                "layer 1 row 0 is not 2 blocks wide",
+            ),
+            (
+                layers,
+                r#"[["cx"], [" c"]]"#,
+                "unknown palette character 'x'",
+            ),
+            (r#""Planks""#, r#""Plank""#, "unknown variant"),
+            ("[2, 2, 1]", "[2, 2]", "invalid length 2"),
+        ];
+        for (from, to, expected) in cases {
+            let text = POST.replace(from, to);
+            assert_ne!(text, POST);
+            let Err(error) = StructureTemplate::from_toml(&text) else {
+                panic!("template with {} was accepted", to);
+            };
+            assert!(
+                error.contains(expected),
+                "{:?} should mention {:?}",
+                error,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn built_in_templates_load() {
+        let templates = StructureTemplates::load();
+        assert!(templates.get(TemplateId::House).foundation.is_some());
+    }
+}
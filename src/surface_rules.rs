@@ -0,0 +1,229 @@
+use crate::biome::Biome;
+use crate::block::BlockType;
+use serde::{Deserialize, Serialize};
+
+// Surface rules run once the base terrain is shaped and adjust the top of
+// each column: snow on peaks, bare rock on cliffs, ice on cold water. Every
+// rule whose conditions all hold is applied in order, so later rules win.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SurfaceRule {
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    pub then: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    // y of the topmost terrain block
+    MinHeight(usize),
+    MaxHeight(usize),
+    // Largest height difference to one of the four direct neighbours
+    MinSlope(usize),
+    MaxSlope(usize),
+    // Temperature noise after cooling with altitude, roughly -1..1
+    MinTemperature(f64),
+    MaxTemperature(f64),
+    Biomes(Vec<Biome>),
+    // The terrain top is above the water
+    Dry,
+    // The terrain top is covered by water
+    Underwater,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // Replaces the topmost terrain block
+    Surface(BlockType),
+    // Replaces the layer between the surface block and the stone
+    Filler(BlockType),
+    // Places a block on top of dry ground, such as a layer of snow
+    Cover(BlockType),
+    // Replaces the top block of the water above the column
+    Freeze(BlockType),
+}
+
+// What the rules know about one column
+pub struct SurfaceContext {
+    pub height: usize,
+    pub slope: usize,
+    pub temperature: f64,
+    pub biome: Biome,
+    pub underwater: bool,
+}
+
+// The blocks at the top of a column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Surface {
+    pub top: BlockType,
+    pub filler: BlockType,
+    pub cover: Option<BlockType>,
+    pub ice: Option<BlockType>,
+}
+
+impl Condition {
+    pub fn holds(&self, context: &SurfaceContext) -> bool {
+        match self {
+            Condition::MinHeight(height) => context.height >= *height,
+            Condition::MaxHeight(height) => context.height <= *height,
+            Condition::MinSlope(slope) => context.slope >= *slope,
+            Condition::MaxSlope(slope) => context.slope <= *slope,
+            Condition::MinTemperature(temperature) => context.temperature >= *temperature,
+            Condition::MaxTemperature(temperature) => context.temperature <= *temperature,
+            Condition::Biomes(biomes) => biomes.contains(&context.biome),
+            Condition::Dry => !context.underwater,
+            Condition::Underwater => context.underwater,
+        }
+    }
+}
+
+impl SurfaceRule {
+    pub fn matches(&self, context: &SurfaceContext) -> bool {
+        self.when.iter().all(|condition| condition.holds(context))
+    }
+}
+
+pub fn apply_rules(rules: &[SurfaceRule], context: &SurfaceContext, surface: &mut Surface) {
+    for rule in rules.iter().filter(|rule| rule.matches(context)) {
+        for action in &rule.then {
+            match *action {
+                Action::Surface(block_type) => surface.top = block_type,
+                Action::Filler(block_type) => surface.filler = block_type,
+                Action::Cover(block_type) => surface.cover = Some(block_type),
+                Action::Freeze(block_type) => surface.ice = Some(block_type),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> SurfaceContext {
+        SurfaceContext {
+            height: 80,
+            slope: 1,
+            temperature: 0.0,
+            biome: Biome::Plains,
+            underwater: false,
+        }
+    }
+
+    fn grass() -> Surface {
+        Surface {
+            top: BlockType::Grass,
+            filler: BlockType::Dirt,
+            cover: None,
+            ice: None,
+        }
+    }
+
+    #[test]
+    fn conditions_check_one_property_each() {
+        let context = context();
+        let cases = [
+            (Condition::MinHeight(80), true),
+            (Condition::MinHeight(81), false),
+            (Condition::MaxHeight(80), true),
+            (Condition::MaxHeight(79), false),
+            (Condition::MinSlope(2), false),
+            (Condition::MaxSlope(1), true),
+            (Condition::MinTemperature(0.5), false),
+            (Condition::MaxTemperature(0.0), true),
+            (Condition::Biomes(vec![Biome::Desert, Biome::Plains]), true),
+            (Condition::Biomes(vec![Biome::Taiga]), false),
+            (Condition::Dry, true),
+            (Condition::Underwater, false),
+        ];
+        for (condition, expected) in cases {
+            assert_eq!(condition.holds(&context), expected, "{:?}", condition);
+        }
+    }
+
+    #[test]
+    fn rules_apply_only_when_every_condition_holds() {
+        let peak = SurfaceRule {
+            when: vec![Condition::MinHeight(70), Condition::MaxSlope(0)],
+            then: vec![Action::Surface(BlockType::Snow)],
+        };
+        let mut surface = grass();
+        apply_rules(std::slice::from_ref(&peak), &context(), &mut surface);
+        assert_eq!(surface, grass());
+
+        let flat = SurfaceContext {
+            slope: 0,
+            ..context()
+        };
+        apply_rules(&[peak], &flat, &mut surface);
+        assert_eq!(surface.top, BlockType::Snow);
+        assert_eq!(surface.filler, BlockType::Dirt);
+
+        // A rule without conditions always applies
+        let always = SurfaceRule {
+            when: Vec::new(),
+            then: vec![Action::Freeze(BlockType::Ice)],
+        };
+        assert!(always.matches(&context()));
+    }
+
+    #[test]
+    fn later_rules_win() {
+        let rules = [
+            SurfaceRule {
+                when: vec![Condition::Dry],
+                then: vec![
+                    Action::Surface(BlockType::Stone),
+                    Action::Filler(BlockType::Stone),
+                    Action::Cover(BlockType::SnowLayer),
+                ],
+            },
+            SurfaceRule {
+                when: vec![Condition::MinHeight(50)],
+                then: vec![Action::Surface(BlockType::Gravel)],
+            },
+            // Doesn't match, so it can't undo the gravel
+            SurfaceRule {
+                when: vec![Condition::Underwater],
+                then: vec![Action::Surface(BlockType::Sand)],
+            },
+        ];
+        let mut surface = grass();
+        apply_rules(&rules, &context(), &mut surface);
+        assert_eq!(
+            surface,
+            Surface {
+                top: BlockType::Gravel,
+                filler: BlockType::Stone,
+                cover: Some(BlockType::SnowLayer),
+                ice: None,
+            }
+        );
+    }
+
+    #[test]
+    fn rules_read_from_toml() {
+        #[derive(Deserialize)]
+        struct Rules {
+            surface_rules: Vec<SurfaceRule>,
+        }
+        let text = r#"
+            [[surface_rules]]
+            when = [{ max_temperature = -0.4 }, "underwater", { biomes = ["Ocean"] }]
+            then = [{ freeze = "Ice" }]
+        "#;
+        let rules: Rules = toml::from_str(text).unwrap();
+        assert_eq!(
+            rules.surface_rules,
+            [SurfaceRule {
+                when: vec![
+                    Condition::MaxTemperature(-0.4),
+                    Condition::Underwater,
+                    Condition::Biomes(vec![Biome::Ocean]),
+                ],
+                then: vec![Action::Freeze(BlockType::Ice)],
+            }]
+        );
+    }
+}
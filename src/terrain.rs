@@ -1,121 +1,1074 @@
+use crate::angle;
+use crate::biome::Biome;
 use crate::block::BlockType;
 use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk};
+use crate::dimension::ChunkGenerator;
+use crate::erosion::{self, ErodedRegion, ErosionCache, Heightmap};
+use crate::rng::{ChunkRng, SALT_EROSION, SALT_LAKE, SALT_STRUCTURE_DECAY, SALT_VEGETATION};
+use crate::structure::{
+    self, Piece, StructureKind, StructureStart, StructureTemplates, TemplateId,
+};
+use crate::surface_rules::{self, Surface, SurfaceContext};
+use crate::terrain_config::{PlacementConfig, TerrainConfig};
+use crate::vegetation::{
+    MAX_TREE_CHANCE, MAX_TREE_REACH, VegetationTable, grow_tree, pick_weighted, place_tree_block,
+};
 use noise::{NoiseFn, Perlin};
 
+// Number of points sampled around a lake to find the lowest point of its rim
+const LAKE_RIM_SAMPLES: usize = 16;
+// How far outside the lake its banks slope back up to the natural terrain
+const LAKE_BANK: f64 = 0.5;
+// Furthest a structure piece can reach from its start chunk, in chunks
+const STRUCTURE_REACH: i32 = 2;
+// How many regions out find_nearest_structure searches before giving up
+const STRUCTURE_SEARCH_RADIUS: i32 = 32;
+
 pub struct TerrainGenerator {
+    seed: u32,
     noise: Perlin,
+    river_noise: Perlin,
+    detail_noise: Perlin,
+    temperature_noise: Perlin,
+    humidity_noise: Perlin,
+    templates: StructureTemplates,
+    erosion_cache: ErosionCache,
+    config: TerrainConfig,
+}
+
+// The final shape of one column after rivers and lakes have been carved
+#[derive(Clone, Copy)]
+struct Column {
+    // Terrain occupies 0..height
+    height: usize,
+    // Water fills height..water_level
+    water_level: usize,
+    // Carved by a river or lake, so the bed gets sand or gravel
+    water_bed: bool,
+}
+
+// A column together with what grows and lies on top of it
+struct ShapedColumn {
+    column: Column,
+    biome: Biome,
+    surface: Surface,
+}
+
+struct Lake {
+    center_x: f64,
+    center_z: f64,
+    radius: f64,
+    surface: usize,
+    cell: (i32, i32),
 }
 
 impl TerrainGenerator {
-    pub fn new(seed: u32) -> Self {
+    pub fn new(seed: u32, config: TerrainConfig) -> Self {
         Self {
+            seed,
             noise: Perlin::new(seed),
+            river_noise: Perlin::new(seed.wrapping_add(1)),
+            detail_noise: Perlin::new(seed.wrapping_add(2)),
+            temperature_noise: Perlin::new(seed.wrapping_add(3)),
+            humidity_noise: Perlin::new(seed.wrapping_add(4)),
+            templates: StructureTemplates::load(),
+            erosion_cache: ErosionCache::default(),
+            config,
         }
     }
 
     pub fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
         let mut chunk = Chunk::new(chunk_x, chunk_z);
+        let dirt_depth = self.config.layers.dirt_depth;
+        let columns = self.shape_columns(chunk_x, chunk_z);
 
         for x in 0..CHUNK_SIZE {
             for z in 0..CHUNK_SIZE {
-                let world_x = chunk_x * CHUNK_SIZE as i32 + x as i32;
-                let world_z = chunk_z * CHUNK_SIZE as i32 + z as i32;
-
-                // Multi-octave noise for realistic terrain
-                let height = self.get_height(world_x, world_z);
+                let ShapedColumn {
+                    column, surface, ..
+                } = &columns[z * CHUNK_SIZE + x];
+                let height = column.height;
 
                 for y in 0..CHUNK_HEIGHT {
-                    let block_type = if y < 60 {
-                        // Water level
-                        if y < height {
-                            if y < height - 4 {
-                                BlockType::Stone
-                            } else if y < height - 1 {
-                                BlockType::Dirt
-                            } else {
-                                BlockType::Grass
-                            }
+                    let block_type = if y < height {
+                        if y + dirt_depth + 1 < height {
+                            BlockType::Stone
+                        } else if y + 1 < height {
+                            surface.filler
                         } else {
-                            BlockType::Water
+                            surface.top
                         }
+                    } else if y < column.water_level {
+                        BlockType::Water
                     } else {
-                        // Above water
-                        if y < height {
-                            if y < height - 4 {
-                                BlockType::Stone
-                            } else if y < height - 1 {
-                                BlockType::Dirt
-                            } else {
-                                BlockType::Grass
-                            }
-                        } else {
-                            BlockType::Air
-                        }
+                        BlockType::Air
                     };
 
                     chunk.set_block(x, y, z, block_type);
                 }
 
-                // Add trees
-                if height > 62 && self.should_place_tree(world_x, world_z) {
-                    self.add_tree(&mut chunk, x, height, z);
+                if height < column.water_level {
+                    if let Some(ice) = surface.ice {
+                        chunk.set_block(x, column.water_level - 1, z, ice);
+                    }
+                } else if let Some(cover) = surface.cover
+                    && height < CHUNK_HEIGHT
+                {
+                    chunk.set_block(x, height, z, cover);
                 }
             }
         }
 
+        if self.config.trees.enabled || self.config.ground_cover.enabled {
+            self.place_vegetation(&mut chunk, &columns);
+        }
+
+        if self.config.structures.enabled {
+            self.place_structures(&mut chunk);
+        }
+
         chunk
     }
 
-    fn get_height(&self, x: i32, y: i32) -> usize {
-        let scale = 0.01;
-        let x_scaled = x as f64 * scale;
-        let y_scaled = y as f64 * scale;
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let column = self.get_column(x, z);
+        self.column_biome(x, z, &column)
+    }
+
+    fn column_biome(&self, x: i32, z: i32, column: &Column) -> Biome {
+        let (temperature, humidity) = self.climate(x, z);
+        Biome::classify(
+            temperature,
+            humidity,
+            column.height - 1,
+            self.config.sea_level,
+        )
+    }
 
-        // Multiple octaves for varied terrain
-        let noise1 = self.noise.get([x_scaled, y_scaled]) * 30.0;
-        let noise2 = self.noise.get([x_scaled * 2.0, y_scaled * 2.0]) * 15.0;
-        let noise3 = self.noise.get([x_scaled * 4.0, y_scaled * 4.0]) * 7.0;
+    // Raw temperature and humidity noise
+    fn climate(&self, x: i32, z: i32) -> (f64, f64) {
+        let biomes = &self.config.biomes;
+        let temperature = self.temperature_noise.get([
+            x as f64 * biomes.temperature_scale,
+            z as f64 * biomes.temperature_scale,
+        ]);
+        let humidity = self.humidity_noise.get([
+            x as f64 * biomes.humidity_scale,
+            z as f64 * biomes.humidity_scale,
+        ]);
+        (temperature, humidity)
+    }
+
+    // Every column of a chunk with its final surface, indexed [z * CHUNK_SIZE + x]
+    fn shape_columns(&self, chunk_x: i32, chunk_z: i32) -> Vec<ShapedColumn> {
+        // One extra column on each side so slopes can be measured at the edges
+        let size = CHUNK_SIZE + 2;
+        let min_x = chunk_x * CHUNK_SIZE as i32 - 1;
+        let min_z = chunk_z * CHUNK_SIZE as i32 - 1;
+        let columns: Vec<Column> = (0..size * size)
+            .map(|i| self.get_column(min_x + (i % size) as i32, min_z + (i / size) as i32))
+            .collect();
+
+        let mut shaped = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+        for z in 1..=CHUNK_SIZE {
+            for x in 1..=CHUNK_SIZE {
+                let i = z * size + x;
+                let slope = [i - 1, i + 1, i - size, i + size]
+                    .iter()
+                    .map(|&n| columns[n].height.abs_diff(columns[i].height))
+                    .max()
+                    .unwrap();
+                shaped.push(self.shape_column(
+                    min_x + x as i32,
+                    min_z + z as i32,
+                    columns[i],
+                    slope,
+                ));
+            }
+        }
+        shaped
+    }
+
+    // A column outside the chunk being generated, measuring its own slope
+    fn shaped_column_at(&self, x: i32, z: i32) -> ShapedColumn {
+        let column = self.get_column(x, z);
+        let slope = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .iter()
+            .map(|(dx, dz)| {
+                self.get_column(x + dx, z + dz)
+                    .height
+                    .abs_diff(column.height)
+            })
+            .max()
+            .unwrap();
+        self.shape_column(x, z, column, slope)
+    }
+
+    fn shape_column(&self, x: i32, z: i32, column: Column, slope: usize) -> ShapedColumn {
+        let biome = self.column_biome(x, z, &column);
+        let (top, filler) = self.surface_blocks(x, z, &column, biome);
+        let mut surface = Surface {
+            top,
+            filler,
+            cover: None,
+            ice: None,
+        };
+
+        let sea_level = self.config.sea_level;
+        let altitude = (column.height - 1).saturating_sub(sea_level);
+        let (temperature, _) = self.climate(x, z);
+        let context = SurfaceContext {
+            height: column.height - 1,
+            slope,
+            temperature: temperature - altitude as f64 * self.config.biomes.altitude_cooling,
+            biome,
+            underwater: column.height < column.water_level,
+        };
+        surface_rules::apply_rules(&self.config.surface_rules, &context, &mut surface);
+
+        ShapedColumn {
+            column,
+            biome,
+            surface,
+        }
+    }
+
+    // Searches outward region by region and returns the closest structure of
+    // this kind, measured horizontally from pos
+    pub fn find_nearest_structure(
+        &self,
+        kind: StructureKind,
+        pos: (i32, i32, i32),
+    ) -> Option<StructureStart> {
+        if !self.config.structures.enabled || !self.placement(kind).enabled {
+            return None;
+        }
+
+        let spacing = self.placement(kind).spacing;
+        let region_x = pos.0.div_euclid(CHUNK_SIZE as i32).div_euclid(spacing);
+        let region_z = pos.2.div_euclid(CHUNK_SIZE as i32).div_euclid(spacing);
+        let mut nearest: Option<(i64, StructureStart)> = None;
+
+        for ring in 0..=STRUCTURE_SEARCH_RADIUS {
+            // Nothing in this ring can be closer than the gap to it
+            if let Some((best, _)) = &nearest {
+                let gap = ((ring - 1).max(0) * spacing * CHUNK_SIZE as i32) as i64;
+                if gap * gap > *best {
+                    break;
+                }
+            }
+
+            for rx in region_x - ring..=region_x + ring {
+                for rz in region_z - ring..=region_z + ring {
+                    if (rx - region_x).abs() != ring && (rz - region_z).abs() != ring {
+                        continue;
+                    }
+                    let Some(start) = self.structure_start(kind, rx, rz) else {
+                        continue;
+                    };
+                    let dx = (start.origin.0 - pos.0) as i64;
+                    let dz = (start.origin.2 - pos.2) as i64;
+                    let distance = dx * dx + dz * dz;
+                    if nearest.as_ref().is_none_or(|(best, _)| distance < *best) {
+                        nearest = Some((distance, start));
+                    }
+                }
+            }
+        }
+
+        nearest.map(|(_, start)| start)
+    }
+
+    fn placement(&self, kind: StructureKind) -> &PlacementConfig {
+        let structures = &self.config.structures;
+        match kind {
+            StructureKind::Village => &structures.village,
+            StructureKind::Dungeon => &structures.dungeon,
+            StructureKind::Ruins => &structures.ruins,
+        }
+    }
 
-        let height = 70.0 + noise1 + noise2 + noise3;
+    fn place_structures(&self, chunk: &mut Chunk) {
+        for kind in StructureKind::ALL {
+            let placement = self.placement(kind);
+            if !placement.enabled {
+                continue;
+            }
+
+            // Every region whose structure could reach into this chunk
+            let spacing = placement.spacing;
+            let min_rx = (chunk.x - STRUCTURE_REACH).div_euclid(spacing);
+            let max_rx = (chunk.x + STRUCTURE_REACH).div_euclid(spacing);
+            let min_rz = (chunk.z - STRUCTURE_REACH).div_euclid(spacing);
+            let max_rz = (chunk.z + STRUCTURE_REACH).div_euclid(spacing);
+
+            for rx in min_rx..=max_rx {
+                for rz in min_rz..=max_rz {
+                    let Some(start) = self.structure_start(kind, rx, rz) else {
+                        continue;
+                    };
+                    for piece in &start.pieces {
+                        let template = self.templates.get(piece.template);
+                        structure::place_piece(chunk, template, piece, |x, y, z| {
+                            let mut rng =
+                                ChunkRng::new(self.seed, x, z, SALT_STRUCTURE_DECAY + y as u32);
+                            rng.next_f64() >= piece.decay
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // The structure attempt for one region, or None if the spot is unsuitable
+    fn structure_start(
+        &self,
+        kind: StructureKind,
+        region_x: i32,
+        region_z: i32,
+    ) -> Option<StructureStart> {
+        let placement = self.placement(kind);
+        let mut rng = ChunkRng::new(self.seed, region_x, region_z, placement.salt);
+        let range = (placement.spacing - placement.separation) as f64;
+        let chunk_x = region_x * placement.spacing + (rng.next_f64() * range) as i32;
+        let chunk_z = region_z * placement.spacing + (rng.next_f64() * range) as i32;
+
+        let x = chunk_x * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+        let z = chunk_z * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+        let column = self.get_column(x, z);
+        if !kind.allows_biome(self.column_biome(x, z, &column)) {
+            return None;
+        }
+
+        let pieces = match kind {
+            StructureKind::Village => self.plan_village(x, z, &mut rng)?,
+            StructureKind::Dungeon => self.plan_dungeon(x, z, &column)?,
+            StructureKind::Ruins => self.plan_ruins(x, z)?,
+        };
+
+        Some(StructureStart {
+            kind,
+            origin: (x, column.height as i32, z),
+            pieces,
+        })
+    }
+
+    // A well in the middle with houses scattered in a ring around it
+    fn plan_village(&self, x: i32, z: i32, rng: &mut ChunkRng) -> Option<Vec<Piece>> {
+        let well = self.surface_piece(TemplateId::Well, x, z, 0, 0.0)?;
+        let mut pieces = vec![well];
+
+        let house_count = 3 + rng.below(3);
+        for i in 0..house_count {
+            let jitter = rng.next_f64() - 0.5;
+            let turns = (i as f64 + jitter * 0.5) / house_count as f64;
+            let step = (turns * angle::TURN as f64).round() as i32;
+            let distance = 11.0 + rng.next_f64() * 4.0;
+            let hx = x + (angle::cos(step) * distance).round() as i32;
+            let hz = z + (angle::sin(step) * distance).round() as i32;
+            if let Some(house) = self.surface_piece(TemplateId::House, hx, hz, 0, 0.0) {
+                pieces.push(house);
+            }
+        }
+
+        // A lone well isn't much of a village
+        if pieces.len() < 3 {
+            return None;
+        }
+        Some(pieces)
+    }
+
+    fn plan_dungeon(&self, x: i32, z: i32, column: &Column) -> Option<Vec<Piece>> {
+        let (sx, sy, sz) = self.templates.get(TemplateId::Dungeon).size;
+        let y = column.height as i32 - 20;
+        if y < 5 {
+            return None;
+        }
+
+        // Keep the whole room buried under at least a few blocks of rock
+        let min_x = x - sx as i32 / 2;
+        let min_z = z - sz as i32 / 2;
+        let (lowest, _) = self.height_range(min_x, min_z, sx as i32, sz as i32);
+        if y + sy as i32 + 4 > lowest as i32 {
+            return None;
+        }
+
+        Some(vec![Piece {
+            template: TemplateId::Dungeon,
+            x: min_x,
+            y,
+            z: min_z,
+            decay: 0.0,
+        }])
+    }
+
+    fn plan_ruins(&self, x: i32, z: i32) -> Option<Vec<Piece>> {
+        // Sunk one block into the ground and partly crumbled
+        let ruin = self.surface_piece(TemplateId::Ruin, x, z, -1, 0.35)?;
+        Some(vec![ruin])
+    }
+
+    // A piece centred on (x, z) with its floor replacing the surface block.
+    // Fails on water or on ground too uneven for the footprint.
+    fn surface_piece(
+        &self,
+        template: TemplateId,
+        x: i32,
+        z: i32,
+        sink: i32,
+        decay: f64,
+    ) -> Option<Piece> {
+        let (sx, _, sz) = self.templates.get(template).size;
+        let min_x = x - sx as i32 / 2;
+        let min_z = z - sz as i32 / 2;
+
+        let column = self.get_column(x, z);
+        if column.height < column.water_level {
+            return None;
+        }
+        let (lowest, highest) = self.height_range(min_x, min_z, sx as i32, sz as i32);
+        if highest - lowest > 3 || lowest < column.water_level {
+            return None;
+        }
+
+        Some(Piece {
+            template,
+            x: min_x,
+            y: column.height as i32 - 1 + sink,
+            z: min_z,
+            decay,
+        })
+    }
+
+    // Lowest and highest terrain height at the corners and centre of an area
+    fn height_range(&self, min_x: i32, min_z: i32, size_x: i32, size_z: i32) -> (usize, usize) {
+        let samples = [
+            (min_x, min_z),
+            (min_x + size_x - 1, min_z),
+            (min_x, min_z + size_z - 1),
+            (min_x + size_x - 1, min_z + size_z - 1),
+            (min_x + size_x / 2, min_z + size_z / 2),
+        ];
+        let mut lowest = usize::MAX;
+        let mut highest = 0;
+        for (x, z) in samples {
+            let height = self.get_column(x, z).height;
+            lowest = lowest.min(height);
+            highest = highest.max(height);
+        }
+        (lowest, highest)
+    }
+
+    fn get_column(&self, x: i32, z: i32) -> Column {
+        let base_height = self.get_height(x, z);
+        let mut column = Column {
+            height: base_height,
+            water_level: self.config.sea_level,
+            water_bed: false,
+        };
+
+        let lake = if self.config.lakes.enabled {
+            self.lake_in_cell(x, z)
+                .map(|lake| (self.lake_distance(&lake, x, z), lake))
+        } else {
+            None
+        };
+        // Lakes and their banks are shaped by the lake alone: a river cutting
+        // through the rim would leave the lake's water standing in the air
+        let in_lake = lake
+            .as_ref()
+            .is_some_and(|(distance, _)| *distance < 1.0 + LAKE_BANK);
+        if self.config.rivers.enabled && !in_lake {
+            self.carve_river(x, z, base_height, &mut column);
+        }
+        if let Some((distance, lake)) = lake {
+            self.carve_lake(distance, &lake, &mut column);
+        }
+
+        column
+    }
+
+    fn get_height(&self, x: i32, z: i32) -> usize {
+        let mut height = self.noise_height(x, z);
+        if self.config.erosion.enabled {
+            height += self.erosion_delta(x, z);
+        }
         height.max(1.0).min(CHUNK_HEIGHT as f64 - 1.0) as usize
     }
 
-    fn should_place_tree(&self, x: i32, z: i32) -> bool {
-        let tree_noise = self.noise.get([x as f64 * 0.1, z as f64 * 0.1]);
-        tree_noise > 0.7
+    // Raw heightmap before erosion
+    fn noise_height(&self, x: i32, y: i32) -> f64 {
+        let noise_config = &self.config.noise;
+        let x_scaled = x as f64 * noise_config.scale;
+        let y_scaled = y as f64 * noise_config.scale;
+
+        // Multiple octaves for varied terrain
+        let mut height = noise_config.base_height;
+        for octave in &noise_config.octaves {
+            height += self
+                .noise
+                .get([x_scaled * octave.frequency, y_scaled * octave.frequency])
+                * octave.amplitude;
+        }
+
+        height
+    }
+
+    // Erosion regions sit on a grid region_size apart and each covers the
+    // cells of its four neighbours. Tent weights that fall to zero at a
+    // region's edge add up to one everywhere, so blending the four regions
+    // around a point is seamless.
+    fn erosion_delta(&self, x: i32, z: i32) -> f64 {
+        let size = self.config.erosion.region_size;
+        let grid_x = x.div_euclid(size);
+        let grid_z = z.div_euclid(size);
+        let mut keys = Vec::with_capacity(4);
+        let mut weights = Vec::with_capacity(4);
+
+        for region_x in grid_x..=grid_x + 1 {
+            for region_z in grid_z..=grid_z + 1 {
+                let weight_x = 1.0 - (x - region_x * size).abs() as f64 / size as f64;
+                let weight_z = 1.0 - (z - region_z * size).abs() as f64 / size as f64;
+                let weight = weight_x * weight_z;
+                if weight > 0.0 {
+                    keys.push((region_x, region_z));
+                    weights.push(weight);
+                }
+            }
+        }
+
+        let regions = self.erosion_cache.get_all(&keys, |(region_x, region_z)| {
+            self.erode_region(region_x, region_z)
+        });
+        regions
+            .iter()
+            .zip(weights)
+            .map(|(region, weight)| weight * region.delta_at(x, z) as f64)
+            .sum()
+    }
+
+    fn erode_region(&self, region_x: i32, region_z: i32) -> ErodedRegion {
+        let erosion = &self.config.erosion;
+        let reach = erosion.region_size + erosion.padding;
+        let min_x = region_x * erosion.region_size - reach;
+        let min_z = region_z * erosion.region_size - reach;
+        let size = (reach * 2 + 1) as usize;
+
+        let mut original = Vec::with_capacity(size * size);
+        for z in 0..size as i32 {
+            for x in 0..size as i32 {
+                original.push(self.noise_height(min_x + x, min_z + z) as f32);
+            }
+        }
+
+        let mut map = Heightmap::new(size, original.clone());
+        let mut rng = ChunkRng::new(self.seed, region_x, region_z, SALT_EROSION);
+        erosion::erode(&mut map, erosion, &mut rng);
+
+        let delta = map
+            .heights
+            .iter()
+            .zip(&original)
+            .map(|(eroded, original)| eroded - original)
+            .collect();
+        ErodedRegion {
+            min_x,
+            min_z,
+            size,
+            delta,
+        }
+    }
+
+    fn carve_river(&self, x: i32, z: i32, base_height: usize, column: &mut Column) {
+        let rivers = &self.config.rivers;
+        let sea_level = self.config.sea_level;
+
+        // Rivers shrink as the land rises and are gone at max_height
+        let altitude = base_height.saturating_sub(sea_level) as f64;
+        let strength = 1.0 - altitude / rivers.max_height as f64;
+        if strength <= 0.0 {
+            return;
+        }
+
+        let distance = self
+            .river_noise
+            .get([x as f64 * rivers.noise_scale, z as f64 * rivers.noise_scale])
+            .abs();
+        let width = rivers.width * strength;
+        let bank_width = rivers.bank_width * strength;
+
+        let carved = if distance < width {
+            // Channel: deepest in the middle, reaching sea level at the edges
+            let depth = rivers.depth as f64 * (1.0 - distance / width);
+            column.water_bed = true;
+            sea_level - depth.round() as usize
+        } else if distance < bank_width {
+            // Banks: slope from just above the water back up to the terrain
+            let t = smoothstep((distance - width) / (bank_width - width));
+            let bank = (sea_level + 1) as f64;
+            (bank + (base_height as f64 - bank) * t).round() as usize
+        } else {
+            return;
+        };
+
+        column.height = column.height.min(carved);
+    }
+
+    // Distance from the lake's centre, relative to its wobbly shoreline:
+    // below 1 is under water
+    fn lake_distance(&self, lake: &Lake, x: i32, z: i32) -> f64 {
+        let dx = x as f64 + 0.5 - lake.center_x;
+        let dz = z as f64 + 0.5 - lake.center_z;
+        let length = (dx * dx + dz * dz).sqrt();
+        if length == 0.0 {
+            return 0.0;
+        }
+        let radius = lake.radius * self.lake_wobble(lake, dx / length, dz / length);
+        length / radius
+    }
+
+    fn carve_lake(&self, d: f64, lake: &Lake, column: &mut Column) {
+        if d < 1.0 {
+            // Bowl-shaped basin below the water surface
+            let depth = 1.0 + self.config.lakes.max_depth as f64 * (1.0 - d * d);
+            let floor = lake.surface.saturating_sub(depth.round() as usize).max(1);
+            column.height = column.height.min(floor);
+            column.water_level = column.water_level.max(lake.surface);
+            column.water_bed = true;
+        } else if d < 1.0 + LAKE_BANK {
+            // Banks slope from the shoreline back up to the natural terrain.
+            // Ground lower than the water, which the rim samples can miss, is
+            // raised to hold it in.
+            let t = smoothstep((d - 1.0) / LAKE_BANK);
+            let shore = lake.surface as f64;
+            let natural = column.height.max(lake.surface) as f64;
+            column.height = (shore + (natural - shore) * t).round() as usize;
+        }
     }
 
-    fn add_tree(&self, chunk: &mut Chunk, x: usize, base_y: usize, z: usize) {
-        let trunk_height = 5;
+    // Every lake lives entirely inside one grid cell, so a column only ever
+    // has to look at the lake of its own cell
+    fn lake_in_cell(&self, x: i32, z: i32) -> Option<Lake> {
+        let lakes = &self.config.lakes;
+        let cell = (x.div_euclid(lakes.cell_size), z.div_euclid(lakes.cell_size));
+
+        let mut rng = ChunkRng::new(self.seed, cell.0, cell.1, SALT_LAKE);
+        if !rng.chance(lakes.chance) {
+            return None;
+        }
+
+        let radius = lakes.min_radius + (lakes.max_radius - lakes.min_radius) * rng.next_f64();
+        let margin = lakes.max_radius * 1.25 * (1.0 + LAKE_BANK);
+        let span = lakes.cell_size as f64 - 2.0 * margin;
+        let center_x = (cell.0 * lakes.cell_size) as f64 + margin + span * rng.next_f64();
+        let center_z = (cell.1 * lakes.cell_size) as f64 + margin + span * rng.next_f64();
 
-        // Trunk
-        for y in 0..trunk_height {
-            if base_y + y < CHUNK_HEIGHT {
-                chunk.set_block(x, base_y + y, z, BlockType::OakLog);
+        let mut lake = Lake {
+            center_x,
+            center_z,
+            radius,
+            surface: 0,
+            cell,
+        };
+
+        // The water surface sits at the lowest point of the rim so the lake
+        // never spills over its own edge
+        let mut surface = usize::MAX;
+        for i in 0..LAKE_RIM_SAMPLES {
+            let step = i as i32 * angle::TURN / LAKE_RIM_SAMPLES as i32;
+            let (cos, sin) = (angle::cos(step), angle::sin(step));
+            let rim = radius * self.lake_wobble(&lake, cos, sin);
+            let rim_x = (center_x + cos * rim).floor() as i32;
+            let rim_z = (center_z + sin * rim).floor() as i32;
+            surface = surface.min(self.get_height(rim_x, rim_z));
+        }
+
+        // Lakes at or near sea level would just merge into the ocean
+        if surface <= self.config.sea_level + 1 {
+            return None;
+        }
+
+        lake.surface = surface;
+        Some(lake)
+    }
+
+    // Irregular shoreline: scales the lake radius by 0.75..1.25 around the
+    // circle. Takes the direction as cosine and sine rather than an angle.
+    fn lake_wobble(&self, lake: &Lake, cos: f64, sin: f64) -> f64 {
+        let n = self.detail_noise.get([
+            cos * 1.5,
+            sin * 1.5,
+            lake.cell.0 as f64 * 7.31 + lake.cell.1 as f64 * 3.17,
+        ]);
+        1.0 + n.clamp(-1.0, 1.0) * 0.25
+    }
+
+    // Returns the (surface, subsurface) blocks for a column
+    fn surface_blocks(
+        &self,
+        x: i32,
+        z: i32,
+        column: &Column,
+        biome: Biome,
+    ) -> (BlockType, BlockType) {
+        let beaches = &self.config.beaches;
+        let sea_level = self.config.sea_level;
+        let top = column.height - 1;
+        let near_sea = top + beaches.depth >= sea_level && top <= sea_level + beaches.height;
+
+        if beaches.enabled && (column.water_bed || near_sea) {
+            let n = self.detail_noise.get([
+                x as f64 * beaches.noise_scale,
+                z as f64 * beaches.noise_scale,
+            ]);
+            if n > beaches.gravel_threshold {
+                (BlockType::Gravel, BlockType::Gravel)
+            } else {
+                (BlockType::Sand, BlockType::Sand)
             }
+        } else if biome == Biome::Desert {
+            (BlockType::Sand, BlockType::Sand)
+        } else {
+            (BlockType::Grass, BlockType::Dirt)
         }
+    }
+
+    // Trees and ground cover. Trees rooted in neighbouring chunks are grown
+    // as well and clipped to this chunk, so crowns continue across borders.
+    fn place_vegetation(&self, chunk: &mut Chunk, columns: &[ShapedColumn]) {
+        let trees = &self.config.trees;
+        let cover = &self.config.ground_cover;
+        let min_x = chunk.x * CHUNK_SIZE as i32;
+        let min_z = chunk.z * CHUNK_SIZE as i32;
+        let reach = if trees.enabled { MAX_TREE_REACH } else { 0 };
 
-        // Leaves
-        let leaf_y = base_y + trunk_height;
-        for dx in -2..=2_i32 {
-            for dz in -2..=2_i32 {
-                for dy in 0..=2 {
-                    if dx.abs() + dz.abs() + dy <= 3 {
-                        let lx = x as i32 + dx;
-                        let lz = z as i32 + dz;
-                        let ly = leaf_y + dy as usize;
-
-                        if lx >= 0
-                            && lx < CHUNK_SIZE as i32
-                            && lz >= 0
-                            && lz < CHUNK_SIZE as i32
-                            && ly < CHUNK_HEIGHT
-                        {
-                            chunk.set_block(lx as usize, ly, lz as usize, BlockType::Leaves);
+        for x in min_x - reach..min_x + CHUNK_SIZE as i32 + reach {
+            for z in min_z - reach..min_z + CHUNK_SIZE as i32 + reach {
+                let in_chunk = x >= min_x
+                    && x < min_x + CHUNK_SIZE as i32
+                    && z >= min_z
+                    && z < min_z + CHUNK_SIZE as i32;
+                let mut rng = ChunkRng::new(self.seed, x, z, SALT_VEGETATION);
+                let tree_roll = rng.next_f64();
+
+                // Most columns outside the chunk can be skipped before doing
+                // any terrain work
+                if !in_chunk && tree_roll >= MAX_TREE_CHANCE * trees.density {
+                    continue;
+                }
+
+                let outside;
+                let shaped = if in_chunk {
+                    &columns[(z - min_z) as usize * CHUNK_SIZE + (x - min_x) as usize]
+                } else {
+                    outside = self.shaped_column_at(x, z);
+                    &outside
+                };
+                let ShapedColumn {
+                    column,
+                    biome,
+                    surface,
+                } = shaped;
+                if column.height < column.water_level {
+                    continue;
+                }
+                let surface = surface.top;
+                let table = VegetationTable::for_biome(*biome);
+
+                if trees.enabled
+                    && column.height > trees.min_height
+                    && tree_roll < table.tree_chance * trees.density
+                    && let Some(species) = pick_weighted(table.trees, &mut rng)
+                    && species.grows_on(surface)
+                {
+                    let base = (x, column.height as i32, z);
+                    for (bx, by, bz, block_type) in grow_tree(species, base, trees, &mut rng) {
+                        place_tree_block(chunk, bx, by, bz, block_type);
+                    }
+                    continue;
+                }
+
+                if in_chunk
+                    && cover.enabled
+                    && surface == BlockType::Grass
+                    && rng.chance(table.cover_chance * cover.density)
+                {
+                    let (lx, lz) = ((x - min_x) as usize, (z - min_z) as usize);
+                    let free = chunk
+                        .get_block(lx, column.height, lz)
+                        .is_some_and(|block| block.block_type == BlockType::Air);
+                    if let Some(plant) = pick_weighted(table.cover, &mut rng)
+                        && free
+                    {
+                        chunk.set_block(lx, column.height, lz, plant);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerator for TerrainGenerator {
+    fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        TerrainGenerator::generate_chunk(self, chunk_x, chunk_z)
+    }
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator() -> TerrainGenerator {
+        TerrainGenerator::new(12345, TerrainConfig::default())
+    }
+
+    // Where a river would run without the lakes in the way
+    fn river_would_carve(generator: &TerrainGenerator, x: i32, z: i32) -> bool {
+        let base_height = generator.get_height(x, z);
+        let mut column = Column {
+            height: base_height,
+            water_level: 0,
+            water_bed: false,
+        };
+        generator.carve_river(x, z, base_height, &mut column);
+        column.water_bed
+    }
+
+    #[test]
+    fn rivers_cut_water_filled_channels() {
+        let generator = generator();
+        let sea_level = generator.config.sea_level;
+        let channel = (0..4000)
+            .map(|x| (x, generator.get_column(x, 0)))
+            .find(|(x, column)| column.water_bed && river_would_carve(&generator, *x, 0));
+        let (x, column) = channel.expect("no river crosses the x axis");
+        assert!(column.height < sea_level);
+        assert_eq!(column.water_level, sea_level);
+        let (top, _) = generator.surface_blocks(x, 0, &column, Biome::Plains);
+        assert!(matches!(top, BlockType::Sand | BlockType::Gravel));
+    }
+
+    // Every lake column's water is held in by its neighbours, also where a
+    // river runs into the lake
+    #[test]
+    fn lakes_hold_their_water() {
+        let generator = generator();
+        let size = generator.config.lakes.cell_size;
+        let mut lakes = 0;
+        let mut crossed_by_rivers = 0;
+        for cell_x in -3..3 {
+            for cell_z in -3..3 {
+                let Some(lake) = generator.lake_in_cell(cell_x * size, cell_z * size) else {
+                    continue;
+                };
+                lakes += 1;
+                let reach = (lake.radius * 1.25 * (1.0 + LAKE_BANK)).ceil() as i32 + 1;
+                let (center_x, center_z) = (lake.center_x as i32, lake.center_z as i32);
+                let mut crossed = false;
+                for x in center_x - reach..=center_x + reach {
+                    for z in center_z - reach..=center_z + reach {
+                        crossed |= river_would_carve(&generator, x, z);
+                        let column = generator.get_column(x, z);
+                        if column.water_level != lake.surface || column.height >= lake.surface {
+                            continue;
+                        }
+                        for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                            let neighbour = generator.get_column(x + dx, z + dz);
+                            assert!(
+                                neighbour.height >= lake.surface
+                                    || neighbour.water_level >= lake.surface,
+                                "lake water at ({}, {}) spills towards ({}, {})",
+                                x,
+                                z,
+                                x + dx,
+                                z + dz
+                            );
                         }
                     }
                 }
+                crossed_by_rivers += crossed as usize;
             }
         }
+        assert!(lakes > 0 && crossed_by_rivers > 0);
+    }
+
+    #[test]
+    fn lakes_sit_above_the_sea_inside_their_cell() {
+        let generator = generator();
+        let lakes = &generator.config.lakes;
+        for cell_x in -5..5 {
+            for cell_z in -5..5 {
+                let (x, z) = (cell_x * lakes.cell_size, cell_z * lakes.cell_size);
+                let Some(lake) = generator.lake_in_cell(x, z) else {
+                    continue;
+                };
+                assert!(lake.surface > generator.config.sea_level + 1);
+                let extent = lake.radius * 1.25 * (1.0 + LAKE_BANK);
+                for center in [lake.center_x - x as f64, lake.center_z - z as f64] {
+                    assert!(center - extent >= 0.0);
+                    assert!(center + extent <= lakes.cell_size as f64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shores_and_beds_get_sand_or_gravel() {
+        let mut generator = generator();
+        let sea_level = generator.config.sea_level;
+        let shore = Column {
+            height: sea_level + 1,
+            water_level: sea_level,
+            water_bed: false,
+        };
+        let inland = Column {
+            height: sea_level + 20,
+            ..shore
+        };
+        let bed = Column {
+            water_bed: true,
+            ..inland
+        };
+
+        let shore_tops: Vec<BlockType> = (0..200)
+            .map(|x| generator.surface_blocks(x * 7, 0, &shore, Biome::Plains).0)
+            .collect();
+        assert!(shore_tops.contains(&BlockType::Sand));
+        assert!(shore_tops.contains(&BlockType::Gravel));
+        assert!(
+            shore_tops
+                .iter()
+                .all(|top| matches!(top, BlockType::Sand | BlockType::Gravel))
+        );
+        let (bed_top, _) = generator.surface_blocks(0, 0, &bed, Biome::Plains);
+        assert!(matches!(bed_top, BlockType::Sand | BlockType::Gravel));
+        assert_eq!(
+            generator.surface_blocks(0, 0, &inland, Biome::Plains),
+            (BlockType::Grass, BlockType::Dirt)
+        );
+
+        generator.config.beaches.enabled = false;
+        assert_eq!(
+            generator.surface_blocks(0, 0, &shore, Biome::Plains),
+            (BlockType::Grass, BlockType::Dirt)
+        );
+    }
+
+    fn horizontal_distance(start: &StructureStart, pos: (i32, i32, i32)) -> i64 {
+        let dx = (start.origin.0 - pos.0) as i64;
+        let dz = (start.origin.2 - pos.2) as i64;
+        dx * dx + dz * dz
+    }
+
+    // The ring search stops early once no farther ring can win, so check it
+    // against every region around the start
+    #[test]
+    fn nearest_structure_is_the_closest_one() {
+        let generator = generator();
+        let kind = StructureKind::Dungeon;
+        let spacing = generator.placement(kind).spacing;
+        for pos in [(0, 64, 0), (1000, 64, -700), (-2500, 64, 300)] {
+            let found = generator
+                .find_nearest_structure(kind, pos)
+                .expect("no dungeon near the start");
+            let region_x = pos.0.div_euclid(CHUNK_SIZE as i32).div_euclid(spacing);
+            let region_z = pos.2.div_euclid(CHUNK_SIZE as i32).div_euclid(spacing);
+            let closest = (region_x - 6..=region_x + 6)
+                .flat_map(|rx| (region_z - 6..=region_z + 6).map(move |rz| (rx, rz)))
+                .filter_map(|(rx, rz)| generator.structure_start(kind, rx, rz))
+                .map(|start| horizontal_distance(&start, pos))
+                .min()
+                .unwrap();
+            assert_eq!(horizontal_distance(&found, pos), closest);
+        }
+    }
+
+    #[test]
+    fn structure_search_gives_up_at_its_radius() {
+        let mut generator = generator();
+        generator.config.structures.ruins.enabled = false;
+        assert!(
+            generator
+                .find_nearest_structure(StructureKind::Ruins, (0, 64, 0))
+                .is_none()
+        );
+
+        // With the sea above every column there is no land for ruins, and
+        // the search ends after its last ring instead of going on forever
+        let config = TerrainConfig {
+            sea_level: CHUNK_HEIGHT - 1,
+            ..TerrainConfig::default()
+        };
+        let flooded = TerrainGenerator::new(12345, config);
+        assert!(
+            flooded
+                .find_nearest_structure(StructureKind::Ruins, (0, 64, 0))
+                .is_none()
+        );
+    }
+
+    fn eroding_generator() -> TerrainGenerator {
+        // Light erosion keeps the tests quick
+        let mut config = TerrainConfig::default();
+        config.erosion.enabled = true;
+        config.erosion.droplets_per_block = 0.2;
+        config.erosion.thermal_iterations = 2;
+        TerrainGenerator::new(12345, config)
+    }
+
+    // Neighbouring columns on either side of a region edge differ no more
+    // than neighbours anywhere else
+    #[test]
+    fn erosion_blends_across_region_edges() {
+        let generator = eroding_generator();
+        let size = generator.config.erosion.region_size;
+        let deltas: Vec<f64> = (-2 * size..=2 * size)
+            .map(|x| generator.erosion_delta(x, 37))
+            .collect();
+        assert!(deltas.iter().any(|delta| delta.abs() > 0.1));
+
+        let mut largest_inside = 0.0_f64;
+        let mut largest_across = 0.0_f64;
+        for (i, pair) in deltas.windows(2).enumerate() {
+            let x = -2 * size + i as i32;
+            let step = (pair[1] - pair[0]).abs();
+            if (x + 1).rem_euclid(size) == 0 || x.rem_euclid(size) == 0 {
+                largest_across = largest_across.max(step);
+            } else {
+                largest_inside = largest_inside.max(step);
+            }
+        }
+        assert!(
+            largest_across <= largest_inside,
+            "{} > {}",
+            largest_across,
+            largest_inside
+        );
+    }
+
+    // Heights don't depend on which regions happen to be cached
+    #[test]
+    fn erosion_is_the_same_in_any_order() {
+        let generator = eroding_generator();
+        let mut forgetful = eroding_generator();
+        forgetful.erosion_cache = ErosionCache::new(2);
+        let points: Vec<(i32, i32)> = (0..40).map(|i| (i * 5 - 100, 50 - i * 3)).collect();
+
+        let forward: Vec<usize> = points
+            .iter()
+            .map(|&(x, z)| generator.get_height(x, z))
+            .collect();
+        let mut backward: Vec<usize> = points
+            .iter()
+            .rev()
+            .map(|&(x, z)| forgetful.get_height(x, z))
+            .collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
     }
 }
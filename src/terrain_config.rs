@@ -0,0 +1,872 @@
+use crate::block::{BlockType, LEAF_DISTANCE};
+use crate::chunk::CHUNK_HEIGHT;
+use crate::surface_rules::{Action, Condition, SurfaceRule};
+use crate::vegetation::MAX_TREE_REACH;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Everything the terrain generator used to hardcode. Every section has
+// defaults, so a config file only needs to list the values it changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    pub noise: NoiseConfig,
+    pub layers: LayerConfig,
+    pub sea_level: usize,
+    pub trees: TreeConfig,
+    pub ground_cover: GroundCoverConfig,
+    pub rivers: RiverConfig,
+    pub lakes: LakeConfig,
+    pub beaches: BeachConfig,
+    pub biomes: BiomeConfig,
+    pub structures: StructureConfig,
+    pub erosion: ErosionConfig,
+    pub surface_rules: Vec<SurfaceRule>,
+    pub sky_islands: SkyIslandConfig,
+    pub growth: GrowthConfig,
+}
+
+// Heightmap noise: height = base_height + sum(noise(pos * scale * frequency) * amplitude)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseConfig {
+    pub scale: f64,
+    pub base_height: f64,
+    pub octaves: Vec<Octave>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Octave {
+    pub frequency: f64,
+    pub amplitude: f64,
+}
+
+// Column layout from the top: surface block, then dirt, then stone
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayerConfig {
+    pub dirt_depth: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeConfig {
+    pub enabled: bool,
+    // Multiplies the per-biome tree chance
+    pub density: f64,
+    // Trees only grow where the terrain height is above this
+    pub min_height: usize,
+    // Shape of the common oak
+    pub trunk_height: usize,
+    pub leaf_radius: i32,
+}
+
+// Slow changes in a played world, made on random ticks. Chances are per
+// random tick of the block.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrowthConfig {
+    pub grass: GrassGrowthConfig,
+    pub leaves: LeafDecayConfig,
+    pub saplings: SaplingConfig,
+    pub crops: CropConfig,
+}
+
+// Grass turns to dirt under opaque blocks and spreads to dirt open to the sky
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrassGrowthConfig {
+    pub enabled: bool,
+    // Nearby blocks tried for spreading each random tick
+    pub spread_attempts: u32,
+}
+
+// Leaves further than decay_distance from a log, counting steps through
+// other leaves, disappear
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeafDecayConfig {
+    pub enabled: bool,
+    pub decay_distance: i32,
+    // Chance a decaying leaf block drops a sapling
+    pub sapling_chance: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaplingConfig {
+    pub enabled: bool,
+    pub growth_chance: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CropConfig {
+    pub enabled: bool,
+    // Chance of growing one stage
+    pub growth_chance: f64,
+}
+
+// Tall grass and flowers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GroundCoverConfig {
+    pub enabled: bool,
+    // Multiplies the per-biome ground cover chance
+    pub density: f64,
+}
+
+// Rivers follow the zero line of a low-frequency noise. The channel is carved
+// down to sea level so every river drains into the ocean, and it narrows as
+// the terrain rises until it fades out in the mountains.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiverConfig {
+    pub enabled: bool,
+    pub noise_scale: f64,
+    // Half-widths in noise units: the water channel, then the sloped banks
+    pub width: f64,
+    pub bank_width: f64,
+    // Channel depth below sea level at the river centre
+    pub depth: usize,
+    // Height above sea level at which rivers have faded out completely
+    pub max_height: usize,
+}
+
+// Lakes are placed at most one per grid cell. The water surface sits at the
+// lowest point of the rim so the basin always holds its water.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LakeConfig {
+    pub enabled: bool,
+    pub cell_size: i32,
+    pub chance: f64,
+    pub min_radius: f64,
+    pub max_radius: f64,
+    pub max_depth: usize,
+}
+
+// Surfaces close to sea level, and river and lake beds, become sand or gravel
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeachConfig {
+    pub enabled: bool,
+    // Band around sea level, in blocks above and below it
+    pub height: usize,
+    pub depth: usize,
+    pub noise_scale: f64,
+    // Noise above this gives gravel instead of sand
+    pub gravel_threshold: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeConfig {
+    pub temperature_scale: f64,
+    pub humidity_scale: f64,
+    // Temperature drop per block above sea level, seen by the surface rules
+    pub altitude_cooling: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructureConfig {
+    pub enabled: bool,
+    pub village: PlacementConfig,
+    pub dungeon: PlacementConfig,
+    pub ruins: PlacementConfig,
+}
+
+// The world is split into regions of spacing x spacing chunks with at most
+// one structure attempt each. Separation keeps the attempt away from the far
+// edge of its region, so two structures are at least that many chunks apart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlacementConfig {
+    pub enabled: bool,
+    pub spacing: i32,
+    pub separation: i32,
+    // Mixed into the seed so kinds sharing a spacing don't line up
+    pub salt: u32,
+}
+
+// Optional erosion pass over the heightmap. Regions of 2 * region_size blocks
+// (plus padding) are simulated independently and cross-faded with their
+// neighbours, so the result has no seams and doesn't depend on chunk order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionConfig {
+    pub enabled: bool,
+    pub region_size: i32,
+    // Extra simulated border so region edges see water from outside
+    pub padding: i32,
+    // Hydraulic erosion
+    pub droplets_per_block: f64,
+    pub droplet_lifetime: usize,
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporate_speed: f32,
+    pub gravity: f32,
+    // Thermal erosion: slopes steeper than talus (height per block) crumble
+    pub thermal_iterations: usize,
+    pub talus: f32,
+    pub thermal_rate: f32,
+}
+
+// Floating islands of the sky dimension. Blocks are solid where 3D noise
+// beats the threshold, but only inside the height bands: the noise is faded
+// out towards the bottom and top of each band, more slowly below the widest
+// point so islands get flat tops and tapering undersides.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkyIslandConfig {
+    pub noise_scale: f64,
+    // Below 1 stretches islands horizontally
+    pub vertical_scale: f64,
+    pub threshold: f64,
+    pub bands: Vec<HeightBand>,
+    // Chances per grass column
+    pub tree_chance: f64,
+    pub cover_chance: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightBand {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            noise: NoiseConfig::default(),
+            layers: LayerConfig::default(),
+            sea_level: 60,
+            trees: TreeConfig::default(),
+            ground_cover: GroundCoverConfig::default(),
+            rivers: RiverConfig::default(),
+            lakes: LakeConfig::default(),
+            beaches: BeachConfig::default(),
+            biomes: BiomeConfig::default(),
+            structures: StructureConfig::default(),
+            erosion: ErosionConfig::default(),
+            surface_rules: default_surface_rules(),
+            sky_islands: SkyIslandConfig::default(),
+            growth: GrowthConfig::default(),
+        }
+    }
+}
+
+// Bare rock on cliffs, snow on peaks and in cold regions, ice on cold water
+fn default_surface_rules() -> Vec<SurfaceRule> {
+    vec![
+        SurfaceRule {
+            when: vec![Condition::MinSlope(3), Condition::Dry],
+            then: vec![
+                Action::Surface(BlockType::Stone),
+                Action::Filler(BlockType::Stone),
+            ],
+        },
+        SurfaceRule {
+            when: vec![
+                Condition::MinHeight(110),
+                Condition::MaxSlope(2),
+                Condition::Dry,
+            ],
+            then: vec![
+                Action::Surface(BlockType::Snow),
+                Action::Cover(BlockType::SnowLayer),
+            ],
+        },
+        SurfaceRule {
+            when: vec![
+                Condition::MaxTemperature(-0.5),
+                Condition::MaxSlope(2),
+                Condition::Dry,
+            ],
+            then: vec![Action::Cover(BlockType::SnowLayer)],
+        },
+        SurfaceRule {
+            when: vec![Condition::MaxTemperature(-0.4), Condition::Underwater],
+            then: vec![Action::Freeze(BlockType::Ice)],
+        },
+    ]
+}
+
+impl Default for SkyIslandConfig {
+    fn default() -> Self {
+        Self {
+            noise_scale: 0.015,
+            vertical_scale: 0.5,
+            threshold: 0.25,
+            bands: vec![
+                HeightBand { min: 70, max: 120 },
+                HeightBand { min: 150, max: 200 },
+            ],
+            tree_chance: 0.02,
+            cover_chance: 0.2,
+        }
+    }
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        Self {
+            scale: 0.01,
+            base_height: 70.0,
+            octaves: vec![
+                Octave {
+                    frequency: 1.0,
+                    amplitude: 30.0,
+                },
+                Octave {
+                    frequency: 2.0,
+                    amplitude: 15.0,
+                },
+                Octave {
+                    frequency: 4.0,
+                    amplitude: 7.0,
+                },
+            ],
+        }
+    }
+}
+
+impl Default for LayerConfig {
+    fn default() -> Self {
+        Self { dirt_depth: 3 }
+    }
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 1.0,
+            min_height: 62,
+            trunk_height: 5,
+            leaf_radius: 2,
+        }
+    }
+}
+
+impl Default for GrassGrowthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            spread_attempts: 4,
+        }
+    }
+}
+
+impl Default for LeafDecayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            decay_distance: 6,
+            sapling_chance: 0.05,
+        }
+    }
+}
+
+impl Default for SaplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            growth_chance: 0.1,
+        }
+    }
+}
+
+impl Default for CropConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            growth_chance: 0.1,
+        }
+    }
+}
+
+impl Default for GroundCoverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 1.0,
+        }
+    }
+}
+
+impl Default for RiverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            noise_scale: 0.003,
+            width: 0.03,
+            bank_width: 0.08,
+            depth: 4,
+            max_height: 30,
+        }
+    }
+}
+
+impl Default for LakeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cell_size: 96,
+            chance: 0.35,
+            min_radius: 6.0,
+            max_radius: 18.0,
+            max_depth: 5,
+        }
+    }
+}
+
+impl Default for BeachConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            height: 2,
+            depth: 3,
+            noise_scale: 0.05,
+            gravel_threshold: 0.4,
+        }
+    }
+}
+
+impl Default for BiomeConfig {
+    fn default() -> Self {
+        Self {
+            temperature_scale: 0.002,
+            humidity_scale: 0.003,
+            altitude_cooling: 0.01,
+        }
+    }
+}
+
+impl Default for StructureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            village: PlacementConfig {
+                enabled: true,
+                spacing: 24,
+                separation: 8,
+                salt: 10387312,
+            },
+            dungeon: PlacementConfig {
+                enabled: true,
+                spacing: 8,
+                separation: 3,
+                salt: 14357617,
+            },
+            ruins: PlacementConfig {
+                enabled: true,
+                spacing: 16,
+                separation: 6,
+                salt: 34222645,
+            },
+        }
+    }
+}
+
+impl Default for PlacementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            spacing: 16,
+            separation: 4,
+            salt: 0,
+        }
+    }
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            region_size: 64,
+            padding: 16,
+            droplets_per_block: 0.8,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.02,
+            gravity: 4.0,
+            thermal_iterations: 10,
+            talus: 1.5,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read terrain config: {}", err),
+            ConfigError::Parse(err) => write!(f, "failed to parse terrain config: {}", err),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid terrain config: {}", problems.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl TerrainConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let config: TerrainConfig = toml::from_str(&text).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Falls back to the defaults when the file is missing or broken, so a bad
+    // edit never stops the engine from starting
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Self::default();
+        }
+        match Self::load(path) {
+            Ok(config) => config,
+            Err(err) => {
+                println!("{} ({}), using defaults", err, path.display());
+                Self::default()
+            }
+        }
+    }
+
+    // Collects every problem instead of stopping at the first one
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let max_y = CHUNK_HEIGHT - 1;
+
+        if !is_positive(self.noise.scale) {
+            problems.push(format!(
+                "noise.scale must be positive, got {}",
+                self.noise.scale
+            ));
+        }
+        if self.noise.octaves.is_empty() {
+            problems.push("noise.octaves must contain at least one octave".to_string());
+        }
+        for (i, octave) in self.noise.octaves.iter().enumerate() {
+            if !is_positive(octave.frequency) {
+                problems.push(format!(
+                    "noise.octaves[{}].frequency must be positive, got {}",
+                    i, octave.frequency
+                ));
+            }
+            if !octave.amplitude.is_finite() {
+                problems.push(format!("noise.octaves[{}].amplitude must be finite", i));
+            }
+        }
+        if !(1.0..=max_y as f64).contains(&self.noise.base_height) {
+            problems.push(format!(
+                "noise.base_height must be within 1..={}, got {}",
+                max_y, self.noise.base_height
+            ));
+        }
+        if self.sea_level > max_y {
+            problems.push(format!(
+                "sea_level must be at most {}, got {}",
+                max_y, self.sea_level
+            ));
+        }
+        if !(1..=max_y).contains(&self.trees.trunk_height) {
+            problems.push(format!(
+                "trees.trunk_height must be within 1..={}, got {}",
+                max_y, self.trees.trunk_height
+            ));
+        }
+        // Chunks only look this far out for trees growing into them
+        if !(0..=MAX_TREE_REACH).contains(&self.trees.leaf_radius) {
+            problems.push(format!(
+                "trees.leaf_radius must be within 0..={}, got {}",
+                MAX_TREE_REACH, self.trees.leaf_radius
+            ));
+        }
+        if !is_non_negative(self.trees.density) || !is_non_negative(self.ground_cover.density) {
+            problems.push(format!(
+                "trees.density and ground_cover.density must not be negative, got {} and {}",
+                self.trees.density, self.ground_cover.density
+            ));
+        }
+        if !is_positive(self.rivers.noise_scale) {
+            problems.push(format!(
+                "rivers.noise_scale must be positive, got {}",
+                self.rivers.noise_scale
+            ));
+        }
+        if !is_positive(self.rivers.width) || self.rivers.bank_width <= self.rivers.width {
+            problems.push(format!(
+                "rivers.width must be positive and below rivers.bank_width, got {} and {}",
+                self.rivers.width, self.rivers.bank_width
+            ));
+        }
+        if self.rivers.depth >= self.sea_level {
+            problems.push(format!(
+                "rivers.depth must be below sea_level, got {}",
+                self.rivers.depth
+            ));
+        }
+        if self.rivers.max_height == 0 {
+            problems.push("rivers.max_height must be at least 1".to_string());
+        }
+        if !is_positive(self.lakes.min_radius) || self.lakes.max_radius < self.lakes.min_radius {
+            problems.push(format!(
+                "lakes radii must satisfy 0 < min_radius <= max_radius, got {} and {}",
+                self.lakes.min_radius, self.lakes.max_radius
+            ));
+        }
+        // The lake plus its banks must fit inside its cell so chunks never
+        // disagree about a lake in a neighbouring cell
+        if (self.lakes.cell_size as f64) < self.lakes.max_radius * 4.0 {
+            problems.push(format!(
+                "lakes.cell_size must be at least 4 * lakes.max_radius, got {}",
+                self.lakes.cell_size
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.lakes.chance) {
+            problems.push(format!(
+                "lakes.chance must be within 0..=1, got {}",
+                self.lakes.chance
+            ));
+        }
+        if !is_positive(self.beaches.noise_scale) {
+            problems.push(format!(
+                "beaches.noise_scale must be positive, got {}",
+                self.beaches.noise_scale
+            ));
+        }
+        if !is_positive(self.biomes.temperature_scale) || !is_positive(self.biomes.humidity_scale) {
+            problems.push("biomes scales must be positive".to_string());
+        }
+        if !is_non_negative(self.biomes.altitude_cooling) {
+            problems.push(format!(
+                "biomes.altitude_cooling must not be negative, got {}",
+                self.biomes.altitude_cooling
+            ));
+        }
+        let sky = &self.sky_islands;
+        if !is_positive(sky.noise_scale) || !is_positive(sky.vertical_scale) {
+            problems.push(format!(
+                "sky_islands.noise_scale and vertical_scale must be positive, got {} and {}",
+                sky.noise_scale, sky.vertical_scale
+            ));
+        }
+        for (i, band) in sky.bands.iter().enumerate() {
+            if band.min + 4 > band.max || band.max > max_y {
+                problems.push(format!(
+                    "sky_islands.bands[{}] must satisfy min + 4 <= max <= {}, got {}..{}",
+                    i, max_y, band.min, band.max
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&sky.tree_chance) || !(0.0..=1.0).contains(&sky.cover_chance) {
+            problems.push("sky_islands chances must be within 0..=1".to_string());
+        }
+        for (i, rule) in self.surface_rules.iter().enumerate() {
+            if rule.then.is_empty() {
+                problems.push(format!("surface_rules[{}] has no actions", i));
+            }
+            let covers_with_air = rule.then.iter().any(|action| {
+                matches!(
+                    action,
+                    Action::Surface(BlockType::Air) | Action::Filler(BlockType::Air)
+                )
+            });
+            if covers_with_air {
+                problems.push(format!(
+                    "surface_rules[{}] must not replace terrain with air",
+                    i
+                ));
+            }
+        }
+        for (name, placement) in [
+            ("village", &self.structures.village),
+            ("dungeon", &self.structures.dungeon),
+            ("ruins", &self.structures.ruins),
+        ] {
+            if placement.spacing <= 0 || placement.separation < 0 {
+                problems.push(format!(
+                    "structures.{}: spacing must be positive and separation not negative",
+                    name
+                ));
+            } else if placement.separation >= placement.spacing {
+                problems.push(format!(
+                    "structures.{}.separation must be below spacing, got {} and {}",
+                    name, placement.separation, placement.spacing
+                ));
+            }
+        }
+        let erosion = &self.erosion;
+        if erosion.region_size <= 0 || erosion.padding < 0 {
+            problems.push(format!(
+                "erosion.region_size must be positive and erosion.padding not negative, got {} and {}",
+                erosion.region_size, erosion.padding
+            ));
+        }
+        if !is_non_negative(erosion.droplets_per_block) {
+            problems.push(format!(
+                "erosion.droplets_per_block must not be negative, got {}",
+                erosion.droplets_per_block
+            ));
+        }
+        for (name, value) in [
+            ("inertia", erosion.inertia),
+            ("erode_speed", erosion.erode_speed),
+            ("deposit_speed", erosion.deposit_speed),
+            ("evaporate_speed", erosion.evaporate_speed),
+            ("thermal_rate", erosion.thermal_rate),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!(
+                    "erosion.{} must be within 0..=1, got {}",
+                    name, value
+                ));
+            }
+        }
+        if !is_non_negative(erosion.talus as f64) {
+            problems.push(format!(
+                "erosion.talus must not be negative, got {}",
+                erosion.talus
+            ));
+        }
+
+        let growth = &self.growth;
+        // Leaves store their distance up to one past decay_distance
+        let max_decay = LEAF_DISTANCE as i32 - 1;
+        if !(1..=max_decay).contains(&growth.leaves.decay_distance) {
+            problems.push(format!(
+                "growth.leaves.decay_distance must be within 1..={}, got {}",
+                max_decay, growth.leaves.decay_distance
+            ));
+        }
+        for (name, value) in [
+            ("leaves.sapling_chance", growth.leaves.sapling_chance),
+            ("saplings.growth_chance", growth.saplings.growth_chance),
+            ("crops.growth_chance", growth.crops.growth_chance),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!(
+                    "growth.{} must be within 0..=1, got {}",
+                    name, value
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn is_non_negative(value: f64) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Breakage = fn(&mut TerrainConfig);
+
+    fn problems(config: &TerrainConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid(problems)) => problems,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn defaults_and_the_shipped_config_are_valid() {
+        assert!(TerrainConfig::default().validate().is_ok());
+        let shipped = TerrainConfig::load(concat!(env!("CARGO_MANIFEST_DIR"), "/terrain.toml"));
+        assert!(shipped.is_ok(), "{}", shipped.unwrap_err());
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        // The field a message starts with, and how to break it
+        let cases: [(&str, Breakage); 8] = [
+            ("noise.scale", |config| config.noise.scale = 0.0),
+            ("noise.octaves", |config| config.noise.octaves.clear()),
+            ("sea_level", |config| config.sea_level = CHUNK_HEIGHT),
+            ("trees.trunk_height", |config| config.trees.trunk_height = 0),
+            ("trees.trunk_height", |config| {
+                config.trees.trunk_height = CHUNK_HEIGHT
+            }),
+            ("trees.leaf_radius", |config| {
+                config.trees.leaf_radius = MAX_TREE_REACH + 1
+            }),
+            ("lakes.chance", |config| config.lakes.chance = 1.5),
+            ("erosion.inertia", |config| {
+                config.erosion.inertia = f32::NAN
+            }),
+        ];
+        for (field, break_it) in cases {
+            let mut config = TerrainConfig::default();
+            break_it(&mut config);
+            let problems = problems(&config);
+            assert_eq!(problems.len(), 1, "{:?}", problems);
+            assert!(problems[0].starts_with(field), "{}", problems[0]);
+        }
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let mut config = TerrainConfig::default();
+        config.noise.base_height = -5.0;
+        config.trees.leaf_radius = -1;
+        config.rivers.width = config.rivers.bank_width;
+        config.growth.crops.growth_chance = 2.0;
+        let problems = problems(&config);
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        for field in [
+            "noise.base_height",
+            "trees.leaf_radius",
+            "rivers.width",
+            "growth.crops",
+        ] {
+            assert!(problems.iter().any(|problem| problem.starts_with(field)));
+        }
+
+        let message = ConfigError::Invalid(problems).to_string();
+        assert!(message.starts_with("invalid terrain config: noise.base_height"));
+        assert_eq!(message.matches("; ").count(), 3);
+    }
+
+    #[test]
+    fn parse_errors_are_not_validation_errors() {
+        let path = std::env::temp_dir().join(format!("terrain-{}.toml", std::process::id()));
+        fs::write(&path, "sea_level = \"high\"").unwrap();
+        let result = TerrainConfig::load(&path);
+        let _ = fs::remove_file(&path);
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+}
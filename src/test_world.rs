@@ -0,0 +1,101 @@
+// Small worlds and helpers shared by the tests of modules that need a world
+use crate::block::{Block, BlockType};
+use crate::chunk::{CHUNK_SIZE, Chunk};
+use crate::dimension::{ChunkGenerator, DimensionId};
+use crate::world::World;
+use glam::Vec3;
+use std::ops::{Range, RangeInclusive};
+
+pub const DT: f32 = 1.0 / 60.0;
+
+// Fills the bottom layer of every chunk with one block type. Air gives
+// empty chunks.
+pub struct FloorGenerator(pub BlockType);
+
+impl ChunkGenerator for FloorGenerator {
+    fn generate_chunk(&self, chunk_x: i32, chunk_z: i32) -> Chunk {
+        let mut chunk = Chunk::new(chunk_x, chunk_z);
+        if self.0 != BlockType::Air {
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set_block(x, 0, z, self.0);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+// Empty chunks over the given chunk coordinates, with no generator
+pub fn empty_world(chunks_x: RangeInclusive<i32>, chunks_z: RangeInclusive<i32>) -> World {
+    let mut world = World::new();
+    for cx in chunks_x {
+        for cz in chunks_z.clone() {
+            world.chunks.insert((cx, cz), Chunk::new(cx, cz));
+        }
+    }
+    world
+}
+
+// Empty overworld chunks loaded through a generator, so the world can load
+// more of them later
+pub fn generated_world(chunks: RangeInclusive<i32>) -> World {
+    let mut world = World::new();
+    world.add_dimension(
+        DimensionId::Overworld,
+        Box::new(FloorGenerator(BlockType::Air)),
+    );
+    for cx in chunks.clone() {
+        for cz in chunks.clone() {
+            world.load_chunk(cx, cz);
+        }
+    }
+    world
+}
+
+// Sets every block in a box to one block type
+pub fn fill_box(
+    world: &mut World,
+    block_type: BlockType,
+    xs: Range<i32>,
+    ys: Range<i32>,
+    zs: Range<i32>,
+) {
+    for x in xs {
+        for y in ys.clone() {
+            for z in zs.clone() {
+                world.set_block(x, y, z, Block::new(block_type));
+            }
+        }
+    }
+}
+
+// A floor of one block type with its top at y = 5
+pub fn lay_floor(world: &mut World, block_type: BlockType, xs: Range<i32>, zs: Range<i32>) {
+    fill_box(world, block_type, xs, 4..5, zs);
+}
+
+// Stone floor topped at y = 5 over four chunks around the origin
+pub fn flat_world() -> World {
+    let mut world = empty_world(-1..=0, -1..=0);
+    lay_floor(&mut world, BlockType::Stone, -16..16, -16..16);
+    world
+}
+
+pub fn block_at(world: &World, x: i32, y: i32, z: i32) -> BlockType {
+    world.get_block(x, y, z).unwrap().block_type
+}
+
+// Ticks the world with no player around
+pub fn run(world: &mut World, ticks: usize) {
+    for _ in 0..ticks {
+        world.tick(DT, None);
+    }
+}
+
+// Ticks the world with the player standing still at the given point
+pub fn run_with_player(world: &mut World, player: Vec3, ticks: usize) {
+    for _ in 0..ticks {
+        world.tick(DT, Some(player));
+    }
+}
@@ -6,6 +6,8 @@ pub struct TextureAtlas {
 }
 
 impl TextureAtlas {
+    // Needs a current GL context, so there is deliberately no Default
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         let mut texture_id = 0;
         let size = 256;
@@ -45,6 +47,65 @@ impl TextureAtlas {
         // Tile 7: Leaves (solid forest green)
         Self::fill_tile(&mut img, 7, 0, tile_size, Rgba([48, 168, 48, 255]));
 
+        // Tile 8: Sand (pale yellow)
+        Self::fill_tile(&mut img, 8, 0, tile_size, Rgba([219, 207, 163, 255]));
+
+        // Tile 9: Gravel (gray-brown)
+        Self::fill_tile(&mut img, 9, 0, tile_size, Rgba([136, 126, 126, 255]));
+
+        // Tile 10: Planks (warm tan)
+        Self::fill_tile(&mut img, 10, 0, tile_size, Rgba([176, 138, 86, 255]));
+
+        // Tile 11: Cobblestone (dark gray)
+        Self::fill_tile(&mut img, 11, 0, tile_size, Rgba([96, 96, 96, 255]));
+
+        // Tile 12: Birch Log Side (pale bark)
+        Self::fill_tile(&mut img, 12, 0, tile_size, Rgba([216, 215, 210, 255]));
+
+        // Tile 13: Birch Leaves (light green)
+        Self::fill_tile(&mut img, 13, 0, tile_size, Rgba([118, 160, 64, 255]));
+
+        // Tile 14: Spruce Log Side (dark brown)
+        Self::fill_tile(&mut img, 14, 0, tile_size, Rgba([74, 54, 34, 255]));
+
+        // Tile 15: Spruce Leaves (blue-green)
+        Self::fill_tile(&mut img, 15, 0, tile_size, Rgba([52, 92, 60, 255]));
+
+        // Tiles 16+ continue on the second row of the atlas
+
+        // Tile 16: Cactus (cactus green)
+        Self::fill_tile(&mut img, 0, 1, tile_size, Rgba([58, 130, 44, 255]));
+
+        // Tile 17: Tall Grass (grass green)
+        Self::fill_tile(&mut img, 1, 1, tile_size, Rgba([92, 160, 48, 255]));
+
+        // Tile 18: Dandelion (yellow)
+        Self::fill_tile(&mut img, 2, 1, tile_size, Rgba([240, 220, 40, 255]));
+
+        // Tile 19: Poppy (red)
+        Self::fill_tile(&mut img, 3, 1, tile_size, Rgba([200, 30, 30, 255]));
+
+        // Tile 20: Snow (white)
+        Self::fill_tile(&mut img, 4, 1, tile_size, Rgba([240, 244, 250, 255]));
+
+        // Tile 21: Ice (pale blue)
+        Self::fill_tile(&mut img, 5, 1, tile_size, Rgba([160, 196, 240, 255]));
+
+        // Tile 22: Oak Sapling (leaf green)
+        Self::fill_tile(&mut img, 6, 1, tile_size, Rgba([60, 140, 40, 255]));
+
+        // Tile 23: Birch Sapling (light green)
+        Self::fill_tile(&mut img, 7, 1, tile_size, Rgba([128, 170, 72, 255]));
+
+        // Tile 24: Spruce Sapling (blue-green)
+        Self::fill_tile(&mut img, 8, 1, tile_size, Rgba([46, 84, 54, 255]));
+
+        // Tile 25: Wheat (straw yellow)
+        Self::fill_tile(&mut img, 9, 1, tile_size, Rgba([200, 176, 70, 255]));
+
+        // Tile 26: Door (dark wood)
+        Self::fill_tile(&mut img, 10, 1, tile_size, Rgba([128, 92, 52, 255]));
+
         unsafe {
             gl::GenTextures(1, &mut texture_id);
             gl::BindTexture(gl::TEXTURE_2D, texture_id);
@@ -119,13 +180,49 @@ impl TextureAtlas {
                 } // Sides
             }
             crate::block::BlockType::Leaves => 7,
+            crate::block::BlockType::Sand => 8,
+            crate::block::BlockType::Gravel => 9,
+            crate::block::BlockType::Planks => 10,
+            crate::block::BlockType::Cobblestone => 11,
+            crate::block::BlockType::BirchLog => {
+                if face == 4 || face == 5 {
+                    6
+                }
+                // Top/bottom share the oak ring texture
+                else {
+                    12
+                }
+            }
+            crate::block::BlockType::BirchLeaves => 13,
+            crate::block::BlockType::SpruceLog => {
+                if face == 4 || face == 5 {
+                    6
+                }
+                // Top/bottom share the oak ring texture
+                else {
+                    14
+                }
+            }
+            crate::block::BlockType::SpruceLeaves => 15,
+            crate::block::BlockType::Cactus => 16,
+            crate::block::BlockType::TallGrass => 17,
+            crate::block::BlockType::Dandelion => 18,
+            crate::block::BlockType::Poppy => 19,
+            crate::block::BlockType::Snow | crate::block::BlockType::SnowLayer => 20,
+            crate::block::BlockType::Ice => 21,
+            crate::block::BlockType::OakSapling => 22,
+            crate::block::BlockType::BirchSapling => 23,
+            crate::block::BlockType::SpruceSapling => 24,
+            crate::block::BlockType::Wheat => 25,
+            crate::block::BlockType::Door => 26,
             _ => 0,
         };
 
-        let u_min = (tile_index as f32) * tile_size;
+        // 16 tiles per row
+        let u_min = ((tile_index % 16) as f32) * tile_size;
         let u_max = u_min + tile_size;
-        let v_min = 0.0;
-        let v_max = tile_size;
+        let v_min = ((tile_index / 16) as f32) * tile_size;
+        let v_max = v_min + tile_size;
 
         (u_min, v_min, u_max, v_max)
     }
@@ -0,0 +1,86 @@
+// Runs the simulation in ticks of a fixed length whatever the frame rate,
+// so movement and world updates behave the same at 30 and 3000 FPS. Frames
+// add their duration to an accumulator and run every whole tick it holds;
+// the remainder tells the renderer how far it is between two ticks.
+
+// Simulation ticks per second
+pub const TICK_RATE: f32 = 60.0;
+// Frames longer than this (a stall, a breakpoint, loading chunks) are cut
+// short, so the simulation doesn't try to catch up all at once
+const MAX_FRAME_TIME: f32 = 0.25;
+
+pub struct FixedTimestep {
+    tick_length: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: f32) -> Self {
+        assert!(ticks_per_second > 0.0, "tick rate must be positive");
+        Self {
+            tick_length: 1.0 / ticks_per_second,
+            accumulator: 0.0,
+        }
+    }
+
+    // Seconds of game time in one tick
+    pub fn tick_length(&self) -> f32 {
+        self.tick_length
+    }
+
+    // Adds a frame's duration and returns how many ticks to run for it
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.clamp(0.0, MAX_FRAME_TIME);
+        let ticks = (self.accumulator / self.tick_length) as u32;
+        self.accumulator -= ticks as f32 * self.tick_length;
+        ticks
+    }
+
+    // How far the frame is between the last tick and the next, from 0 to 1,
+    // for blending the state of those two ticks when rendering
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_length).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks_for(frame_rate: u32, seconds: u32) -> u32 {
+        let mut timestep = FixedTimestep::new(TICK_RATE);
+        (0..frame_rate * seconds)
+            .map(|_| timestep.advance(1.0 / frame_rate as f32))
+            .sum()
+    }
+
+    #[test]
+    fn tick_count_does_not_depend_on_frame_rate() {
+        let expected = TICK_RATE as u32 * 2;
+        for frame_rate in [30, 60, 144, 3000] {
+            let ticks = ticks_for(frame_rate, 2) as i64;
+            assert!(
+                (ticks - expected as i64).abs() <= 1,
+                "{} ticks at {} FPS",
+                ticks,
+                frame_rate
+            );
+        }
+    }
+
+    #[test]
+    fn remainder_is_carried_over() {
+        let mut timestep = FixedTimestep::new(20.0);
+        assert_eq!(timestep.advance(0.03), 0);
+        assert!((timestep.alpha() - 0.6).abs() < 1e-4);
+        assert_eq!(timestep.advance(0.03), 1);
+        assert!((timestep.alpha() - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(16.0);
+        assert_eq!(timestep.advance(10.0), 4);
+        assert_eq!(timestep.advance(-1.0), 0);
+    }
+}
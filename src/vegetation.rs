@@ -0,0 +1,498 @@
+use crate::angle;
+use crate::biome::Biome;
+use crate::block::{Block, BlockType};
+use crate::chunk::{CHUNK_SIZE, Chunk};
+use crate::rng::ChunkRng;
+use crate::terrain_config::TreeConfig;
+use glam::Vec3;
+
+// Furthest any tree reaches horizontally from its trunk, in blocks
+pub const MAX_TREE_REACH: i32 = 8;
+// Highest tree_chance of any biome table below
+pub const MAX_TREE_CHANCE: f64 = 0.04;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeSpecies {
+    Oak,
+    Birch,
+    Spruce,
+    // Large oak grown from branching rules
+    GreatOak,
+    Bush,
+    Cactus,
+}
+
+impl TreeSpecies {
+    pub fn grows_on(&self, surface: BlockType) -> bool {
+        match self {
+            TreeSpecies::Cactus => surface == BlockType::Sand,
+            _ => surface == BlockType::Grass,
+        }
+    }
+}
+
+// What grows in a biome. Chances are per column; entries are (thing, weight).
+pub struct VegetationTable {
+    pub tree_chance: f64,
+    pub trees: &'static [(TreeSpecies, u32)],
+    pub cover_chance: f64,
+    pub cover: &'static [(BlockType, u32)],
+}
+
+impl VegetationTable {
+    pub fn for_biome(biome: Biome) -> Self {
+        match biome {
+            Biome::Plains => Self {
+                tree_chance: 0.003,
+                trees: &[
+                    (TreeSpecies::Oak, 8),
+                    (TreeSpecies::Bush, 3),
+                    (TreeSpecies::GreatOak, 1),
+                ],
+                cover_chance: 0.25,
+                cover: &[
+                    (BlockType::TallGrass, 10),
+                    (BlockType::Dandelion, 1),
+                    (BlockType::Poppy, 1),
+                ],
+            },
+            Biome::Forest => Self {
+                tree_chance: 0.04,
+                trees: &[
+                    (TreeSpecies::Oak, 6),
+                    (TreeSpecies::Birch, 4),
+                    (TreeSpecies::Bush, 2),
+                    (TreeSpecies::GreatOak, 1),
+                ],
+                cover_chance: 0.15,
+                cover: &[
+                    (BlockType::TallGrass, 6),
+                    (BlockType::Dandelion, 1),
+                    (BlockType::Poppy, 1),
+                ],
+            },
+            Biome::Taiga => Self {
+                tree_chance: 0.03,
+                trees: &[(TreeSpecies::Spruce, 10), (TreeSpecies::Bush, 1)],
+                cover_chance: 0.08,
+                cover: &[(BlockType::TallGrass, 1)],
+            },
+            Biome::Tundra => Self {
+                tree_chance: 0.002,
+                trees: &[(TreeSpecies::Spruce, 1)],
+                cover_chance: 0.02,
+                cover: &[(BlockType::TallGrass, 1)],
+            },
+            Biome::Mountains => Self {
+                tree_chance: 0.005,
+                trees: &[(TreeSpecies::Spruce, 3), (TreeSpecies::Oak, 1)],
+                cover_chance: 0.05,
+                cover: &[(BlockType::TallGrass, 1)],
+            },
+            Biome::Desert => Self {
+                tree_chance: 0.004,
+                trees: &[(TreeSpecies::Cactus, 1)],
+                cover_chance: 0.0,
+                cover: &[],
+            },
+            Biome::Beach | Biome::Ocean => Self {
+                tree_chance: 0.0,
+                trees: &[],
+                cover_chance: 0.0,
+                cover: &[],
+            },
+        }
+    }
+}
+
+pub fn pick_weighted<T: Copy>(entries: &[(T, u32)], rng: &mut ChunkRng) -> Option<T> {
+    let total: u32 = entries.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rng.below(total);
+    for (entry, weight) in entries {
+        if roll < *weight {
+            return Some(*entry);
+        }
+        roll -= weight;
+    }
+    None
+}
+
+// Blocks of a grown tree in world coordinates. Logs come first so callers
+// can let them replace leaves of neighbouring trees.
+pub fn grow_tree(
+    species: TreeSpecies,
+    base: (i32, i32, i32),
+    config: &TreeConfig,
+    rng: &mut ChunkRng,
+) -> Vec<(i32, i32, i32, BlockType)> {
+    let mut blocks = Vec::new();
+    match species {
+        TreeSpecies::Oak => grow_oak(&mut blocks, base, config),
+        TreeSpecies::Birch => grow_birch(&mut blocks, base, rng),
+        TreeSpecies::Spruce => grow_spruce(&mut blocks, base, rng),
+        TreeSpecies::GreatOak => grow_great_oak(&mut blocks, base, rng),
+        TreeSpecies::Bush => grow_bush(&mut blocks, base),
+        TreeSpecies::Cactus => {
+            for y in 0..rng.range_i32(1, 3) {
+                blocks.push((base.0, base.1 + y, base.2, BlockType::Cactus));
+            }
+        }
+    }
+    blocks
+}
+
+fn grow_trunk(
+    blocks: &mut Vec<(i32, i32, i32, BlockType)>,
+    base: (i32, i32, i32),
+    height: i32,
+    log: BlockType,
+) {
+    for y in 0..height {
+        blocks.push((base.0, base.1 + y, base.2, log));
+    }
+}
+
+// The classic shape: straight trunk with a diamond blob of leaves on top
+fn grow_oak(
+    blocks: &mut Vec<(i32, i32, i32, BlockType)>,
+    base: (i32, i32, i32),
+    config: &TreeConfig,
+) {
+    let trunk_height = config.trunk_height as i32;
+    let radius = config.leaf_radius;
+    grow_trunk(blocks, base, trunk_height, BlockType::OakLog);
+
+    let leaf_y = base.1 + trunk_height;
+    for dx in -radius..=radius {
+        for dz in -radius..=radius {
+            for dy in 0..=radius {
+                if dx.abs() + dz.abs() + dy <= radius + 1 {
+                    blocks.push((base.0 + dx, leaf_y + dy, base.2 + dz, BlockType::Leaves));
+                }
+            }
+        }
+    }
+}
+
+// Tall thin trunk with a narrow column of leaves around the top
+fn grow_birch(
+    blocks: &mut Vec<(i32, i32, i32, BlockType)>,
+    base: (i32, i32, i32),
+    rng: &mut ChunkRng,
+) {
+    let height = rng.range_i32(5, 7);
+    grow_trunk(blocks, base, height, BlockType::BirchLog);
+
+    let top = base.1 + height - 1;
+    for dy in -2..=1 {
+        let radius: i32 = if dy >= 0 { 1 } else { 2 };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                // Randomly trimmed corners keep the crown from looking boxy
+                let corner = dx.abs() == radius && dz.abs() == radius;
+                if corner && (dy == 1 || rng.chance(0.5)) {
+                    continue;
+                }
+                blocks.push((base.0 + dx, top + dy, base.2 + dz, BlockType::BirchLeaves));
+            }
+        }
+    }
+}
+
+// Conical tree: leaf rings widen going down, alternating with narrow rings
+fn grow_spruce(
+    blocks: &mut Vec<(i32, i32, i32, BlockType)>,
+    base: (i32, i32, i32),
+    rng: &mut ChunkRng,
+) {
+    let height = rng.range_i32(7, 10);
+    grow_trunk(blocks, base, height, BlockType::SpruceLog);
+
+    let top = base.1 + height - 1;
+    blocks.push((base.0, top + 1, base.2, BlockType::SpruceLeaves));
+    for y in base.1 + 2..=top {
+        let layer = top - y;
+        let mut radius = ((layer + 1) / 2).min(3);
+        if layer % 2 == 1 && radius > 1 {
+            radius -= 1;
+        }
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                if dx.abs() + dz.abs() <= radius + 1 && (dx, dz) != (0, 0) {
+                    blocks.push((base.0 + dx, y, base.2 + dz, BlockType::SpruceLeaves));
+                }
+            }
+        }
+    }
+}
+
+// Low shrub: a single log hidden in a ball of leaves
+fn grow_bush(blocks: &mut Vec<(i32, i32, i32, BlockType)>, base: (i32, i32, i32)) {
+    blocks.push((base.0, base.1, base.2, BlockType::OakLog));
+    for dx in -1..=1_i32 {
+        for dz in -1..=1_i32 {
+            for dy in 0..=1_i32 {
+                if dx * dx + dy * dy + dz * dz <= 2 {
+                    blocks.push((base.0 + dx, base.1 + dy, base.2 + dz, BlockType::Leaves));
+                }
+            }
+        }
+    }
+}
+
+// Writes one block of a tree if it lands inside the chunk. Leaves only fill
+// empty space; logs may also push through leaves, plants and snow.
+pub fn place_tree_block(chunk: &mut Chunk, x: i32, y: i32, z: i32, block_type: BlockType) {
+    let lx = x - chunk.x * CHUNK_SIZE as i32;
+    let lz = z - chunk.z * CHUNK_SIZE as i32;
+    if lx < 0 || lx >= CHUNK_SIZE as i32 || lz < 0 || lz >= CHUNK_SIZE as i32 || y < 0 {
+        return;
+    }
+    let (lx, y, lz) = (lx as usize, y as usize, lz as usize);
+    let Some(existing) = chunk.get_block(lx, y, lz) else {
+        return;
+    };
+
+    let replaceable = existing.block_type == BlockType::Air
+        || existing.block_type == BlockType::SnowLayer
+        || existing.is_cross()
+        || (existing.is_leaves() && !Block::new(block_type).is_leaves());
+    if replaceable {
+        chunk.set_block(lx, y, lz, block_type);
+    }
+}
+
+// Rewriting rules for the great oak:
+//   F  grow one log forward
+//   &  tilt away from the current direction
+//   /  roll around the current direction, so the next tilt points elsewhere
+//   [] save and restore the turtle
+//   A  a branching point, replaced by leaves once the rules stop
+struct LSystem {
+    axiom: &'static str,
+    rules: &'static [(char, &'static str)],
+    iterations: usize,
+}
+
+impl LSystem {
+    fn expand(&self) -> String {
+        let mut current = self.axiom.to_string();
+        for _ in 0..self.iterations {
+            let mut next = String::with_capacity(current.len() * 4);
+            for c in current.chars() {
+                match self.rules.iter().find(|(symbol, _)| *symbol == c) {
+                    Some((_, replacement)) => next.push_str(replacement),
+                    None => next.push(c),
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+const GREAT_OAK: LSystem = LSystem {
+    axiom: "FFFFFA",
+    rules: &[('A', "F[&FFFA]/[&FFFA]/[&FFA]")],
+    iterations: 2,
+};
+
+#[derive(Clone, Copy)]
+struct Turtle {
+    position: Vec3,
+    heading: Vec3,
+    left: Vec3,
+}
+
+// Turns v around a unit axis by whole angle steps (Rodrigues' formula)
+fn rotate(v: Vec3, axis: Vec3, step: i32) -> Vec3 {
+    let cos = angle::cos(step) as f32;
+    let sin = angle::sin(step) as f32;
+    v * cos + axis.cross(v) * sin + axis * axis.dot(v) * (1.0 - cos)
+}
+
+fn grow_great_oak(
+    blocks: &mut Vec<(i32, i32, i32, BlockType)>,
+    base: (i32, i32, i32),
+    rng: &mut ChunkRng,
+) {
+    let mut turtle = Turtle {
+        position: Vec3::new(
+            base.0 as f32 + 0.5,
+            base.1 as f32 - 0.5,
+            base.2 as f32 + 0.5,
+        ),
+        heading: Vec3::Y,
+        left: Vec3::X,
+    };
+    // Random starting roll so branches don't all point the same way
+    let roll = rng.range_i32(0, angle::TURN - 1);
+    turtle.left = rotate(turtle.left, Vec3::Y, roll);
+
+    let mut stack = Vec::new();
+    let mut leaves = Vec::new();
+
+    for symbol in GREAT_OAK.expand().chars() {
+        match symbol {
+            'F' => {
+                turtle.position += turtle.heading;
+                let p = turtle.position.floor();
+                blocks.push((p.x as i32, p.y as i32, p.z as i32, BlockType::OakLog));
+            }
+            '&' => {
+                let tilt = rng.range_i32(angle::from_degrees(30.0), angle::from_degrees(45.0));
+                turtle.heading = rotate(turtle.heading, turtle.left, tilt);
+            }
+            '/' => {
+                let roll = rng.range_i32(angle::from_degrees(110.0), angle::from_degrees(130.0));
+                turtle.left = rotate(turtle.left, turtle.heading, roll);
+            }
+            '[' => stack.push(turtle),
+            ']' => {
+                if let Some(saved) = stack.pop() {
+                    turtle = saved;
+                }
+            }
+            'A' => leaves.push(turtle.position.floor()),
+            _ => {}
+        }
+    }
+
+    // Leaves go in after every log so they never cover a branch
+    for center in leaves {
+        for dx in -2..=2_i32 {
+            for dy in -1..=2_i32 {
+                for dz in -2..=2_i32 {
+                    let distance = dx * dx + dy * dy + dz * dz;
+                    if distance <= 4 || (distance <= 6 && rng.chance(0.5)) {
+                        blocks.push((
+                            center.x as i32 + dx,
+                            center.y as i32 + dy,
+                            center.z as i32 + dz,
+                            BlockType::Leaves,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECIES: [TreeSpecies; 6] = [
+        TreeSpecies::Oak,
+        TreeSpecies::Birch,
+        TreeSpecies::Spruce,
+        TreeSpecies::GreatOak,
+        TreeSpecies::Bush,
+        TreeSpecies::Cactus,
+    ];
+
+    // Chunks only look MAX_TREE_REACH blocks out for trees, so no tree may
+    // reach further, even an oak with the widest crown the config allows
+    #[test]
+    fn trees_stay_within_their_reach() {
+        let config = TreeConfig {
+            leaf_radius: MAX_TREE_REACH,
+            ..TreeConfig::default()
+        };
+        for species in SPECIES {
+            for seed in 0..200 {
+                let mut rng = ChunkRng::new(seed, 0, 0, 0);
+                let blocks = grow_tree(species, (100, 64, -100), &config, &mut rng);
+                assert!(!blocks.is_empty());
+                for (x, y, z, _) in blocks {
+                    assert!(
+                        (x - 100).abs() <= MAX_TREE_REACH,
+                        "{:?} at x {}",
+                        species,
+                        x
+                    );
+                    assert!(
+                        (z + 100).abs() <= MAX_TREE_REACH,
+                        "{:?} at z {}",
+                        species,
+                        z
+                    );
+                    assert!(y >= 64, "{:?} below its base", species);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn logs_come_before_leaves() {
+        for species in SPECIES {
+            let mut rng = ChunkRng::new(7, 0, 0, 0);
+            let blocks = grow_tree(species, (0, 64, 0), &TreeConfig::default(), &mut rng);
+            let first_leaf = blocks
+                .iter()
+                .position(|&(_, _, _, block_type)| Block::new(block_type).is_leaves())
+                .unwrap_or(blocks.len());
+            assert!(
+                blocks[first_leaf..]
+                    .iter()
+                    .all(|&(_, _, _, block_type)| Block::new(block_type).is_leaves()),
+                "{:?} has logs after its leaves",
+                species
+            );
+        }
+    }
+
+    #[test]
+    fn tree_blocks_land_only_in_their_chunk() {
+        let mut chunk = Chunk::new(1, 0);
+        chunk.set_block(3, 64, 3, BlockType::TallGrass);
+        chunk.set_block(4, 64, 4, BlockType::Leaves);
+        chunk.set_block(5, 64, 5, BlockType::OakLog);
+
+        // Outside the chunk and below the world nothing is written
+        place_tree_block(&mut chunk, 15, 64, 3, BlockType::OakLog);
+        place_tree_block(&mut chunk, 32, 64, 3, BlockType::OakLog);
+        place_tree_block(&mut chunk, 18, -1, 3, BlockType::OakLog);
+        assert_eq!(
+            chunk.get_block(15, 64, 3).unwrap().block_type,
+            BlockType::Air
+        );
+
+        place_tree_block(&mut chunk, 19, 64, 3, BlockType::Leaves);
+        place_tree_block(&mut chunk, 20, 64, 4, BlockType::OakLog);
+        place_tree_block(&mut chunk, 21, 64, 5, BlockType::Leaves);
+        let block_at = |x, z| chunk.get_block(x, 64, z).unwrap().block_type;
+        // Plants give way, leaves give way to logs, logs never give way
+        assert_eq!(block_at(3, 3), BlockType::Leaves);
+        assert_eq!(block_at(4, 4), BlockType::OakLog);
+        assert_eq!(block_at(5, 5), BlockType::OakLog);
+    }
+
+    #[test]
+    fn weighted_picks_skip_zero_weights() {
+        let mut rng = ChunkRng::new(3, 0, 0, 0);
+        assert_eq!(pick_weighted::<BlockType>(&[], &mut rng), None);
+        assert_eq!(pick_weighted(&[(1, 0)], &mut rng), None);
+        for _ in 0..100 {
+            assert_eq!(pick_weighted(&[(1, 0), (2, 5), (3, 0)], &mut rng), Some(2));
+        }
+    }
+
+    #[test]
+    fn rotation_matches_a_quaternion() {
+        let axis = Vec3::new(1.0, 2.0, -0.5).normalize();
+        let v = Vec3::new(0.3, -1.0, 2.0);
+        for step in [0, 20, 32, 100, 192] {
+            let radians = step as f32 / angle::TURN as f32 * std::f32::consts::TAU;
+            let expected = glam::Quat::from_axis_angle(axis, radians) * v;
+            assert!(
+                rotate(v, axis, step).abs_diff_eq(expected, 1e-5),
+                "{}",
+                step
+            );
+        }
+    }
+}
@@ -1,16 +1,115 @@
-use crate::block::Block;
-use crate::chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE};
-use std::collections::HashMap;
+use crate::block::{Block, BlockType};
+use crate::block_update::{self, DEFAULT_RANDOM_TICK_SPEED, TickPriority, TickQueue};
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk, HeightmapKind};
+use crate::dimension::{ChunkGenerator, DimensionId};
+use crate::entity::{Entity, EntityId, EntityKind};
+use crate::pathfinding::MAX_PATH_REACH;
+use crate::rng::{ChunkRng, SALT_RANDOM_TICK};
+use crate::terrain_config::{GrowthConfig, TreeConfig};
+use glam::Vec3;
+use history::EditHistory;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem;
 
+pub mod edit;
+pub mod history;
+
+// A block's world position
+pub type BlockPos = (i32, i32, i32);
+
+// The six blocks sharing a face with a block
+pub const NEIGHBOURS: [(i32, i32, i32); 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+// A dimension the world isn't in: its chunks, and the updates and ticks it
+// still had to run when the world left it
+#[derive(Default)]
+struct SetAside {
+    chunks: HashMap<(i32, i32), Chunk>,
+    block_updates: VecDeque<BlockPos>,
+    scheduled_ticks: TickQueue,
+}
+
+// Block access always goes to the current dimension. The other dimensions are
+// kept aside until the world switches back to them.
+#[derive(Default)]
 pub struct World {
     pub chunks: HashMap<(i32, i32), Chunk>,
+    pub dimension: DimensionId,
+    generators: HashMap<DimensionId, Box<dyn ChunkGenerator>>,
+    other_dimensions: HashMap<DimensionId, SetAside>,
+    next_entity_id: EntityId,
+    // Seed of the level, which random ticks mix with the game tick
+    pub seed: u32,
+    // Random ticks per chunk section per game tick
+    pub random_tick_speed: u32,
+    // What random ticks make grow, and the shape of trees grown from saplings
+    pub growth: GrowthConfig,
+    pub trees: TreeConfig,
+    // Blocks to notify of a change next to them or to themselves, oldest
+    // first
+    block_updates: VecDeque<BlockPos>,
+    // Chunks with blocks changed since their meshes were last built
+    changed_chunks: HashSet<(i32, i32)>,
+    game_tick: u64,
+    scheduled_ticks: TickQueue,
+    // Player edits, for undo and redo and to put back into chunks that are
+    // generated again
+    pub history: EditHistory,
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
-            chunks: HashMap::new(),
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            ..Self::default()
+        }
+    }
+
+    pub fn add_dimension(&mut self, id: DimensionId, generator: Box<dyn ChunkGenerator>) {
+        self.generators.insert(id, generator);
+    }
+
+    pub fn switch_dimension(&mut self, id: DimensionId) {
+        if id == self.dimension {
+            return;
         }
+        let left = SetAside {
+            chunks: mem::take(&mut self.chunks),
+            block_updates: mem::take(&mut self.block_updates),
+            scheduled_ticks: mem::take(&mut self.scheduled_ticks),
+        };
+        self.other_dimensions.insert(self.dimension, left);
+        let entered = self.other_dimensions.remove(&id).unwrap_or_default();
+        self.chunks = entered.chunks;
+        self.block_updates = entered.block_updates;
+        self.scheduled_ticks = entered.scheduled_ticks;
+        self.dimension = id;
+        self.changed_chunks.clear();
+    }
+
+    // Generates a chunk of the current dimension unless it is already loaded.
+    // Returns false if the dimension has no generator.
+    pub fn load_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> bool {
+        if self.chunks.contains_key(&(chunk_x, chunk_z)) {
+            return true;
+        }
+        let Some(generator) = self.generators.get(&self.dimension) else {
+            return false;
+        };
+        let mut chunk = generator.generate_chunk(chunk_x, chunk_z);
+        for ((x, y, z), block) in self.history.edits_in(self.dimension, (chunk_x, chunk_z)) {
+            let (_, (local_x, local_z)) = Self::world_to_chunk_coords(x, z);
+            chunk.put_block(local_x, y as usize, local_z, block);
+        }
+        self.chunks.insert((chunk_x, chunk_z), chunk);
+        true
     }
 
     // FIXED coordinate conversion for negative values
@@ -40,23 +139,528 @@ impl World {
 
         let ((chunk_x, chunk_z), (local_x, local_z)) = Self::world_to_chunk_coords(x, z);
 
-        if let Some(chunk) = self.chunks.get(&(chunk_x, chunk_z)) {
-            if let Some(block) = chunk.get_block(local_x, y as usize, local_z) {
-                return Some(*block);
-            }
+        if let Some(chunk) = self.chunks.get(&(chunk_x, chunk_z))
+            && let Some(block) = chunk.get_block(local_x, y as usize, local_z)
+        {
+            return Some(*block);
         }
         None
     }
 
     pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        let pos = (x, y, z);
+        if !self.put(pos, block) {
+            return;
+        }
+        self.block_updates.push_back(pos);
+        for (dx, dy, dz) in NEIGHBOURS {
+            self.block_updates.push_back((x + dx, y + dy, z + dz));
+        }
+        self.paths_changed(pos, pos);
+    }
+
+    // Sets many blocks in one go and returns how many were set; positions
+    // outside the loaded chunks are skipped. However many blocks change,
+    // every chunk is remeshed once, only the blocks around the edit are
+    // notified from outside it and mobs check their paths once.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (BlockPos, Block)>) -> usize {
+        // In the order they were set, so updates run the same every time
+        let mut order = Vec::new();
+        let mut changed = HashSet::new();
+        for (pos, block) in blocks {
+            if self.put(pos, block) && changed.insert(pos) {
+                order.push(pos);
+            }
+        }
+        let Some(&first) = order.first() else {
+            return 0;
+        };
+
+        // The blocks themselves and the ones touching them may react
+        let (mut min, mut max) = (first, first);
+        for &(x, y, z) in &order {
+            min = (min.0.min(x), min.1.min(y), min.2.min(z));
+            max = (max.0.max(x), max.1.max(y), max.2.max(z));
+            self.block_updates.push_back((x, y, z));
+            for (dx, dy, dz) in NEIGHBOURS {
+                let neighbour = (x + dx, y + dy, z + dz);
+                if !changed.contains(&neighbour) {
+                    self.block_updates.push_back(neighbour);
+                }
+            }
+        }
+        self.paths_changed(min, max);
+        order.len()
+    }
+
+    // Sets one block and marks what needs remeshing, without notifying
+    // anything. Returns false outside the loaded chunks.
+    fn put(&mut self, (x, y, z): BlockPos, block: Block) -> bool {
         if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return false;
+        }
+        let ((chunk_x, chunk_z), (local_x, local_z)) = Self::world_to_chunk_coords(x, z);
+        let Some(chunk) = self.chunks.get_mut(&(chunk_x, chunk_z)) else {
+            return false;
+        };
+        if !self.history.is_recording() {
+            self.history
+                .changed_outside(self.dimension, (x, y, z), block);
+        } else if let Some(&old) = chunk.get_block(local_x, y as usize, local_z) {
+            self.history.record((x, y, z), old, block);
+        }
+        let top = chunk.height(HeightmapKind::MotionBlocking, local_x, local_z);
+        chunk.put_block(local_x, y as usize, local_z, block);
+        let lit = top != chunk.height(HeightmapKind::MotionBlocking, local_x, local_z);
+
+        // Faces against a neighbouring chunk change its mesh too
+        self.changed_chunks.insert((chunk_x, chunk_z));
+        let last = CHUNK_SIZE - 1;
+        for (edge, neighbour) in [
+            (local_x == 0, (chunk_x - 1, chunk_z)),
+            (local_x == last, (chunk_x + 1, chunk_z)),
+            (local_z == 0, (chunk_x, chunk_z - 1)),
+            (local_z == last, (chunk_x, chunk_z + 1)),
+        ] {
+            if edge && self.chunks.contains_key(&neighbour) {
+                self.changed_chunks.insert(neighbour);
+            }
+        }
+        // So do shadows: meshes take the sky light of the columns around
+        // theirs, so every chunk next to a column whose top moved is out of
+        // date
+        if lit {
+            self.mark_light_changed(x, z);
+        }
+        true
+    }
+
+    // Lets mobs drop paths through a box of changed blocks. A mob walks
+    // along its path, which stays within MAX_PATH_REACH of where it started,
+    // so only mobs up to twice that from the box can be walking through it.
+    fn paths_changed(&mut self, min: BlockPos, max: BlockPos) {
+        let reach = 2 * MAX_PATH_REACH + 1;
+        let (low, _) = Self::world_to_chunk_coords(min.0 - reach, min.2 - reach);
+        let (high, _) = Self::world_to_chunk_coords(max.0 + reach, max.2 + reach);
+        for chunk_x in low.0..=high.0 {
+            for chunk_z in low.1..=high.1 {
+                let Some(chunk) = self.chunks.get_mut(&(chunk_x, chunk_z)) else {
+                    continue;
+                };
+                for entity in &mut chunk.entities {
+                    if let Some(brain) = &mut entity.brain {
+                        brain.region_changed(min, max);
+                    }
+                }
+            }
+        }
+    }
+
+    // Runs an edit as one transaction that undo takes back in one go.
+    // Edits made inside another transaction become part of it.
+    pub fn transaction<R>(&mut self, edit: impl FnOnce(&mut World) -> R) -> R {
+        let outermost = self.history.begin(self.dimension);
+        let result = edit(self);
+        if outermost {
+            self.history.commit();
+        }
+        result
+    }
+
+    // Sets back the blocks the last transaction in this dimension changed
+    // and returns how many, or None if there is nothing here to undo
+    pub fn undo(&mut self) -> Option<usize> {
+        let transaction = self.history.take_undo(self.dimension)?;
+        let set = self.set_blocks(
+            transaction
+                .changes
+                .iter()
+                .map(|change| (change.pos, change.old)),
+        );
+        self.history.undone(transaction);
+        Some(set)
+    }
+
+    // Makes the last undone transaction again
+    pub fn redo(&mut self) -> Option<usize> {
+        let transaction = self.history.take_redo(self.dimension)?;
+        let set = self.set_blocks(
+            transaction
+                .changes
+                .iter()
+                .map(|change| (change.pos, change.new)),
+        );
+        self.history.redone(transaction);
+        Some(set)
+    }
+
+    // Marks the chunks whose meshes read the sky light of this column
+    fn mark_light_changed(&mut self, x: i32, z: i32) {
+        for (dx, dz) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let (chunk, _) = Self::world_to_chunk_coords(x + dx, z + dz);
+            if self.chunks.contains_key(&chunk) {
+                self.changed_chunks.insert(chunk);
+            }
+        }
+    }
+
+    // The chunks whose meshes are out of date, forgetting them
+    pub fn take_changed_chunks(&mut self) -> Vec<(i32, i32)> {
+        self.changed_chunks.drain().collect()
+    }
+
+    // Runs one game tick: neighbour updates from the last tick's changes,
+    // then the scheduled ticks that are due, random ticks and entities
+    pub fn tick(&mut self, dt: f32, player: Option<Vec3>) {
+        self.game_tick += 1;
+        self.update_neighbours();
+        self.run_scheduled_ticks();
+        self.run_random_ticks();
+        self.tick_entities(dt, player);
+    }
+
+    // Game ticks run so far
+    pub fn game_tick(&self) -> u64 {
+        self.game_tick
+    }
+
+    // Carries on counting from a saved world. Only for a world that has no
+    // scheduled ticks yet, as they are due at a game tick.
+    pub fn set_game_tick(&mut self, game_tick: u64) {
+        self.game_tick = game_tick;
+    }
+
+    // Asks for the block at a position to get a scheduled tick in delay game
+    // ticks, unless it already waits for one
+    pub fn schedule_tick(&mut self, pos: BlockPos, delay: u64, priority: TickPriority) {
+        let due = self.game_tick + delay.max(1);
+        self.scheduled_ticks.schedule(pos, due, priority);
+    }
+
+    // Notifies every block next to a change, including changes made while
+    // notifying: removing the support of a sand column lets every block of
+    // it fall in turn
+    fn update_neighbours(&mut self) {
+        while let Some(pos) = self.block_updates.pop_front() {
+            block_update::neighbour_changed(self, pos);
+        }
+    }
+
+    fn run_scheduled_ticks(&mut self) {
+        while let Some(pos) = self.scheduled_ticks.pop_due(self.game_tick) {
+            block_update::scheduled_tick(self, pos);
+        }
+    }
+
+    fn run_random_ticks(&mut self) {
+        if self.random_tick_speed == 0 {
             return;
         }
+        // Same order every tick, whatever the hash map does
+        let mut loaded: Vec<(i32, i32)> = self.chunks.keys().copied().collect();
+        loaded.sort();
+        for (chunk_x, chunk_z) in loaded {
+            let mut rng = ChunkRng::at_tick(
+                self.seed,
+                self.game_tick,
+                chunk_x,
+                chunk_z,
+                SALT_RANDOM_TICK,
+            );
+            for (x, y, z) in block_update::random_tick_positions(&mut rng, self.random_tick_speed) {
+                let pos = (
+                    chunk_x * CHUNK_SIZE as i32 + x as i32,
+                    y as i32,
+                    chunk_z * CHUNK_SIZE as i32 + z as i32,
+                );
+                block_update::random_tick(self, pos);
+            }
+        }
+    }
+
+    // y of the highest solid block in a column, if its chunk is loaded and
+    // the column isn't empty
+    pub fn height_at(&self, x: i32, z: i32) -> Option<i32> {
+        self.heightmap_at(HeightmapKind::Solid, x, z)
+    }
 
+    pub fn heightmap_at(&self, kind: HeightmapKind, x: i32, z: i32) -> Option<i32> {
         let ((chunk_x, chunk_z), (local_x, local_z)) = Self::world_to_chunk_coords(x, z);
+        let chunk = self.chunks.get(&(chunk_x, chunk_z))?;
+        chunk.height(kind, local_x, local_z).map(|y| y as i32)
+    }
+
+    // Nothing above this position stops sunlight or rain
+    pub fn is_exposed_to_sky(&self, x: i32, y: i32, z: i32) -> bool {
+        self.heightmap_at(HeightmapKind::MotionBlocking, x, z)
+            .is_none_or(|top| y > top)
+    }
+
+    // The y where falling rain or snow comes to rest in a column, for weather
+    // effects. Plants and snow layers don't stop it.
+    pub fn precipitation_height(&self, x: i32, z: i32) -> i32 {
+        self.heightmap_at(HeightmapKind::MotionBlocking, x, z)
+            .map_or(0, |top| top + 1)
+    }
+
+    // Adds an entity to the chunk it stands in and returns its new id, or
+    // None if that chunk isn't loaded
+    pub fn spawn_entity(&mut self, mut entity: Entity) -> Option<EntityId> {
+        let chunk = self.chunks.get_mut(&entity.chunk_pos())?;
+        self.next_entity_id += 1;
+        entity.id = self.next_entity_id;
+        chunk.entities.push(entity);
+        Some(self.next_entity_id)
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<&Entity> {
+        self.entities().find(|entity| entity.id == id)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.chunks.values().flat_map(|chunk| chunk.entities.iter())
+    }
+
+    // Takes out every entity the predicate picks and returns them
+    pub fn remove_entities(&mut self, mut predicate: impl FnMut(&Entity) -> bool) -> Vec<Entity> {
+        let mut removed = Vec::new();
+        for chunk in self.chunks.values_mut() {
+            let (taken, kept) = chunk.entities.drain(..).partition(&mut predicate);
+            chunk.entities = kept;
+            removed.extend(taken);
+        }
+        removed
+    }
+
+    // Moves every entity by one tick. Entities are taken out of their chunks
+    // while they move so they can collide with the world, then filed under
+    // the chunk they end up in. One that would cross into a chunk that isn't
+    // loaded stays where it was. Mobs steer towards or away from the player,
+    // if there is one.
+    fn tick_entities(&mut self, dt: f32, player: Option<Vec3>) {
+        let mut moving: Vec<Entity> = self
+            .chunks
+            .values_mut()
+            .flat_map(|chunk| chunk.entities.drain(..))
+            .collect();
+        // Same order every tick, whatever the hash map does
+        moving.sort_by_key(|entity| entity.id);
+
+        for entity in &mut moving {
+            let before = entity.clone();
+            entity.think(self, player, dt);
+            entity.tick(self, dt);
+            if !self.chunks.contains_key(&entity.chunk_pos()) {
+                *entity = before;
+                entity.velocity = Vec3::ZERO;
+            }
+            // Landing right away lets the falling block above land on it
+            if let EntityKind::FallingBlock(block_type) = entity.kind
+                && entity.removed
+                && entity.on_ground
+            {
+                self.land(block_type, entity.position);
+            }
+        }
+
+        for entity in moving {
+            if entity.removed {
+                continue;
+            }
+            if let Some(chunk) = self.chunks.get_mut(&entity.chunk_pos()) {
+                chunk.entities.push(entity);
+            }
+        }
+    }
+
+    // A falling block that came to rest turns back into a block, crushing
+    // plants or snow in its way, or drops as an item if a block is there
+    fn land(&mut self, block_type: BlockType, position: Vec3) {
+        let cell = position.round().as_ivec3();
+        let (x, z) = (position.x.floor() as i32, position.z.floor() as i32);
+        match self.get_block(x, cell.y, z) {
+            Some(block) if !block.is_solid() => {
+                self.set_block(x, cell.y, z, Block::new(block_type));
+            }
+            _ => {
+                self.spawn_entity(Entity::new(EntityKind::Item(block_type), position));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_world::{DT, FloorGenerator, empty_world};
+
+    fn block_at(world: &World, x: i32, y: i32, z: i32) -> Option<BlockType> {
+        world.get_block(x, y, z).map(|block| block.block_type)
+    }
+
+    #[test]
+    fn each_dimension_keeps_its_own_chunks() {
+        let mut world = World::new();
+        world.add_dimension(
+            DimensionId::Overworld,
+            Box::new(FloorGenerator(BlockType::Stone)),
+        );
+        world.add_dimension(DimensionId::Sky, Box::new(FloorGenerator(BlockType::Grass)));
+
+        assert!(world.load_chunk(0, 0));
+        world.set_block(3, 1, 3, Block::new(BlockType::Planks));
+
+        world.switch_dimension(DimensionId::Sky);
+        assert_eq!(world.dimension, DimensionId::Sky);
+        assert_eq!(block_at(&world, 3, 1, 3), None);
+        assert!(world.load_chunk(0, 0));
+        assert_eq!(block_at(&world, 3, 0, 3), Some(BlockType::Grass));
+        assert_eq!(block_at(&world, 3, 1, 3), Some(BlockType::Air));
+        world.set_block(5, 1, 5, Block::new(BlockType::Cobblestone));
+
+        world.switch_dimension(DimensionId::Overworld);
+        assert_eq!(block_at(&world, 3, 0, 3), Some(BlockType::Stone));
+        assert_eq!(block_at(&world, 3, 1, 3), Some(BlockType::Planks));
+        assert_eq!(block_at(&world, 5, 1, 5), Some(BlockType::Air));
+
+        // Switching to the current dimension changes nothing
+        world.switch_dimension(DimensionId::Overworld);
+        assert_eq!(block_at(&world, 3, 1, 3), Some(BlockType::Planks));
+
+        world.switch_dimension(DimensionId::Sky);
+        assert_eq!(world.chunks.len(), 1);
+        assert_eq!(block_at(&world, 5, 1, 5), Some(BlockType::Cobblestone));
+    }
+
+    fn changed_chunks(world: &mut World) -> Vec<(i32, i32)> {
+        let mut changed = world.take_changed_chunks();
+        changed.sort();
+        changed
+    }
+
+    #[test]
+    fn shadow_changes_remesh_the_chunks_that_show_them() {
+        let mut world = World::new();
+        world.add_dimension(
+            DimensionId::Overworld,
+            Box::new(FloorGenerator(BlockType::Stone)),
+        );
+        for chunk_x in -1..=1 {
+            for chunk_z in -1..=1 {
+                world.load_chunk(chunk_x, chunk_z);
+            }
+        }
+
+        // Under the column's top nothing but its own chunk changes
+        world.set_block(8, 20, 8, Block::new(BlockType::Stone));
+        world.take_changed_chunks();
+        world.set_block(8, 10, 8, Block::new(BlockType::Stone));
+        assert_eq!(changed_chunks(&mut world), [(0, 0)]);
 
-        if let Some(chunk) = self.chunks.get_mut(&(chunk_x, chunk_z)) {
-            chunk.set_block(local_x, y as usize, local_z, block.block_type);
+        // A new roof at the edge shades the chunk next to it
+        world.set_block(15, 30, 8, Block::new(BlockType::Stone));
+        assert_eq!(changed_chunks(&mut world), [(0, 0), (1, 0)]);
+        world.set_block(-1, 30, -1, Block::new(BlockType::Leaves));
+        assert_eq!(changed_chunks(&mut world), [(-1, -1), (-1, 0), (0, -1)]);
+
+        // Taking it away lights them again
+        world.set_block(15, 30, 8, Block::new(BlockType::Air));
+        assert_eq!(changed_chunks(&mut world), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn dimensions_keep_their_updates_and_ticks_while_away() {
+        let mut world = World::new();
+        world.add_dimension(
+            DimensionId::Overworld,
+            Box::new(FloorGenerator(BlockType::Air)),
+        );
+        world.add_dimension(DimensionId::Sky, Box::new(FloorGenerator(BlockType::Air)));
+        world.load_chunk(0, 0);
+        world.set_block(3, 10, 3, Block::new(BlockType::Sand));
+        world.schedule_tick((5, 10, 5), 20, TickPriority::Normal);
+
+        world.switch_dimension(DimensionId::Sky);
+        assert!(world.block_updates.is_empty());
+        assert!(!world.scheduled_ticks.is_scheduled((5, 10, 5)));
+
+        world.switch_dimension(DimensionId::Overworld);
+        assert!(world.block_updates.contains(&(3, 10, 3)));
+        assert!(world.scheduled_ticks.is_scheduled((5, 10, 5)));
+        // The sand waiting to fall when the world left still falls
+        for _ in 0..10 {
+            world.tick(DT, None);
         }
+        assert_eq!(block_at(&world, 3, 10, 3), Some(BlockType::Air));
+    }
+
+    #[test]
+    fn dimensions_without_a_generator_load_nothing() {
+        let mut world = World::new();
+        world.switch_dimension(DimensionId::Sky);
+        assert!(!world.load_chunk(0, 0));
+        assert!(world.chunks.is_empty());
+    }
+
+    #[test]
+    fn ticks_update_neighbours_then_run_scheduled_ticks_random_ticks_and_entities() {
+        let mut world = empty_world(-1..=1, -1..=1);
+        world.seed = 9;
+        world.random_tick_speed = 1;
+        world.growth.saplings.growth_chance = 1.0;
+        world.growth.leaves.sapling_chance = 1.0;
+        let tick = 100;
+        world.set_game_tick(tick - 1);
+
+        // Where the next tick's random ticks land in chunk (0, 0), one per
+        // section
+        let mut rng = ChunkRng::at_tick(world.seed, tick, 0, 0, SALT_RANDOM_TICK);
+        let picked = block_update::random_tick_positions(&mut rng, 1);
+        let (x, y, z) = picked[2];
+        let sapling = (x as i32, y as i32, z as i32);
+        let (x, y, z) = picked[4];
+        let leaf = (x as i32, y as i32, z as i32);
+
+        // A sapling whose trunk is blocked by floating sand, lone leaves and
+        // a column of floating sand. Both lower sand blocks fall this tick.
+        let (x, y, z) = sapling;
+        world.set_block(x, y - 1, z, Block::new(BlockType::Dirt));
+        world.set_block(x, y, z, Block::new(BlockType::OakSapling));
+        world.set_block(x, y + 2, z, Block::new(BlockType::Sand));
+        world.set_block(leaf.0, leaf.1, leaf.2, Block::new(BlockType::Leaves));
+        let (lower, upper) = ((-8, 40, -8), (-8, 41, -8));
+        world.set_block(lower.0, lower.1, lower.2, Block::new(BlockType::Sand));
+        world.set_block(upper.0, upper.1, upper.2, Block::new(BlockType::Sand));
+        for pos in [(x, y + 2, z), lower] {
+            world.schedule_tick(pos, 1, TickPriority::Normal);
+        }
+        world.update_neighbours();
+
+        world.tick(DT, None);
+        assert_eq!(world.game_tick(), tick);
+        // Taking out the lower sand notifies the upper one next tick
+        assert!(!world.scheduled_ticks.is_scheduled(upper));
+        // The sand above the sapling fell before the sapling's random tick
+        assert_eq!(block_at(&world, x, y + 1, z), Some(BlockType::OakLog));
+        // What the scheduled and random ticks dropped has moved already
+        let falling = world
+            .entities()
+            .find(|entity| {
+                entity.kind == EntityKind::FallingBlock(BlockType::Sand) && entity.position.x < 0.0
+            })
+            .unwrap();
+        assert!(falling.position.y < lower.1 as f32);
+        let dropped = world
+            .entities()
+            .find(|entity| entity.kind == EntityKind::Item(BlockType::OakSapling))
+            .unwrap();
+        assert!(dropped.position.y < leaf.1 as f32 + 0.25);
+        assert_eq!(
+            block_at(&world, leaf.0, leaf.1, leaf.2),
+            Some(BlockType::Air)
+        );
+
+        world.tick(DT, None);
+        assert!(world.scheduled_ticks.is_scheduled(upper));
     }
 }
@@ -0,0 +1,337 @@
+// Bulk edits for building levels: fill a region, build boxes and spheres,
+// swap one block type for another and move builds around through a
+// clipboard. Every operation sets its blocks in one World::set_blocks call,
+// so however large the edit each chunk is remeshed once, and is one
+// transaction for undo.
+use crate::block::{Block, BlockType};
+use crate::world::{BlockPos, World};
+
+// A box of blocks between two corners, both included
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl Region {
+    // The corners can be any two opposite ones
+    pub fn new(a: BlockPos, b: BlockPos) -> Self {
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+
+    // Blocks along x, y and z
+    pub fn size(&self) -> (i32, i32, i32) {
+        (
+            self.max.0 - self.min.0 + 1,
+            self.max.1 - self.min.1 + 1,
+            self.max.2 - self.min.2 + 1,
+        )
+    }
+
+    pub fn volume(&self) -> usize {
+        let (sx, sy, sz) = self.size();
+        sx as usize * sy as usize * sz as usize
+    }
+
+    pub fn contains(&self, (x, y, z): BlockPos) -> bool {
+        (self.min.0..=self.max.0).contains(&x)
+            && (self.min.1..=self.max.1).contains(&y)
+            && (self.min.2..=self.max.2).contains(&z)
+    }
+
+    // Is the position on one of the six faces of the box
+    pub fn is_edge(&self, (x, y, z): BlockPos) -> bool {
+        x == self.min.0
+            || x == self.max.0
+            || y == self.min.1
+            || y == self.max.1
+            || z == self.min.2
+            || z == self.max.2
+    }
+
+    // Every position in the box, x fastest, then z, then y
+    pub fn positions(&self) -> impl Iterator<Item = BlockPos> + use<> {
+        let Region { min, max } = *self;
+        (min.1..=max.1).flat_map(move |y| {
+            (min.2..=max.2).flat_map(move |z| (min.0..=max.0).map(move |x| (x, y, z)))
+        })
+    }
+}
+
+// The two corners picked so far, one click each
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub first: Option<BlockPos>,
+    pub second: Option<BlockPos>,
+}
+
+impl Selection {
+    // None until both corners are picked
+    pub fn region(&self) -> Option<Region> {
+        Some(Region::new(self.first?, self.second?))
+    }
+}
+
+// One transaction, however many blocks
+fn apply(world: &mut World, blocks: impl IntoIterator<Item = (BlockPos, Block)>) -> usize {
+    world.transaction(|world| world.set_blocks(blocks))
+}
+
+// Sets every block in the region. Returns how many blocks were set.
+pub fn fill(world: &mut World, region: Region, block: Block) -> usize {
+    apply(world, region.positions().map(|pos| (pos, block)))
+}
+
+// Sets the faces of the region, leaving the inside as it is
+pub fn hollow_box(world: &mut World, region: Region, block: Block) -> usize {
+    let shell = region.positions().filter(|&pos| region.is_edge(pos));
+    apply(world, shell.map(|pos| (pos, block)))
+}
+
+// Sets every block whose centre is within radius of the centre of the given
+// block
+pub fn sphere(world: &mut World, centre: BlockPos, radius: f32, block: Block) -> usize {
+    let reach = radius.max(0.0).floor() as i32;
+    let (cx, cy, cz) = centre;
+    let bounds = Region::new(
+        (cx - reach, cy - reach, cz - reach),
+        (cx + reach, cy + reach, cz + reach),
+    );
+    let inside = bounds.positions().filter(|&(x, y, z)| {
+        let (dx, dy, dz) = ((x - cx) as f32, (y - cy) as f32, (z - cz) as f32);
+        dx * dx + dy * dy + dz * dz <= radius * radius
+    });
+    apply(world, inside.map(|pos| (pos, block)))
+}
+
+// Turns every block of one type in the region into another block
+pub fn replace(world: &mut World, region: Region, from: BlockType, to: Block) -> usize {
+    let matching: Vec<BlockPos> = region
+        .positions()
+        .filter(|&(x, y, z)| {
+            world
+                .get_block(x, y, z)
+                .is_some_and(|block| block.block_type == from)
+        })
+        .collect();
+    apply(world, matching.into_iter().map(|pos| (pos, to)))
+}
+
+// Axes a clipboard can be mirrored along
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Z,
+}
+
+// Blocks copied out of a region, to be pasted somewhere else. Cells that
+// were in chunks that weren't loaded are None and left alone when pasting.
+#[derive(Clone, Debug)]
+pub struct Clipboard {
+    size: (i32, i32, i32),
+    // x fastest, then z, then y, like Region::positions
+    blocks: Vec<Option<Block>>,
+}
+
+impl Clipboard {
+    pub fn size(&self) -> (i32, i32, i32) {
+        self.size
+    }
+
+    // The block at a position relative to the lowest corner
+    pub fn get(&self, x: i32, y: i32, z: i32) -> Option<Block> {
+        let (sx, sy, sz) = self.size;
+        if !(0..sx).contains(&x) || !(0..sy).contains(&y) || !(0..sz).contains(&z) {
+            return None;
+        }
+        self.blocks[((y * sz + z) * sx + x) as usize]
+    }
+
+    // Builds a clipboard of the given size from what each new cell holds
+    fn remap(size: (i32, i32, i32), cell: impl Fn(i32, i32, i32) -> Option<Block>) -> Self {
+        let blocks = Region::new((0, 0, 0), (size.0 - 1, size.1 - 1, size.2 - 1))
+            .positions()
+            .map(|(x, y, z)| cell(x, y, z))
+            .collect();
+        Self { size, blocks }
+    }
+
+    // Turned about the y axis by quarter turns, clockwise seen from above:
+    // what faced north (-z) faces east (+x) after one turn
+    pub fn rotated(&self, quarter_turns: i32) -> Self {
+        let mut rotated = self.clone();
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            let (sx, sy, sz) = rotated.size;
+            rotated = Self::remap((sz, sy, sx), |x, y, z| rotated.get(z, y, sz - 1 - x));
+        }
+        rotated
+    }
+
+    // Flipped end to end along an axis
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        let (sx, _, sz) = self.size;
+        Self::remap(self.size, |x, y, z| match axis {
+            Axis::X => self.get(sx - 1 - x, y, z),
+            Axis::Z => self.get(x, y, sz - 1 - z),
+        })
+    }
+}
+
+pub fn copy(world: &World, region: Region) -> Clipboard {
+    let blocks = region
+        .positions()
+        .map(|(x, y, z)| world.get_block(x, y, z))
+        .collect();
+    Clipboard {
+        size: region.size(),
+        blocks,
+    }
+}
+
+// Copies the region, then empties it
+pub fn cut(world: &mut World, region: Region) -> Clipboard {
+    let clipboard = copy(world, region);
+    fill(world, region, Block::new(BlockType::Air));
+    clipboard
+}
+
+// Sets the clipboard's blocks with its lowest corner at origin, air
+// included
+pub fn paste(world: &mut World, clipboard: &Clipboard, origin: BlockPos) -> usize {
+    let (sx, sy, sz) = clipboard.size;
+    let (ox, oy, oz) = origin;
+    let target = Region::new(origin, (ox + sx - 1, oy + sy - 1, oz + sz - 1));
+    let blocks = target.positions().filter_map(|(x, y, z)| {
+        let block = clipboard.get(x - ox, y - oy, z - oz)?;
+        Some(((x, y, z), block))
+    });
+    apply(world, blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_world::{block_at, empty_world};
+
+    fn count(world: &World, region: Region, block_type: BlockType) -> usize {
+        region
+            .positions()
+            .filter(|&(x, y, z)| block_at(world, x, y, z) == block_type)
+            .count()
+    }
+
+    #[test]
+    fn regions_take_corners_in_any_order() {
+        let region = Region::new((3, 10, -2), (-1, 8, 4));
+        assert_eq!(region.min, (-1, 8, -2));
+        assert_eq!(region.max, (3, 10, 4));
+        assert_eq!(region.size(), (5, 3, 7));
+        assert_eq!(region.positions().count(), region.volume());
+        assert!(region.positions().all(|pos| region.contains(pos)));
+
+        let mut selection = Selection {
+            first: Some((0, 0, 0)),
+            second: None,
+        };
+        assert_eq!(selection.region(), None);
+        selection.second = Some((1, 1, 1));
+        assert_eq!(selection.region().unwrap().volume(), 8);
+    }
+
+    #[test]
+    fn shapes_set_the_right_blocks() {
+        let mut world = empty_world(-2..=1, -2..=1);
+        let region = Region::new((0, 10, 0), (4, 14, 4));
+        assert_eq!(fill(&mut world, region, Block::new(BlockType::Stone)), 125);
+        assert_eq!(count(&world, region, BlockType::Stone), 125);
+
+        let shell = Region::new((-10, 10, -10), (-6, 14, -6));
+        assert_eq!(
+            hollow_box(&mut world, shell, Block::new(BlockType::Planks)),
+            125 - 27
+        );
+        assert_eq!(block_at(&world, -8, 12, -8), BlockType::Air);
+        assert_eq!(block_at(&world, -10, 12, -8), BlockType::Planks);
+
+        // A radius of one is the block and its six neighbours
+        assert_eq!(
+            sphere(&mut world, (10, 20, 10), 1.0, Block::new(BlockType::Dirt)),
+            7
+        );
+        assert_eq!(block_at(&world, 10, 21, 10), BlockType::Dirt);
+        assert_eq!(block_at(&world, 11, 21, 10), BlockType::Air);
+
+        world.set_block(2, 12, 2, Block::new(BlockType::Dirt));
+        assert_eq!(
+            replace(
+                &mut world,
+                region,
+                BlockType::Stone,
+                Block::new(BlockType::Sand)
+            ),
+            124
+        );
+        assert_eq!(block_at(&world, 2, 12, 2), BlockType::Dirt);
+        assert_eq!(count(&world, region, BlockType::Sand), 124);
+    }
+
+    #[test]
+    fn bulk_edits_remesh_each_chunk_once() {
+        let mut world = empty_world(-2..=1, -2..=1);
+        world.take_changed_chunks();
+        // Inside four chunks, away from the edges of the other twelve
+        let region = Region::new((-14, 0, -14), (13, 100, 13));
+        fill(&mut world, region, Block::new(BlockType::Stone));
+        let mut changed = world.take_changed_chunks();
+        changed.sort();
+        assert_eq!(changed, vec![(-1, -1), (-1, 0), (0, -1), (0, 0)]);
+    }
+
+    #[test]
+    fn cut_and_paste_moves_a_build() {
+        let mut world = empty_world(-2..=1, -2..=1);
+        world.set_block(0, 10, 0, Block::new(BlockType::OakLog));
+        world.set_block(0, 11, 0, Block::new(BlockType::Leaves));
+        let region = Region::new((0, 10, 0), (0, 11, 0));
+        let clipboard = cut(&mut world, region);
+        assert_eq!(count(&world, region, BlockType::Air), 2);
+
+        assert_eq!(paste(&mut world, &clipboard, (5, 20, 5)), 2);
+        assert_eq!(block_at(&world, 5, 20, 5), BlockType::OakLog);
+        assert_eq!(block_at(&world, 5, 21, 5), BlockType::Leaves);
+    }
+
+    #[test]
+    fn clipboards_rotate_and_mirror() {
+        let mut world = empty_world(-2..=1, -2..=1);
+        // An L along x, with its foot towards +z
+        world.set_block(0, 10, 0, Block::new(BlockType::Stone));
+        world.set_block(1, 10, 0, Block::new(BlockType::Stone));
+        world.set_block(2, 10, 0, Block::new(BlockType::Stone));
+        world.set_block(2, 10, 1, Block::new(BlockType::Dirt));
+        let clipboard = copy(&world, Region::new((0, 10, 0), (2, 10, 1)));
+
+        let turned = clipboard.rotated(1);
+        assert_eq!(turned.size(), (2, 1, 3));
+        // The arm now runs along z, the foot towards -x
+        assert_eq!(turned.get(1, 0, 0).unwrap().block_type, BlockType::Stone);
+        assert_eq!(turned.get(1, 0, 2).unwrap().block_type, BlockType::Stone);
+        assert_eq!(turned.get(0, 0, 2).unwrap().block_type, BlockType::Dirt);
+        assert_eq!(turned.get(0, 0, 0).unwrap().block_type, BlockType::Air);
+
+        // Four turns, or one and a reverse one, change nothing
+        for same in [clipboard.rotated(4), clipboard.rotated(1).rotated(-1)] {
+            assert_eq!(same.size(), clipboard.size());
+            assert_eq!(same.get(2, 0, 1).unwrap().block_type, BlockType::Dirt);
+        }
+
+        let mirrored = clipboard.mirrored(Axis::X);
+        assert_eq!(mirrored.get(0, 0, 1).unwrap().block_type, BlockType::Dirt);
+        let mirrored = clipboard.mirrored(Axis::Z);
+        assert_eq!(mirrored.get(2, 0, 0).unwrap().block_type, BlockType::Dirt);
+        assert_eq!(mirrored.get(2, 0, 1).unwrap().block_type, BlockType::Stone);
+    }
+}
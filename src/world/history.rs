@@ -0,0 +1,570 @@
+// Player edits as a log of transactions, each the old and new block of
+// every position one click or bulk edit changed, so they can be undone and
+// redone. Only the newest transactions are kept for undoing, up to a total
+// number of block changes. Chunks aren't saved, so the log also keeps what
+// players made of every block they edited, or what the simulation made of
+// it since, and puts it back when the chunk is generated again. A block
+// edited back to what it was before is forgotten.
+use crate::block::Block;
+use crate::chunk::CHUNK_SIZE;
+use crate::dimension::DimensionId;
+use crate::save::SaveError;
+use crate::world::BlockPos;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod file;
+
+// Block changes kept for undo and redo before the oldest are forgotten
+pub const DEFAULT_MAX_HISTORY_CHANGES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockChange {
+    pub pos: BlockPos,
+    pub old: Block,
+    pub new: Block,
+}
+
+// Everything one edit changed, in the dimension it was made in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub dimension: DimensionId,
+    pub changes: Vec<BlockChange>,
+}
+
+// A block players edited: what they left there and what it was before
+// their first edit
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Edit {
+    before: Block,
+    block: Block,
+}
+
+type ChunkEdits = HashMap<(DimensionId, (i32, i32)), HashMap<BlockPos, Edit>>;
+
+#[derive(Debug)]
+pub struct EditHistory {
+    // Oldest first
+    undo: VecDeque<Transaction>,
+    // The next one to redo last
+    redo: Vec<Transaction>,
+    // Block changes in undo and redo together
+    changes: usize,
+    pub max_changes: usize,
+    // The edited blocks by dimension and chunk
+    edits: ChunkEdits,
+    // The transaction being made, with where each position is in it
+    recording: Option<(Transaction, HashMap<BlockPos, usize>)>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            changes: 0,
+            max_changes: DEFAULT_MAX_HISTORY_CHANGES,
+            edits: HashMap::new(),
+            recording: None,
+        }
+    }
+}
+
+fn chunk_of((x, _, z): BlockPos) -> (i32, i32) {
+    (
+        x.div_euclid(CHUNK_SIZE as i32),
+        z.div_euclid(CHUNK_SIZE as i32),
+    )
+}
+
+impl EditHistory {
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Starts a transaction, unless one is already being made
+    pub fn begin(&mut self, dimension: DimensionId) -> bool {
+        if self.recording.is_some() {
+            return false;
+        }
+        let transaction = Transaction {
+            dimension,
+            changes: Vec::new(),
+        };
+        self.recording = Some((transaction, HashMap::new()));
+        true
+    }
+
+    // A block set while recording. Setting a position twice keeps the block
+    // from before the first time.
+    pub fn record(&mut self, pos: BlockPos, old: Block, new: Block) {
+        let Some((transaction, index)) = &mut self.recording else {
+            return;
+        };
+        match index.get(&pos) {
+            Some(&i) => transaction.changes[i].new = new,
+            None if old == new => {}
+            None => {
+                index.insert(pos, transaction.changes.len());
+                transaction.changes.push(BlockChange { pos, old, new });
+            }
+        }
+    }
+
+    // Ends the transaction being made, making it the next to undo. Anything
+    // undone before can't be redone any more.
+    pub fn commit(&mut self) {
+        let Some((transaction, _)) = self.recording.take() else {
+            return;
+        };
+        if transaction.changes.is_empty() {
+            return;
+        }
+        self.remember(&transaction, false);
+        self.changes -= self.redo.drain(..).map(|t| t.changes.len()).sum::<usize>();
+        self.changes += transaction.changes.len();
+        self.undo.push_back(transaction);
+        self.trim();
+    }
+
+    // The next transaction to undo if it was made in this dimension. Hand it
+    // back to undone once its old blocks are set.
+    pub fn take_undo(&mut self, dimension: DimensionId) -> Option<Transaction> {
+        if self.undo.back()?.dimension != dimension {
+            return None;
+        }
+        self.undo.pop_back()
+    }
+
+    pub fn undone(&mut self, transaction: Transaction) {
+        self.remember(&transaction, true);
+        self.redo.push(transaction);
+    }
+
+    // Same for redoing: take_redo, set the new blocks, then redone
+    pub fn take_redo(&mut self, dimension: DimensionId) -> Option<Transaction> {
+        if self.redo.last()?.dimension != dimension {
+            return None;
+        }
+        self.redo.pop()
+    }
+
+    pub fn redone(&mut self, transaction: Transaction) {
+        self.remember(&transaction, false);
+        self.undo.push_back(transaction);
+    }
+
+    // A block changed outside any transaction, by flowing water, growth or
+    // the like. Where players edited the position the chunk now holds this
+    // block instead of theirs. The edit stays even if the block is back to
+    // what it was before, so undoing it still knows that block.
+    pub fn changed_outside(&mut self, dimension: DimensionId, pos: BlockPos, block: Block) {
+        if let Some(edit) = self
+            .edits
+            .get_mut(&(dimension, chunk_of(pos)))
+            .and_then(|edits| edits.get_mut(&pos))
+        {
+            edit.block = block;
+        }
+    }
+
+    // The blocks players left in a chunk
+    pub fn edits_in(
+        &self,
+        dimension: DimensionId,
+        chunk: (i32, i32),
+    ) -> impl Iterator<Item = (BlockPos, Block)> + '_ {
+        self.edits
+            .get(&(dimension, chunk))
+            .into_iter()
+            .flat_map(|edits| edits.iter().map(|(&pos, edit)| (pos, edit.block)))
+    }
+
+    // Number of blocks players left different from before
+    pub fn edit_count(&self) -> usize {
+        self.edits.values().map(HashMap::len).sum()
+    }
+
+    // Keeps the blocks a transaction set, its old ones when undoing
+    fn remember(&mut self, transaction: &Transaction, undoing: bool) {
+        let key = |change: &BlockChange| (transaction.dimension, chunk_of(change.pos));
+        for change in &transaction.changes {
+            let (from, to) = if undoing {
+                (change.new, change.old)
+            } else {
+                (change.old, change.new)
+            };
+            let edits = self.edits.entry(key(change)).or_default();
+            let before = edits.get(&change.pos).map_or(from, |edit| edit.before);
+            if to == before {
+                edits.remove(&change.pos);
+            } else {
+                edits.insert(change.pos, Edit { before, block: to });
+            }
+        }
+        for change in &transaction.changes {
+            if self.edits.get(&key(change)).is_some_and(HashMap::is_empty) {
+                self.edits.remove(&key(change));
+            }
+        }
+    }
+
+    // Forgets the oldest transactions until the history fits, those left to
+    // undo first and then the furthest ones to redo. The next one to undo
+    // is always kept, even when it alone is over the limit, so an edit can
+    // be taken back right after it was made however big it was.
+    fn trim(&mut self) {
+        while self.changes > self.max_changes && self.undo.len() + self.redo.len() > 1 {
+            let forgotten = if self.undo.len() > 1 {
+                self.undo.pop_front()
+            } else {
+                Some(self.redo.remove(0))
+            };
+            self.changes -= forgotten.map_or(0, |transaction| transaction.changes.len());
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let bytes = fs::read(path).map_err(SaveError::Io)?;
+        let (edits, undo, redo) = file::read(&bytes).map_err(SaveError::Damaged)?;
+        let mut history = Self {
+            edits,
+            ..Self::default()
+        };
+        history.changes = undo
+            .iter()
+            .chain(&redo)
+            .map(|transaction| transaction.changes.len())
+            .sum();
+        history.undo = undo.into();
+        history.redo = redo;
+        history.trim();
+        Ok(history)
+    }
+
+    // An empty history when there is no save yet. A save that can't be read
+    // holds the only copy of the player's builds, so it is moved aside
+    // rather than overwritten by the next save. Failing to move it is an
+    // error.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        match Self::load(path) {
+            Ok(history) => Ok(history),
+            Err(err) => {
+                let aside = damaged_path(path);
+                fs::rename(path, &aside).map_err(SaveError::Io)?;
+                println!(
+                    "{} ({}), moved it to {} and starting without edits",
+                    err,
+                    path.display(),
+                    aside.display()
+                );
+                Ok(Self::default())
+            }
+        }
+    }
+
+    // Written next to the save and then renamed over it, so a crash while
+    // saving leaves the last save whole
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(SaveError::Io)?;
+        }
+        let partial = with_suffix(path, ".partial");
+        fs::write(&partial, file::write(self)).map_err(SaveError::Io)?;
+        fs::rename(&partial, path).map_err(SaveError::Io)
+    }
+}
+
+// The path with more added to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Where a damaged save goes: history.bin.damaged, or history.bin.damaged.1
+// and so on when that is taken
+fn damaged_path(path: &Path) -> PathBuf {
+    let mut aside = with_suffix(path, ".damaged");
+    let mut count = 0;
+    while aside.exists() {
+        count += 1;
+        aside = with_suffix(path, &format!(".damaged.{}", count));
+    }
+    aside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockType;
+    use crate::test_world::{FloorGenerator, block_at, generated_world};
+    use crate::world::World;
+    use crate::world::edit::{self, Region};
+
+    fn click(world: &mut World, (x, y, z): BlockPos, block_type: BlockType) {
+        world.transaction(|world| world.set_block(x, y, z, Block::new(block_type)));
+    }
+
+    #[test]
+    fn clicks_and_bulk_edits_undo_in_one_step() {
+        let mut world = generated_world(-1..=0);
+        click(&mut world, (1, 10, 1), BlockType::Stone);
+        let region = Region::new((-4, 20, -4), (3, 23, 3));
+        edit::fill(&mut world, region, Block::new(BlockType::Planks));
+        // Simulation isn't part of any edit
+        world.set_block(5, 10, 5, Block::new(BlockType::Sand));
+        assert_eq!(world.history.undo_len(), 2);
+
+        assert_eq!(world.undo(), Some(region.volume()));
+        assert_eq!(block_at(&world, 0, 21, 0), BlockType::Air);
+        assert_eq!(block_at(&world, 1, 10, 1), BlockType::Stone);
+        assert_eq!(world.undo(), Some(1));
+        assert_eq!(block_at(&world, 1, 10, 1), BlockType::Air);
+        assert_eq!(block_at(&world, 5, 10, 5), BlockType::Sand);
+        assert_eq!(world.undo(), None);
+
+        assert_eq!(world.redo(), Some(1));
+        assert_eq!(block_at(&world, 1, 10, 1), BlockType::Stone);
+        // A new edit drops what was left to redo
+        click(&mut world, (2, 10, 2), BlockType::Dirt);
+        assert_eq!(world.redo(), None);
+        assert_eq!(world.history.redo_len(), 0);
+    }
+
+    #[test]
+    fn setting_a_block_twice_undoes_to_the_first_block() {
+        let mut world = generated_world(-1..=0);
+        world.transaction(|world| {
+            world.set_block(0, 5, 0, Block::new(BlockType::Stone));
+            world.set_block(0, 5, 0, Block::new(BlockType::Dirt));
+            // Nested transactions join the outer one
+            click(world, (1, 5, 0), BlockType::Stone);
+        });
+        assert_eq!(world.history.undo_len(), 1);
+        world.undo();
+        assert_eq!(block_at(&world, 0, 5, 0), BlockType::Air);
+        assert_eq!(block_at(&world, 1, 5, 0), BlockType::Air);
+        world.redo();
+        assert_eq!(block_at(&world, 0, 5, 0), BlockType::Dirt);
+    }
+
+    #[test]
+    fn old_transactions_are_forgotten_past_the_limit() {
+        let mut world = generated_world(-1..=0);
+        world.history.max_changes = 10;
+        for x in 0..4 {
+            let region = Region::new((x, 0, 0), (x, 2, 0));
+            edit::fill(&mut world, region, Block::new(BlockType::Stone));
+        }
+        // Only the last three edits of three blocks fit in ten
+        assert_eq!(world.history.undo_len(), 3);
+        for _ in 0..3 {
+            assert!(world.undo().is_some());
+        }
+        assert_eq!(world.undo(), None);
+        assert_eq!(block_at(&world, 0, 1, 0), BlockType::Stone);
+        assert_eq!(block_at(&world, 1, 1, 0), BlockType::Air);
+    }
+
+    #[test]
+    fn an_edit_over_the_limit_can_still_be_undone() {
+        let mut world = generated_world(-1..=0);
+        world.history.max_changes = 4;
+        click(&mut world, (0, 5, 0), BlockType::Stone);
+        let region = Region::new((-2, 10, -2), (1, 10, 1));
+        edit::fill(&mut world, region, Block::new(BlockType::Planks));
+        assert_eq!(world.history.undo_len(), 1);
+        assert_eq!(world.undo(), Some(16));
+        assert_eq!(block_at(&world, 0, 10, 0), BlockType::Air);
+        assert_eq!(world.undo(), None);
+
+        // Until the next edit, which leaves no room for it
+        assert_eq!(world.redo(), Some(16));
+        click(&mut world, (0, 6, 0), BlockType::Stone);
+        assert_eq!(world.history.undo_len(), 1);
+        assert_eq!(world.undo(), Some(1));
+        assert_eq!(world.undo(), None);
+    }
+
+    #[test]
+    fn blocks_edited_back_are_forgotten() {
+        let mut world = generated_world(-1..=0);
+        let region = Region::new((-2, 10, -2), (1, 12, 1));
+        edit::fill(&mut world, region, Block::new(BlockType::Planks));
+        assert_eq!(world.history.edit_count(), region.volume());
+        world.undo();
+        assert_eq!(world.history.edit_count(), 0);
+        world.redo();
+        assert_eq!(world.history.edit_count(), region.volume());
+
+        // Clearing them by hand counts too, and so does putting a block
+        // back after another edit
+        edit::fill(&mut world, region, Block::new(BlockType::Air));
+        assert_eq!(world.history.edit_count(), 0);
+        click(&mut world, (5, 5, 5), BlockType::Stone);
+        click(&mut world, (5, 5, 5), BlockType::Dirt);
+        click(&mut world, (5, 5, 5), BlockType::Air);
+        assert_eq!(world.history.edit_count(), 0);
+        assert!(world.history.edits.is_empty());
+    }
+
+    #[test]
+    fn simulation_changes_to_edited_blocks_are_kept() {
+        let mut world = generated_world(-1..=0);
+        click(&mut world, (2, 10, 2), BlockType::OakSapling);
+        click(&mut world, (3, 10, 2), BlockType::Sand);
+        // The sapling grows and the sand falls away
+        world.set_block(2, 10, 2, Block::new(BlockType::OakLog));
+        world.set_block(3, 10, 2, Block::new(BlockType::Air));
+        // Blocks nobody edited stay out of the history
+        world.set_block(4, 10, 2, Block::new(BlockType::Stone));
+        let mut edits: Vec<_> = world
+            .history
+            .edits_in(DimensionId::Overworld, (0, 0))
+            .collect();
+        edits.sort_by_key(|&(pos, _)| pos);
+        assert_eq!(
+            edits,
+            [
+                ((2, 10, 2), Block::new(BlockType::OakLog)),
+                ((3, 10, 2), Block::new(BlockType::Air)),
+            ]
+        );
+
+        // Undoing still goes back to before the edit
+        assert_eq!(world.undo(), Some(1));
+        assert_eq!(block_at(&world, 3, 10, 2), BlockType::Air);
+        assert_eq!(world.undo(), Some(1));
+        assert_eq!(block_at(&world, 2, 10, 2), BlockType::Air);
+        assert_eq!(world.history.edit_count(), 0);
+        assert_eq!(world.redo(), Some(1));
+        assert_eq!(block_at(&world, 2, 10, 2), BlockType::OakSapling);
+        assert_eq!(world.history.edit_count(), 1);
+    }
+
+    #[test]
+    fn history_and_edits_survive_a_save_and_load() {
+        let path = std::env::temp_dir().join(format!("history-{}.bin", std::process::id()));
+        let mut world = generated_world(-1..=0);
+        click(&mut world, (3, 10, -3), BlockType::Planks);
+        click(&mut world, (4, 10, -3), BlockType::Cobblestone);
+        world.undo();
+        world.history.save(&path).unwrap();
+
+        let mut loaded = World::new();
+        loaded.history = EditHistory::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        loaded.add_dimension(
+            DimensionId::Overworld,
+            Box::new(FloorGenerator(BlockType::Air)),
+        );
+        loaded.load_chunk(0, -1);
+        // Chunks are generated again with the edits in them
+        assert_eq!(block_at(&loaded, 3, 10, -3), BlockType::Planks);
+        assert_eq!(block_at(&loaded, 4, 10, -3), BlockType::Air);
+
+        assert_eq!(loaded.redo(), Some(1));
+        assert_eq!(block_at(&loaded, 4, 10, -3), BlockType::Cobblestone);
+        loaded.undo();
+        assert_eq!(loaded.undo(), Some(1));
+        assert_eq!(block_at(&loaded, 3, 10, -3), BlockType::Air);
+        assert_eq!(loaded.history.edit_count(), 0);
+    }
+
+    #[test]
+    fn big_fills_save_as_a_few_runs() {
+        let path = std::env::temp_dir().join(format!("history-fill-{}.bin", std::process::id()));
+        let mut world = generated_world(-1..=0);
+        world.history.max_changes = 0;
+        let region = Region::new((-16, 0, -16), (15, 63, 15));
+        edit::fill(&mut world, region, Block::new(BlockType::Stone));
+        world.set_block(3, 70, 3, Block::new(BlockType::Dirt));
+        click(&mut world, (3, 70, 3), BlockType::Wheat);
+        world.history.save(&path).unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+        let loaded = EditHistory::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        // One run per chunk for the fill, and the wheat remembers the dirt
+        // under it was there before
+        assert!(size < 1_000, "{} bytes", size);
+        assert_eq!(loaded.edit_count(), region.volume() + 1);
+        let edits: HashMap<BlockPos, Edit> = loaded
+            .edits
+            .values()
+            .flat_map(|edits| edits.iter().map(|(&pos, &edit)| (pos, edit)))
+            .collect();
+        assert_eq!(edits[&(-16, 0, 15)].block, Block::new(BlockType::Stone));
+        assert_eq!(edits[&(15, 63, -16)].before, Block::new(BlockType::Air));
+        assert_eq!(
+            edits[&(3, 70, 3)],
+            Edit {
+                before: Block::new(BlockType::Dirt),
+                block: Block::new(BlockType::Wheat),
+            }
+        );
+        assert_eq!(loaded.undo_len(), 1);
+    }
+
+    #[test]
+    fn damaged_saves_are_refused() {
+        let mut world = generated_world(-1..=0);
+        click(&mut world, (1, 2, 3), BlockType::Planks);
+        let bytes = file::write(&world.history);
+        assert!(file::read(&bytes).is_ok());
+        for cut in [0, 4, bytes.len() / 2, bytes.len() - 1] {
+            assert!(file::read(&bytes[..cut]).is_err());
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(file::read(&longer).is_err());
+    }
+
+    #[test]
+    fn damaged_saves_are_moved_aside_rather_than_overwritten() {
+        let dir = std::env::temp_dir().join(format!("history-damaged-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.bin");
+        fs::write(&path, b"VXHIST damaged").unwrap();
+        fs::write(dir.join("history.bin.damaged"), b"older").unwrap();
+
+        let history = EditHistory::load_or_default(&path).unwrap();
+        assert_eq!(history.edit_count(), 0);
+        assert!(!path.exists());
+        let aside = fs::read(dir.join("history.bin.damaged.1")).unwrap();
+        assert_eq!(aside, b"VXHIST damaged");
+        assert_eq!(fs::read(dir.join("history.bin.damaged")).unwrap(), b"older");
+
+        // Saving leaves only the new history and what was moved aside
+        history.save(&path).unwrap();
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(
+            names,
+            [
+                "history.bin",
+                "history.bin.damaged",
+                "history.bin.damaged.1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+// The edit history as saved: a binary file rather than TOML, as a big build
+// is millions of edited blocks. Each different block is written once in a
+// palette and then referred to by its index. Edits go chunk by chunk, as
+// runs of positions next to each other along x, then z, then y that hold
+// the same edit, so a filled box takes a few bytes per row.
+use super::{BlockChange, ChunkEdits, Edit, EditHistory, Transaction};
+use crate::block::{Block, BlockType};
+use crate::chunk::{CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::dimension::DimensionId;
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use serde::de::value::{Error, StrDeserializer};
+use std::collections::HashMap;
+
+const MAGIC: &[u8] = b"VXHIST";
+const VERSION: u8 = 1;
+const CHUNK_VOLUME: u32 = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT) as u32;
+
+pub type Loaded = (ChunkEdits, Vec<Transaction>, Vec<Transaction>);
+
+// Position of a block within its chunk, counting along x, then z, then y
+fn index_in_chunk((x, y, z): (i32, i32, i32)) -> u32 {
+    let size = CHUNK_SIZE as i32;
+    ((y * size + z.rem_euclid(size)) * size + x.rem_euclid(size)) as u32
+}
+
+fn position_in_chunk((chunk_x, chunk_z): (i32, i32), index: u32) -> (i32, i32, i32) {
+    let size = CHUNK_SIZE as u32;
+    let x = (index % size) as i32 + chunk_x * CHUNK_SIZE as i32;
+    let z = (index / size % size) as i32 + chunk_z * CHUNK_SIZE as i32;
+    (x, (index / (size * size)) as i32, z)
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    palette: Vec<Block>,
+    indices: HashMap<(BlockType, u8), u32>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn name(&mut self, name: &str) {
+        self.u8(name.len() as u8);
+        self.bytes.extend_from_slice(name.as_bytes());
+    }
+
+    // The block's place in the palette, adding it the first time
+    fn block(&mut self, block: Block) -> u32 {
+        let next = self.palette.len() as u32;
+        let index = *self
+            .indices
+            .entry((block.block_type, block.level))
+            .or_insert(next);
+        if index == next {
+            self.palette.push(block);
+        }
+        index
+    }
+
+    fn transactions<'t>(&mut self, transactions: impl ExactSizeIterator<Item = &'t Transaction>) {
+        self.u32(transactions.len() as u32);
+        for transaction in transactions {
+            self.name(transaction.dimension.name());
+            self.u32(transaction.changes.len() as u32);
+            for change in &transaction.changes {
+                let (old, new) = (self.block(change.old), self.block(change.new));
+                self.i32(change.pos.0);
+                self.i32(change.pos.1);
+                self.i32(change.pos.2);
+                self.u32(old);
+                self.u32(new);
+            }
+        }
+    }
+}
+
+pub fn write(history: &EditHistory) -> Vec<u8> {
+    let mut body = Writer::default();
+
+    // Same file for the same edits, whatever the hash maps do
+    let mut chunks: Vec<_> = history.edits.iter().collect();
+    chunks.sort_by_key(|&(&(dimension, chunk), _)| (dimension.name(), chunk));
+    body.u32(chunks.len() as u32);
+    for (&(dimension, chunk), edits) in chunks {
+        let mut blocks: Vec<(u32, Edit)> = edits
+            .iter()
+            .map(|(&pos, &edit)| (index_in_chunk(pos), edit))
+            .collect();
+        blocks.sort_by_key(|&(index, _)| index);
+        // Runs of the same edit at consecutive indices
+        let mut runs: Vec<(u32, u32, Edit)> = Vec::new();
+        for (index, edit) in blocks {
+            match runs.last_mut() {
+                Some((start, length, last)) if *start + *length == index && *last == edit => {
+                    *length += 1;
+                }
+                _ => runs.push((index, 1, edit)),
+            }
+        }
+
+        body.name(dimension.name());
+        body.i32(chunk.0);
+        body.i32(chunk.1);
+        body.u32(runs.len() as u32);
+        for (start, length, edit) in runs {
+            let (before, block) = (body.block(edit.before), body.block(edit.block));
+            body.u32(start);
+            body.u32(length);
+            body.u32(before);
+            body.u32(block);
+        }
+    }
+    body.transactions(history.undo.iter());
+    body.transactions(history.redo.iter());
+
+    // The palette goes first so reading can look blocks up straight away
+    let mut file = Writer::default();
+    file.bytes.extend_from_slice(MAGIC);
+    file.u8(VERSION);
+    file.u32(body.palette.len() as u32);
+    for block in &body.palette {
+        file.name(&format!("{:?}", block.block_type));
+        file.u8(block.level);
+    }
+    file.bytes.extend_from_slice(&body.bytes);
+    file.bytes
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    palette: Vec<Block>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("file ends early".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn name(&mut self) -> Result<&'a str, String> {
+        let length = self.u8()? as usize;
+        std::str::from_utf8(self.take(length)?).map_err(|e| e.to_string())
+    }
+
+    fn dimension(&mut self) -> Result<DimensionId, String> {
+        let name = self.name()?;
+        DimensionId::from_name(name).ok_or_else(|| format!("unknown dimension '{}'", name))
+    }
+
+    fn block(&mut self) -> Result<Block, String> {
+        let index = self.u32()?;
+        self.palette
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("block {} is not in the palette", index))
+    }
+
+    fn transactions(&mut self) -> Result<Vec<Transaction>, String> {
+        let count = self.u32()?;
+        let mut transactions = Vec::new();
+        for _ in 0..count {
+            let dimension = self.dimension()?;
+            let mut changes = Vec::new();
+            for _ in 0..self.u32()? {
+                let pos = (self.i32()?, self.i32()?, self.i32()?);
+                let (old, new) = (self.block()?, self.block()?);
+                changes.push(BlockChange { pos, old, new });
+            }
+            transactions.push(Transaction { dimension, changes });
+        }
+        Ok(transactions)
+    }
+}
+
+pub fn read(bytes: &[u8]) -> Result<Loaded, String> {
+    let mut reader = Reader {
+        bytes,
+        palette: Vec::new(),
+    };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("not an edit history".to_string());
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(format!("unknown version {}", version));
+    }
+
+    for _ in 0..reader.u32()? {
+        let name = reader.name()?;
+        let deserializer: StrDeserializer<Error> = name.into_deserializer();
+        let block_type = BlockType::deserialize(deserializer)
+            .map_err(|_| format!("unknown block type '{}'", name))?;
+        let level = reader.u8()?;
+        reader.palette.push(Block { block_type, level });
+    }
+
+    let mut edits = ChunkEdits::new();
+    for _ in 0..reader.u32()? {
+        let dimension = reader.dimension()?;
+        let chunk = (reader.i32()?, reader.i32()?);
+        let blocks = edits.entry((dimension, chunk)).or_default();
+        for _ in 0..reader.u32()? {
+            let (start, length) = (reader.u32()?, reader.u32()?);
+            let edit = Edit {
+                before: reader.block()?,
+                block: reader.block()?,
+            };
+            if start
+                .checked_add(length)
+                .is_none_or(|end| end > CHUNK_VOLUME)
+            {
+                return Err(format!("run {}+{} is outside its chunk", start, length));
+            }
+            for index in start..start + length {
+                blocks.insert(position_in_chunk(chunk, index), edit);
+            }
+        }
+    }
+    let undo = reader.transactions()?;
+    let redo = reader.transactions()?;
+    if !reader.bytes.is_empty() {
+        return Err("unexpected data at the end".to_string());
+    }
+    Ok((edits, undo, redo))
+}
@@ -0,0 +1,116 @@
+// Golden hashes of generated chunks. Generation must give identical worlds
+// on every platform and run, so any change to these hashes means existing
+// worlds would change too. If that is intended, update the hashes in the
+// same commit and say so in its message.
+
+use voxel_engine::chunk::{CHUNK_HEIGHT, CHUNK_SIZE, Chunk};
+use voxel_engine::sky_islands::SkyIslandGenerator;
+use voxel_engine::terrain::TerrainGenerator;
+use voxel_engine::terrain_config::TerrainConfig;
+
+// FNV-1a over every block in x, y, z order. Stable, unlike std's hashers.
+fn chunk_hash(chunk: &Chunk) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325_u64;
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_SIZE {
+                let block_type = chunk.get_block(x, y, z).unwrap().block_type;
+                hash ^= block_type as u8 as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+            }
+        }
+    }
+    hash
+}
+
+fn overworld_hashes(seed: u32, config: TerrainConfig, chunks: &[(i32, i32)]) -> Vec<u64> {
+    let terrain_gen = TerrainGenerator::new(seed, config);
+    chunks
+        .iter()
+        .map(|&(x, z)| chunk_hash(&terrain_gen.generate_chunk(x, z)))
+        .collect()
+}
+
+#[test]
+fn overworld_matches_golden_hashes() {
+    let chunks = [(0, 0), (-3, 7), (10, -4)];
+    let hashes = overworld_hashes(12345, TerrainConfig::default(), &chunks);
+    assert_eq!(
+        hashes,
+        [
+            10356393801341356797,
+            5252148493634091840,
+            12275017856507046893
+        ]
+    );
+}
+
+#[test]
+fn other_seed_matches_golden_hashes() {
+    let chunks = [(0, 0), (25, 25)];
+    let hashes = overworld_hashes(42, TerrainConfig::default(), &chunks);
+    assert_eq!(hashes, [8453072460065318998, 5706692997009187132]);
+}
+
+#[test]
+fn eroded_overworld_matches_golden_hashes() {
+    let mut config = TerrainConfig::default();
+    config.erosion.enabled = true;
+    let hashes = overworld_hashes(12345, config, &[(1, 1)]);
+    assert_eq!(hashes, [6526357075908295191]);
+}
+
+#[test]
+fn sky_islands_match_golden_hashes() {
+    let sky_gen = SkyIslandGenerator::new(12345, TerrainConfig::default());
+    let hashes: Vec<u64> = [(0, 0), (-6, 2)]
+        .iter()
+        .map(|&(x, z)| chunk_hash(&sky_gen.generate_chunk(x, z)))
+        .collect();
+    assert_eq!(hashes, [836292240642178813, 13007078314067378802]);
+}
+
+// Neighbouring chunks, trees, structures and erosion regions must not depend
+// on which chunk was generated first
+#[test]
+fn generation_order_does_not_matter() {
+    let mut config = TerrainConfig::default();
+    config.erosion.enabled = true;
+    config.erosion.droplets_per_block = 0.2;
+    let chunks: Vec<(i32, i32)> = (2..5).flat_map(|x| (3..6).map(move |z| (x, z))).collect();
+    // Every other chunk first, then the rest backwards
+    let mut scrambled: Vec<(i32, i32)> = chunks.iter().copied().step_by(2).collect();
+    scrambled.extend(chunks.iter().copied().skip(1).step_by(2).rev());
+
+    let forward_gen = TerrainGenerator::new(7, config.clone());
+    let scrambled_gen = TerrainGenerator::new(7, config);
+    let forward: Vec<Chunk> = chunks
+        .iter()
+        .map(|&(x, z)| forward_gen.generate_chunk(x, z))
+        .collect();
+    let mut other: Vec<Chunk> = scrambled
+        .iter()
+        .map(|&(x, z)| scrambled_gen.generate_chunk(x, z))
+        .collect();
+    other.sort_by_key(|chunk| (chunk.x, chunk.z));
+
+    for (a, b) in forward.iter().zip(&other) {
+        assert_eq!((a.x, a.z), (b.x, b.z));
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_HEIGHT {
+                for z in 0..CHUNK_SIZE {
+                    assert_eq!(
+                        a.get_block(x, y, z),
+                        b.get_block(x, y, z),
+                        "chunk ({}, {}) differs at ({}, {}, {})",
+                        a.x,
+                        a.z,
+                        x,
+                        y,
+                        z
+                    );
+                }
+            }
+        }
+    }
+}